@@ -1,7 +1,10 @@
+use crate::energy::Energy;
 use crate::line::LineDirection;
+use crate::passenger::callbacks::{ActionContext, PassengerAction};
 use crate::passenger::{Passenger, PassengerNextStopIndex};
 
 use crate::line::SegmentType;
+use crate::rolling_stock::RollingStock;
 use crate::utils::counter::Counter;
 
 use crate::utils::index_list::IndexList;
@@ -9,11 +12,23 @@ use crate::utils::index_list::IndexList;
 #[derive(Debug)]
 pub struct Car<T> {
     passengers: IndexList<Passenger<T>, PassengerNextStopIndex>,
+    origin: usize,
     destination: usize,
     location: CarLocation,
     direction: LineDirection,
     status: CarStatus,
     counter: Counter,
+    capacity: usize,
+    doors_closed: bool,
+    id: usize,
+    departure_step: usize,
+    route: Option<CarRoute>,
+    door_throughput: Option<usize>,
+    moves: usize,
+    stock: Option<RollingStock>,
+    energy: Energy,
+    held: usize,
+    disrupted: bool,
 }
 
 impl<T> Car<T> {
@@ -25,26 +40,233 @@ impl<T> Car<T> {
         network_size: usize,
         station_len: usize,
     ) -> Self {
+        let origin = match location {
+            CarLocation::Station { station, .. } => station,
+            CarLocation::Segment { .. } => destination,
+        };
         Self {
+            origin,
             destination,
             location,
             direction,
             counter: station_len.into(),
             status: CarStatus::Running,
             passengers: IndexList::new_with_default_index(network_size),
+            capacity: usize::MAX,
+            doors_closed: false,
+            id: 0,
+            departure_step: 0,
+            route: None,
+            door_throughput: None,
+            moves: 0,
+            stock: None,
+            energy: Energy::default(),
+            held: 0,
+            disrupted: false,
         }
     }
 
+    /// Restrict the car to a part of the line. By
+    /// default the car serves the whole line.
+    #[must_use]
+    pub fn set_route(mut self, route: CarRoute) -> Self {
+        self.route = Some(route);
+        self
+    }
+
+    /// Check if the car stops at the given station.
+    #[must_use]
+    pub fn serves(&self, station: usize) -> bool {
+        self.route.as_ref().is_none_or(|r| r.serves(station))
+    }
+
+    /// Branch of the line the car runs on.
+    #[must_use]
+    pub fn get_branch(&self) -> usize {
+        self.route.as_ref().map_or(0, CarRoute::get_branch)
+    }
+
+    /// Directions, identified by their terminus station, of the
+    /// passengers that can board the car: its destination first
+    /// and, running toward the end of a branched line, the other
+    /// branch ends.
+    #[must_use]
+    pub fn boarding_directions(&self) -> Vec<usize> {
+        let mut directions = vec![self.destination];
+        if let Some(route) = &self.route {
+            if route.branch_ends.contains(&self.destination) {
+                let others = route.branch_ends.iter().filter(|e| **e != self.destination);
+                directions.extend(others);
+            }
+        }
+        directions
+    }
+
+    /// Set the car identifier, unique within its line.
+    #[must_use]
+    pub fn set_id(mut self, id: usize) -> Self {
+        self.id = id;
+        self
+    }
+
+    #[must_use]
+    pub fn get_id(&self) -> usize {
+        self.id
+    }
+
+    /// Set the step when the car left the depot.
+    #[must_use]
+    pub fn set_departure_step(mut self, step: usize) -> Self {
+        self.departure_step = step;
+        self
+    }
+
+    #[must_use]
+    pub fn get_departure_step(&self) -> usize {
+        self.departure_step
+    }
+
+    /// Set the number of passengers that can board or alight in a
+    /// step. The car then stays in a station at least as long as it
+    /// takes all the passengers to board and alight, besides the
+    /// station time. By default the dwell time is the station time.
+    #[must_use]
+    pub fn set_door_throughput(mut self, rate: usize) -> Self {
+        self.door_throughput = Some(rate);
+        self
+    }
+
+    /// Number of passengers that boarded or alighted
+    /// at the current stop.
+    #[must_use]
+    pub fn get_moves(&self) -> usize {
+        self.moves
+    }
+
+    /// Set the class of the car, its capacity becomes
+    /// the capacity of the class.
+    #[must_use]
+    pub fn set_rolling_stock(mut self, stock: RollingStock) -> Self {
+        self.capacity = stock.get_capacity();
+        self.stock = Some(stock);
+        self
+    }
+
+    #[must_use]
+    pub fn get_rolling_stock(&self) -> Option<&RollingStock> {
+        self.stock.as_ref()
+    }
+
+    /// Energy used by the car since it started.
+    #[must_use]
+    pub fn get_energy(&self) -> Energy {
+        self.energy
+    }
+
+    pub fn add_energy(&mut self, energy: Energy) {
+        self.energy += energy;
+    }
+
+    /// Number of passengers on board without a seat. Without
+    /// a rolling stock class every passenger is seated.
+    #[must_use]
+    pub fn standing_count(&self) -> usize {
+        self.stock
+            .as_ref()
+            .map_or(0, |s| self.passenger_count().saturating_sub(s.get_seated()))
+    }
+
+    /// Set the maximal number of passengers on board.
+    /// By default the capacity is unlimited.
+    #[must_use]
+    pub fn set_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    #[must_use]
+    pub fn get_capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of passengers that can still board.
+    #[must_use]
+    pub fn free_space(&self) -> usize {
+        self.capacity.saturating_sub(self.passengers.len())
+    }
+
     pub fn set_location(&mut self, loc: CarLocation) {
         self.location = loc;
     }
 
+    /// Passengers alighting at the current station. They must
+    /// all be taken from the returned list.
     pub fn unboard_passengers(&mut self) -> &'_ mut Vec<Passenger<T>> {
-        self.passengers.get_list_mut(self.get_current_station())
+        let station = self.get_current_station();
+        let count = self.passengers.get_list(station).len();
+        self.count_moves(count);
+        self.passengers.get_list_mut(station)
     }
 
-    pub fn board_passengers(&mut self, ps: &mut Vec<Passenger<T>>) {
-        self.passengers.append(ps);
+    /// Board passengers from the front of the given list, as long
+    /// as there is space on the train. Only passengers whose next
+    /// stop is served by the car board. Passengers that do not board
+    /// stay in the list in their original order. Return the number
+    /// of passengers left behind because the car is full.
+    pub fn board_passengers(&mut self, ps: &mut Vec<Passenger<T>>, ctx: &ActionContext) -> usize
+    where
+        T: PassengerAction,
+    {
+        if self.route.is_none() {
+            let count = ps.len().min(self.free_space());
+            let boarded = ps.drain(..count).map(|p| p.board(ctx));
+            self.passengers.append_iter(boarded);
+            self.count_moves(count);
+            return ps.len();
+        }
+        let mut left = 0;
+        let mut boarded = 0;
+        let mut waiting = Vec::new();
+        for p in ps.drain(..) {
+            if !self.serves(p.get_next_stop()) {
+                waiting.push(p);
+            } else if self.free_space() > 0 {
+                self.passengers.push(p.board(ctx));
+                boarded += 1;
+            } else {
+                left += 1;
+                waiting.push(p);
+            }
+        }
+        *ps = waiting;
+        self.count_moves(boarded);
+        left
+    }
+
+    /// Count passengers boarding or alighting and, with a door
+    /// throughput, keep the car in the station until they are done.
+    fn count_moves(&mut self, count: usize) {
+        self.moves += count;
+        if let Some(rate) = self.door_throughput {
+            self.counter.extend_to(self.moves.div_ceil(rate));
+        }
+    }
+
+    /// Return true only once per stop, on the last
+    /// boarding operation before the train leaves.
+    pub fn close_doors(&mut self) -> bool {
+        if self.counter.is_done() && !self.doors_closed {
+            self.doors_closed = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Number of passengers currently on board.
+    #[must_use]
+    pub fn passenger_count(&self) -> usize {
+        self.passengers.len()
     }
 
     #[must_use]
@@ -62,8 +284,16 @@ impl<T> Car<T> {
         self.destination
     }
 
+    /// Change the direction, identified by a station, of the
+    /// passengers boarding the car. On a loop line it is the next
+    /// stop, so it changes at each station.
+    pub fn set_destination(&mut self, destination: usize) {
+        self.destination = destination;
+    }
+
     pub fn change_direction(&mut self) {
         self.direction.swap();
+        std::mem::swap(&mut self.origin, &mut self.destination);
     }
 
     #[must_use]
@@ -91,11 +321,37 @@ impl<T> Car<T> {
 
     #[must_use]
     pub fn run_step(&mut self) -> bool {
+        self.held = self.held.saturating_sub(1);
         self.counter.step()
     }
 
+    /// Keep the car still for the given number of steps,
+    /// as after a failure.
+    pub fn hold(&mut self, steps: usize) {
+        self.held += steps;
+        self.counter.postpone(steps);
+    }
+
+    /// Check if the car is kept still.
+    #[must_use]
+    pub fn is_held(&self) -> bool {
+        self.held > 0
+    }
+
+    /// Mark the car as delayed by a disruption in the last step.
+    pub fn set_disrupted(&mut self, value: bool) {
+        self.disrupted = value;
+    }
+
+    #[must_use]
+    pub fn is_disrupted(&self) -> bool {
+        self.disrupted
+    }
+
     pub fn next_step(&mut self, value: usize, kind: SegmentType, loc: CarLocation) {
         self.counter = value.into();
+        self.doors_closed = false;
+        self.moves = 0;
         self.location = loc;
         self.update_state(kind);
     }
@@ -104,17 +360,120 @@ impl<T> Car<T> {
         matches! {self.status, CarStatus::Swapping}
     }
 
+    /// Turn back on the current segment at the next
+    /// move, as at a terminus.
+    pub fn turn_back(&mut self) {
+        if !self.is_swapping() {
+            self.status.next();
+        }
+    }
+
+    /// Send the passengers on board whose next stop is from, or all
+    /// of them, to the given station instead. Return their number.
+    pub fn redirect_passengers(&mut self, from: Option<usize>, to: usize) -> usize
+    where
+        T: PassengerAction,
+    {
+        let moved = match from {
+            Some(station) => std::mem::take(self.passengers.get_list_mut(station)),
+            None => self.passengers.take_all(),
+        };
+        let count = moved.iter().filter(|p| p.get_next_stop() != to).count();
+        self.passengers
+            .append_iter(moved.into_iter().map(|p| p.set_next_stop(to)));
+        count
+    }
+
     fn update_state(&mut self, kind: SegmentType) {
-        if matches! {kind, SegmentType::Terminus(_)} {
+        let turnback = self
+            .route
+            .as_ref()
+            .is_some_and(|r| r.turns_at(self.direction, self.location.get_segment()));
+        if matches! {kind, SegmentType::Terminus(_)} || turnback || self.is_swapping() {
             match self.status {
                 CarStatus::Running => {}
-                CarStatus::Swapping => self.direction.swap(),
+                CarStatus::Swapping => self.change_direction(),
             }
             self.status.next();
         }
     }
 }
 
+/// Part of a line served by a car: the segments where the car
+/// turns back, the stations where it stops and, on a branched
+/// line, the branch it runs on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CarRoute {
+    turnback: Option<(usize, usize)>,
+    stops: Vec<bool>,
+    branch: usize,
+    branch_ends: Vec<usize>,
+}
+
+impl CarRoute {
+    /// Initialize a route stopping at the given stations,
+    /// stops is indexed by station id.
+    #[must_use]
+    pub fn new(stops: Vec<bool>) -> Self {
+        Self {
+            turnback: None,
+            stops,
+            branch: 0,
+            branch_ends: Vec::new(),
+        }
+    }
+
+    /// Turn back before the termini: turnback contains the segment
+    /// where the car turns back running in ``DirectionA`` and the
+    /// one where it turns back running in ``DirectionB``.
+    #[must_use]
+    pub fn set_turnback(mut self, turnback: (usize, usize)) -> Self {
+        self.turnback = Some(turnback);
+        self
+    }
+
+    #[must_use]
+    pub fn get_turnback(&self) -> Option<(usize, usize)> {
+        self.turnback
+    }
+
+    /// Run on the given branch, 0 being the main line.
+    #[must_use]
+    pub fn set_branch(mut self, branch: usize) -> Self {
+        self.branch = branch;
+        self
+    }
+
+    #[must_use]
+    pub fn get_branch(&self) -> usize {
+        self.branch
+    }
+
+    /// Set the terminus stations of all the line branches. Running
+    /// toward them the car also takes the passengers waiting for
+    /// another branch, when it stops at their next stop.
+    #[must_use]
+    pub fn set_branch_ends(mut self, ends: Vec<usize>) -> Self {
+        self.branch_ends = ends;
+        self
+    }
+
+    #[must_use]
+    pub fn serves(&self, station: usize) -> bool {
+        self.stops.get(station).copied().unwrap_or(false)
+    }
+
+    fn turns_at(&self, direction: LineDirection, segment: usize) -> bool {
+        self.turnback.is_some_and(|(a, b)| {
+            let turnback = match direction {
+                LineDirection::DirectionA => a,
+                LineDirection::DirectionB => b,
+            };
+            turnback == segment
+        })
+    }
+}
+
 #[derive(Debug)]
 enum CarStatus {
     Running,
@@ -169,3 +528,200 @@ impl CarLocation {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::passenger::PassengerFactory;
+    use crate::station::PassengerStation;
+    use crate::traffic_generator::TrafficGenerator;
+    use crate::utils::random;
+
+    struct Platform(Vec<Passenger<()>>);
+
+    impl PassengerStation<()> for Platform {
+        fn enter_passenger(&mut self, p: Passenger<()>) {
+            self.0.push(p);
+        }
+    }
+
+    struct Fixed;
+
+    impl TrafficGenerator for Fixed {
+        fn next_traffic_flow(&self, _: u32, _: &mut random::SimRng) -> u32 {
+            5
+        }
+    }
+
+    fn make_passengers() -> Vec<Passenger<()>> {
+        let mut factory = PassengerFactory::new(vec![vec![None, Some(Fixed)]]);
+        let mut stations = [Platform(vec![])];
+        factory.generate_traffic(0, &mut stations, &mut (), &mut random::make_rng(None));
+        let [Platform(passengers)] = stations;
+        passengers
+    }
+
+    fn make_context(step: u32) -> ActionContext {
+        ActionContext::on_train(step, 0, 0, LineDirection::DirectionB)
+    }
+
+    #[test]
+    fn test_board_capacity() {
+        let mut car: Car<()> = Car::new(
+            1,
+            CarLocation::station(0, 0),
+            LineDirection::DirectionB,
+            2,
+            1,
+        )
+        .set_capacity(3);
+        let mut passengers = make_passengers();
+        assert_eq!(car.board_passengers(&mut passengers, &make_context(0)), 2);
+        let left: Vec<u32> = passengers.iter().map(Passenger::get_id).collect();
+        assert_eq!(left, vec![3, 4]);
+        assert_eq!(car.passenger_count(), 3);
+        assert_eq!(car.free_space(), 0);
+        assert_eq!(passengers.len(), 2);
+
+        assert_eq!(car.board_passengers(&mut passengers, &make_context(1)), 2);
+        assert_eq!(car.passenger_count(), 3);
+    }
+
+    #[test]
+    fn test_board_served_stops() {
+        let route = CarRoute::new(vec![true, false, true]).set_turnback((0, 4));
+        let mut car: Car<()> = Car::new(
+            2,
+            CarLocation::station(0, 0),
+            LineDirection::DirectionB,
+            3,
+            1,
+        )
+        .set_capacity(2)
+        .set_route(route);
+        let mut passengers: Vec<Passenger<()>> = make_passengers()
+            .into_iter()
+            .enumerate()
+            .map(|(i, p)| p.set_next_stop(if i % 2 == 0 { 1 } else { 2 }))
+            .collect();
+        // passengers 1 and 3 stop at 2, the others at 1
+        assert_eq!(car.board_passengers(&mut passengers, &make_context(0)), 0);
+        assert_eq!(car.passenger_count(), 2);
+        let left: Vec<u32> = passengers.iter().map(Passenger::get_id).collect();
+        assert_eq!(left, vec![0, 2, 4]);
+        assert!(car.serves(2));
+        assert!(!car.serves(1));
+    }
+
+    #[test]
+    fn test_branch_boarding_directions() {
+        let route = CarRoute::new(vec![true; 7])
+            .set_branch(1)
+            .set_branch_ends(vec![4, 6]);
+        let mut car: Car<()> = Car::new(
+            6,
+            CarLocation::station(0, 0),
+            LineDirection::DirectionB,
+            7,
+            0,
+        )
+        .set_route(route);
+        assert_eq!(car.get_branch(), 1);
+        assert_eq!(car.boarding_directions(), [6, 4]);
+        car.change_direction();
+        assert_eq!(car.boarding_directions(), [0]);
+    }
+
+    #[test]
+    fn test_short_turn() {
+        let route = CarRoute::new(vec![true; 3]).set_turnback((0, 2));
+        let mut car: Car<()> = Car::new(
+            4,
+            CarLocation::station(0, 0),
+            LineDirection::DirectionB,
+            5,
+            0,
+        )
+        .set_route(route);
+        car.next_step(0, SegmentType::Station(1), CarLocation::station(2, 1));
+        assert!(car.is_swapping());
+        car.next_step(0, SegmentType::Station(1), CarLocation::station(2, 1));
+        assert!(!car.is_swapping());
+        assert_eq!(car.get_current_direction(), LineDirection::DirectionA);
+        assert_eq!(car.get_destination(), 0);
+    }
+
+    #[test]
+    fn test_dwell_time() {
+        let mut car: Car<()> = Car::new(
+            1,
+            CarLocation::station(0, 0),
+            LineDirection::DirectionB,
+            2,
+            1,
+        )
+        .set_door_throughput(2);
+        let mut passengers = make_passengers();
+        car.board_passengers(&mut passengers, &make_context(0));
+        assert_eq!(car.get_moves(), 5);
+        // five passengers need three steps at two per step
+        assert!(!car.run_step());
+        assert!(!car.close_doors());
+        assert!(!car.run_step());
+        assert!(!car.run_step());
+        assert!(car.close_doors());
+        assert!(car.run_step());
+
+        car.next_step(1, SegmentType::Station(1), CarLocation::station(2, 1));
+        assert_eq!(car.get_moves(), 0);
+    }
+
+    #[test]
+    fn test_rolling_stock() {
+        let mut car: Car<()> = Car::new(
+            1,
+            CarLocation::station(0, 0),
+            LineDirection::DirectionB,
+            2,
+            1,
+        )
+        .set_rolling_stock(RollingStock::new("small", 2, 2));
+        assert_eq!(car.get_capacity(), 4);
+        let mut passengers = make_passengers();
+        car.board_passengers(&mut passengers, &make_context(0));
+        assert_eq!(car.passenger_count(), 4);
+        assert_eq!(car.standing_count(), 2);
+        assert_eq!(car.get_rolling_stock().unwrap().get_name(), "small");
+    }
+
+    #[test]
+    fn test_hold() {
+        let mut car: Car<()> =
+            Car::new(1, CarLocation::segment(1), LineDirection::DirectionB, 2, 1);
+        assert!(!car.run_step());
+        car.hold(2);
+        assert!(car.is_held());
+        assert!(!car.run_step());
+        assert!(!car.run_step());
+        assert!(!car.is_held());
+        assert!(car.run_step());
+    }
+
+    #[test]
+    fn test_close_doors() {
+        let mut car: Car<()> = Car::new(
+            1,
+            CarLocation::station(0, 0),
+            LineDirection::DirectionB,
+            2,
+            1,
+        );
+        assert!(!car.close_doors());
+        assert!(!car.run_step());
+        assert!(car.close_doors());
+        assert!(!car.close_doors());
+        car.next_step(1, SegmentType::Line, CarLocation::segment(1));
+        assert!(!car.close_doors());
+    }
+}
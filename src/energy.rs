@@ -0,0 +1,206 @@
+//! Estimate the energy used by the trains. A train running between two
+//! stations accelerates to its cruise speed, or to the top speed the
+//! train kinematics allow on the link, keeps it against the
+//! running resistance and brakes to a stop, recovering part of the
+//! braking energy. Energy is in kWh.
+
+use crate::car::Car;
+use crate::line::kinematics::Motion;
+use crate::rolling_stock::RollingStock;
+
+use serde::Serialize;
+use std::ops::AddAssign;
+
+type Duration = usize;
+
+const JOULE_IN_KWH: f64 = 3.6e6;
+
+/// Energy drawn for traction and recovered by regenerative braking.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+pub struct Energy {
+    traction: f64,
+    regenerated: f64,
+}
+
+impl Energy {
+    #[must_use]
+    pub fn new(traction: f64, regenerated: f64) -> Self {
+        Self {
+            traction,
+            regenerated,
+        }
+    }
+
+    #[must_use]
+    pub fn get_traction(&self) -> f64 {
+        self.traction
+    }
+
+    #[must_use]
+    pub fn get_regenerated(&self) -> f64 {
+        self.regenerated
+    }
+
+    /// Energy drawn from the supply, net of the recovered one.
+    #[must_use]
+    pub fn net(&self) -> f64 {
+        self.traction - self.regenerated
+    }
+}
+
+impl AddAssign for Energy {
+    fn add_assign(&mut self, other: Self) {
+        self.traction += other.traction;
+        self.regenerated += other.regenerated;
+    }
+}
+
+impl std::iter::Sum for Energy {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), |mut acc, e| {
+            acc += e;
+            acc
+        })
+    }
+}
+
+/// Energy model of a line. The cruise speed, in m/s, is bound by the
+/// top speed of each rolling stock class. Trains without a class use
+/// the default energy parameters of [`RollingStock`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnergyModel {
+    cruise_speed: f64,
+    resistance: f64,
+    passenger_mass: f64,
+    step_time: f64,
+    default_stock: RollingStock,
+}
+
+impl EnergyModel {
+    /// By default the running resistance is 25 N per tonne, a passenger
+    /// weighs 70 kg and a step lasts a minute.
+    #[must_use]
+    pub fn new(cruise_speed: f64) -> Self {
+        assert!(cruise_speed > 0., "cruise speed must be positive");
+        Self {
+            cruise_speed,
+            resistance: 25.,
+            passenger_mass: 70.,
+            step_time: 60.,
+            default_stock: RollingStock::new("", 0, 0),
+        }
+    }
+
+    /// Set the running resistance in N per tonne.
+    #[must_use]
+    pub fn set_resistance(mut self, resistance: f64) -> Self {
+        self.resistance = resistance;
+        self
+    }
+
+    /// Set the average passenger mass in kg.
+    #[must_use]
+    pub fn set_passenger_mass(mut self, mass: f64) -> Self {
+        self.passenger_mass = mass;
+        self
+    }
+
+    /// Set the simulated seconds in a step.
+    #[must_use]
+    pub fn set_step_time(mut self, seconds: f64) -> Self {
+        assert!(seconds > 0., "step time must be positive");
+        self.step_time = seconds;
+        self
+    }
+
+    /// Energy used by the car to enter its current segment, that takes
+    /// it the given time. departing tells if the car was stopped in a
+    /// station before: it then accelerates. A car stopping in a station
+    /// brakes and recovers energy. With the motion of the link, from the
+    /// train kinematics, the car runs the length of the segment and
+    /// reaches at most the top speed of the link, short links being
+    /// too short to reach the cruise speed.
+    #[must_use]
+    pub fn segment_energy<T>(
+        &self,
+        car: &Car<T>,
+        departing: bool,
+        time: Duration,
+        motion: Option<Motion>,
+    ) -> Energy {
+        let stock = car.get_rolling_stock().unwrap_or(&self.default_stock);
+        let mass = stock.get_mass() * 1000. + car.passenger_count() as f64 * self.passenger_mass;
+        let speed = stock
+            .get_max_speed()
+            .map_or(self.cruise_speed, |s| s.min(self.cruise_speed));
+        let speed = motion.map_or(speed, |m| speed.min(m.get_speed()));
+        let kinetic = 0.5 * mass * speed.powi(2);
+        if car.in_station() {
+            let regenerated = if departing {
+                0.
+            } else {
+                kinetic * stock.get_regen_efficiency()
+            };
+            Energy::new(0., regenerated / JOULE_IN_KWH)
+        } else {
+            let distance = motion.map_or(speed * time as f64 * self.step_time, |m| m.get_length());
+            let mut work = self.resistance * mass / 1000. * distance;
+            if departing {
+                work += kinetic;
+            }
+            let traction = work / stock.get_traction_efficiency();
+            Energy::new(traction / JOULE_IN_KWH, 0.)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::car::CarLocation;
+    use crate::line::LineDirection;
+
+    #[test]
+    fn test_segment_energy() {
+        let stock = RollingStock::new("test", 10, 10)
+            .set_max_speed(10.)
+            .set_energy(72., 0.5, 0.5);
+        let model = EnergyModel::new(20.).set_step_time(1.);
+        let mut car: Car<()> =
+            Car::new(1, CarLocation::segment(1), LineDirection::DirectionB, 2, 1)
+                .set_rolling_stock(stock);
+        // 0.5 * 72 t * (10 m/s)^2 = 3.6 MJ, half lost in traction
+        let start = model.segment_energy(&car, true, 0, None);
+        assert!((start.get_traction() - 2.).abs() < 1e-9);
+        // 25 N/t * 72 t over 2 km = 3.6 MJ
+        let run = model.segment_energy(&car, false, 200, None);
+        assert!((run.get_traction() - 2.).abs() < 1e-9);
+        assert_eq!(run.get_regenerated(), 0.);
+
+        car.set_location(CarLocation::station(2, 1));
+        let stop = model.segment_energy(&car, false, 3, None);
+        assert_eq!(stop.get_traction(), 0.);
+        assert!((stop.get_regenerated() - 0.5).abs() < 1e-9);
+        assert_eq!(model.segment_energy(&car, true, 3, None), Energy::default());
+    }
+
+    #[test]
+    fn test_short_link_energy() {
+        let stock = RollingStock::new("test", 10, 10).set_energy(72., 0.5, 0.5);
+        let model = EnergyModel::new(20.).set_step_time(1.);
+        let mut car: Car<()> =
+            Car::new(1, CarLocation::segment(1), LineDirection::DirectionB, 2, 1)
+                .set_rolling_stock(stock);
+        // a 2 km link run at 1 m/s^2 tops at 10 m/s, not at the cruise speed
+        let motion = Some(Motion::new(2000., 10.));
+        let start = model.segment_energy(&car, true, 200, motion);
+        assert!((start.get_traction() - 4.).abs() < 1e-9);
+        let run = model.segment_energy(&car, false, 1, motion);
+        assert!((run.get_traction() - 2.).abs() < 1e-9);
+
+        car.set_location(CarLocation::station(2, 1));
+        let stop = model.segment_energy(&car, false, 3, motion);
+        assert!((stop.get_regenerated() - 0.5).abs() < 1e-9);
+    }
+}
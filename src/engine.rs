@@ -1,8 +1,20 @@
+//! Run the simulation. ``Simulation`` owns the whole state of
+//! the network and can be advanced one step at a time, while
+//! ``engine`` and ``run`` perform a given number of steps in one go.
+
+use crate::energy::Energy;
 use crate::line;
+use crate::line::deadlock::DeadlockError;
+use crate::passenger::journey::Journey;
 use crate::passenger::{callbacks, PassengerFactory};
+use crate::report::{SimulationReport, StepReport};
+use crate::routes::ClosurePeriod;
 use crate::station::Station;
 use crate::traffic_generator::TrafficGenerator;
+use crate::utils::random::{self, SimRng};
 
+/// Run the simulation for the given number of steps
+/// and return the collected report.
 pub fn engine<
     Tg: TrafficGenerator,
     Tc: callbacks::PassengerAction,
@@ -10,14 +22,242 @@ pub fn engine<
 >(
     steps: u32,
     passenger_factory: &PassengerFactory<Tg>,
-    mut stations: Vec<Station<Tc>>,
-    mut lines: Vec<line::Line<Tc>>,
-    mut tf: Tf,
-) {
-    for step in 0..steps {
-        passenger_factory.generate_traffic(step, &mut stations, &mut tf);
-        move_trains(&mut lines);
-        passenger_boarding(&mut lines, &mut stations);
+    stations: Vec<Station<Tc>>,
+    lines: Vec<line::Line<Tc>>,
+    tf: Tf,
+) -> SimulationReport {
+    run(steps, passenger_factory.by_ref(), stations, lines, tf, None)
+}
+
+/// Run the simulation for the given number of steps, as ``engine``,
+/// taking the passenger factory. If a seed is given the run is
+/// reproducible.
+pub fn run<
+    Tg: TrafficGenerator,
+    Tc: callbacks::PassengerAction,
+    Tf: callbacks::PassengerActionFactory<Tc> + Send + Sync,
+>(
+    steps: u32,
+    passenger_factory: PassengerFactory<Tg>,
+    stations: Vec<Station<Tc>>,
+    lines: Vec<line::Line<Tc>>,
+    tf: Tf,
+    seed: Option<u64>,
+) -> SimulationReport {
+    let mut simulation =
+        Simulation::new(passenger_factory, stations, lines, tf).set_rng(random::make_rng(seed));
+    simulation.run_for(steps);
+    simulation.into_report()
+}
+
+/// Steppable simulation. Owns the passenger factory, the stations,
+/// the lines and the callback factory. Between two steps the state
+/// can be inspected through the read-only accessors.
+pub struct Simulation<'a, Tg, Tc, Tf> {
+    current_step: u32,
+    passenger_factory: PassengerFactory<Tg>,
+    stations: Vec<Station<'a, Tc>>,
+    lines: Vec<line::Line<Tc>>,
+    callback_factory: Tf,
+    rng: SimRng,
+    report: SimulationReport,
+    journeys: Vec<Journey>,
+    closures: &'a [ClosurePeriod],
+    active_closure: Option<usize>,
+}
+
+impl<'a, Tg, Tc, Tf> Simulation<'a, Tg, Tc, Tf>
+where
+    Tg: TrafficGenerator,
+    Tc: callbacks::PassengerAction,
+    Tf: callbacks::PassengerActionFactory<Tc> + Send + Sync,
+{
+    #[must_use]
+    pub fn new(
+        passenger_factory: PassengerFactory<Tg>,
+        stations: Vec<Station<'a, Tc>>,
+        lines: Vec<line::Line<Tc>>,
+        callback_factory: Tf,
+    ) -> Self {
+        Self {
+            current_step: 0,
+            passenger_factory,
+            stations,
+            lines,
+            callback_factory,
+            rng: random::make_rng(None),
+            report: SimulationReport::default(),
+            journeys: Vec::new(),
+            closures: &[],
+            active_closure: None,
+        }
+    }
+
+    /// Seed the random number generator, making the run reproducible.
+    #[must_use]
+    pub fn set_seed(self, seed: u64) -> Self {
+        self.set_rng(random::make_rng(Some(seed)))
+    }
+
+    /// Replace the random number generator used by the simulation.
+    #[must_use]
+    pub fn set_rng(mut self, rng: SimRng) -> Self {
+        self.rng = rng;
+        self
+    }
+
+    /// Routing periods of the planned closures, without overlaps.
+    /// While a period is active the stations route the passengers
+    /// with its matrices.
+    #[must_use]
+    pub fn set_closures(mut self, closures: &'a [ClosurePeriod]) -> Self {
+        self.closures = closures;
+        self
+    }
+
+    /// Perform a single simulation step: start or end closures,
+    /// generate new traffic, fail random trains, move the trains and
+    /// board/land passengers. Return the totals for the step just performed.
+    pub fn step(&mut self) -> &StepReport {
+        let step = self.current_step;
+        let effects = closure_effects(&self.stations, &self.lines);
+        self.update_closure(step as usize);
+        let generated = self.passenger_factory.generate_traffic(
+            step,
+            &mut self.stations,
+            &mut self.callback_factory,
+            &mut self.rng,
+        );
+        inject_failures(&mut self.lines, &mut self.rng);
+        move_trains(&mut self.lines);
+        let denied = denied_boardings(&self.stations);
+        let delivered = passenger_boarding(&mut self.lines, &mut self.stations, step);
+        collect_journeys(&mut self.stations, &mut self.journeys);
+        let denied = denied_boardings(&self.stations) - denied;
+        let (rerouted, stranded) = closure_effects(&self.stations, &self.lines);
+        let report = StepReport::new(
+            step,
+            generated,
+            waiting_passengers(&self.stations),
+            on_board_passengers(&self.lines),
+            delivered,
+        )
+        .set_denied(denied)
+        .set_closure_effects(rerouted - effects.0, stranded - effects.1)
+        .set_deadlocks(line_deadlocks(&self.lines, step as usize).count())
+        .set_energy(line_energy(&self.lines));
+        self.report.push(report);
+        self.current_step += 1;
+        self.report.get_steps().last().unwrap()
+    }
+
+    /// Switch the station routing when a closure period starts or ends.
+    fn update_closure(&mut self, step: usize) {
+        let active = self.closures.iter().position(|c| c.is_active(step));
+        if active != self.active_closure {
+            self.active_closure = active;
+            let closure = active.map(|i| &self.closures[i]);
+            self.stations
+                .iter_mut()
+                .for_each(|s| s.set_closure(closure, step as u32));
+        }
+    }
+
+    /// Perform a single simulation step, as ``step``, and
+    /// return an error if trains of a line got in a deadlock.
+    pub fn try_step(&mut self) -> Result<&StepReport, DeadlockError> {
+        let step = self.current_step as usize;
+        self.step();
+        match line_deadlocks(&self.lines, step).next() {
+            Some(err) => Err(err),
+            None => Ok(self.report.get_steps().last().unwrap()),
+        }
+    }
+
+    /// Perform n simulation steps.
+    pub fn run_for(&mut self, n: u32) {
+        for _ in 0..n {
+            self.step();
+        }
+    }
+
+    /// Perform n simulation steps, stopping at the first deadlock.
+    pub fn try_run_for(&mut self, n: u32) -> Result<(), DeadlockError> {
+        for _ in 0..n {
+            self.try_step()?;
+        }
+        Ok(())
+    }
+
+    /// Perform simulation steps until the predicate returns true.
+    /// The predicate is checked before each step, so if it already
+    /// holds no step is performed. Return the number of performed steps.
+    pub fn run_until<P>(&mut self, mut predicate: P) -> u32
+    where
+        P: FnMut(&Self) -> bool,
+    {
+        let begin = self.current_step;
+        while !predicate(self) {
+            self.step();
+        }
+        self.current_step - begin
+    }
+
+    /// Index of the next step to perform, which is
+    /// also the number of steps performed so far.
+    #[must_use]
+    pub fn current_step(&self) -> u32 {
+        self.current_step
+    }
+
+    #[must_use]
+    pub fn stations(&self) -> &[Station<'a, Tc>] {
+        &self.stations
+    }
+
+    #[must_use]
+    pub fn lines(&self) -> &[line::Line<Tc>] {
+        &self.lines
+    }
+
+    #[must_use]
+    pub fn passenger_factory(&self) -> &PassengerFactory<Tg> {
+        &self.passenger_factory
+    }
+
+    #[must_use]
+    pub fn callback_factory(&self) -> &Tf {
+        &self.callback_factory
+    }
+
+    /// Report of the steps performed so far.
+    #[must_use]
+    pub fn report(&self) -> &SimulationReport {
+        &self.report
+    }
+
+    /// Journeys ended so far, at the destination or stranded
+    /// by a closure, in order of arrival.
+    #[must_use]
+    pub fn journeys(&self) -> &[Journey] {
+        &self.journeys
+    }
+
+    /// Take the journeys ended so far, leaving the list empty.
+    pub fn take_journeys(&mut self) -> Vec<Journey> {
+        std::mem::take(&mut self.journeys)
+    }
+
+    /// Consume the simulation and return the collected report.
+    #[must_use]
+    pub fn into_report(self) -> SimulationReport {
+        self.report
+    }
+}
+
+fn inject_failures<Tc: callbacks::PassengerAction>(lines: &mut [line::Line<Tc>], rng: &mut SimRng) {
+    for line in lines {
+        line.inject_failures(rng);
     }
 }
 
@@ -28,8 +268,57 @@ fn move_trains<Tc: callbacks::PassengerAction>(lines: &mut [line::Line<Tc>]) {
 fn passenger_boarding<Tc: callbacks::PassengerAction>(
     lines: &mut [line::Line<Tc>],
     stations: &mut [Station<Tc>],
-) {
+    step: u32,
+) -> usize {
     lines
         .iter_mut()
-        .for_each(|ln| ln.boarding_operations(stations));
+        .enumerate()
+        .map(|(i, ln)| ln.boarding_operations(stations, step, i))
+        .sum()
+}
+
+fn collect_journeys<Tc>(stations: &mut [Station<Tc>], journeys: &mut Vec<Journey>) {
+    stations
+        .iter_mut()
+        .for_each(|s| journeys.append(&mut s.take_completed_journeys()));
+}
+
+fn waiting_passengers<Tc>(stations: &[Station<Tc>]) -> Vec<usize> {
+    stations.iter().map(Station::waiting_count).collect()
+}
+
+fn denied_boardings<Tc>(stations: &[Station<Tc>]) -> usize {
+    stations.iter().map(Station::total_denied_boardings).sum()
+}
+
+/// Passengers rerouted and stranded by closures so far.
+fn closure_effects<Tc: callbacks::PassengerAction>(
+    stations: &[Station<Tc>],
+    lines: &[line::Line<Tc>],
+) -> (usize, usize) {
+    let rerouted = stations.iter().map(Station::rerouted_count).sum::<usize>()
+        + lines.iter().map(line::Line::rerouted_count).sum::<usize>();
+    let stranded = stations.iter().map(Station::stranded_count).sum();
+    (rerouted, stranded)
+}
+
+/// Deadlocks detected in the given step, with their line index.
+fn line_deadlocks<Tc: callbacks::PassengerAction>(
+    lines: &[line::Line<Tc>],
+    step: usize,
+) -> impl Iterator<Item = DeadlockError> + '_ {
+    lines.iter().enumerate().flat_map(move |(i, line)| {
+        line.get_deadlocks()
+            .iter()
+            .filter(move |d| d.get_step() == step)
+            .map(move |d| DeadlockError::new(i, d.clone()))
+    })
+}
+
+fn line_energy<Tc: callbacks::PassengerAction>(lines: &[line::Line<Tc>]) -> Vec<Energy> {
+    lines.iter().map(line::Line::get_step_energy).collect()
+}
+
+fn on_board_passengers<Tc: callbacks::PassengerAction>(lines: &[line::Line<Tc>]) -> Vec<usize> {
+    lines.iter().map(line::Line::passenger_count).collect()
 }
@@ -3,6 +3,75 @@
 //! trains running on the network.
 
 use crate::car;
+use crate::energy::Energy;
+
+/// Time spent in service by a car, from the step it
+/// left a depot to the step it was withdrawn.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CarService {
+    car: usize,
+    start: usize,
+    end: usize,
+    class: Option<String>,
+    energy: Energy,
+}
+
+impl CarService {
+    #[must_use]
+    pub fn new(car: usize, start: usize, end: usize) -> Self {
+        Self {
+            car,
+            start,
+            end,
+            class: None,
+            energy: Energy::default(),
+        }
+    }
+
+    /// Set the energy used by the car while in service.
+    #[must_use]
+    pub fn set_energy(mut self, energy: Energy) -> Self {
+        self.energy = energy;
+        self
+    }
+
+    #[must_use]
+    pub fn get_energy(&self) -> Energy {
+        self.energy
+    }
+
+    /// Set the name of the rolling stock class of the car.
+    #[must_use]
+    pub fn set_class(mut self, class: &str) -> Self {
+        self.class = Some(class.to_string());
+        self
+    }
+
+    #[must_use]
+    pub fn get_class(&self) -> Option<&str> {
+        self.class.as_deref()
+    }
+
+    #[must_use]
+    pub fn get_car(&self) -> usize {
+        self.car
+    }
+
+    #[must_use]
+    pub fn get_start(&self) -> usize {
+        self.start
+    }
+
+    #[must_use]
+    pub fn get_end(&self) -> usize {
+        self.end
+    }
+
+    #[must_use]
+    pub fn in_service_time(&self) -> usize {
+        self.end - self.start
+    }
+}
 
 pub struct Fleet<T> {
     running: Vec<car::Car<T>>,
@@ -30,6 +99,27 @@ impl<T> Fleet<T> {
         self.running.push(car);
     }
 
+    /// Remove the train at the given position, in iteration
+    /// order, from the list of running trains.
+    pub fn withdraw_train(&mut self, index: usize) -> car::Car<T> {
+        self.running.remove(index)
+    }
+
+    /// Count passengers on board of all running trains.
+    pub fn passenger_count(&self) -> usize {
+        self.running.iter().map(car::Car::passenger_count).sum()
+    }
+
+    /// Iterate through all running trains.
+    pub fn cars_iter(&self) -> impl Iterator<Item = &car::Car<T>> {
+        self.running.iter()
+    }
+
+    /// Count passengers standing on board of all running trains.
+    pub fn standing_count(&self) -> usize {
+        self.running.iter().map(car::Car::standing_count).sum()
+    }
+
     pub fn len(&self) -> usize {
         self.running.len()
     }
@@ -8,6 +8,7 @@ use ndarray::Array2;
 /// Graph's Arc. Define an arbitrary information A,
 /// the destination of the arc and the weight of the arc.
 pub struct Arc<A> {
+    #[allow(dead_code)]
     info: A,
     next: usize,
     weight: GraphWeight,
@@ -1,9 +1,14 @@
 pub mod car;
+pub mod energy;
 pub mod engine;
 pub mod fleet;
 pub mod graph;
 pub mod line;
+pub mod output;
 pub mod passenger;
+pub mod report;
+pub mod rolling_stock;
+pub mod scenario;
 pub mod station;
 pub mod traffic_generator;
 
@@ -14,7 +19,7 @@ pub mod utils;
 type Node = f64;
 type Int = u32;
 
-const MINUTE_IN_HOUR: Int = 60;
+pub(crate) const MINUTE_IN_HOUR: Int = 60;
 
 #[must_use]
 pub fn get_steps(begin: Int, end: Int, minute_resolution: Int) -> Int {
@@ -0,0 +1,140 @@
+//! Circular waits between trains. Single track segments are shared
+//! by both directions, so trains facing each other across them can
+//! wait for each other forever. The line detects these deadlocks
+//! and, with the passing loop priority, keeps trains from entering
+//! single track they cannot clear.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+
+/// How a line deals with trains meeting on single track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeadlockPolicy {
+    /// Trains enter single track as soon as it is free. Circular
+    /// waits are recorded and reported as a ``DeadlockError``.
+    #[default]
+    Fail,
+    /// Trains wait in passing loops and stations until the single
+    /// track ahead and the segment after it can receive them. Trains
+    /// turning around in a terminus leave before the depot ones, and
+    /// trains enter a stretch where others turn back early only while
+    /// it keeps a free track.
+    PassingLoopPriority,
+}
+
+/// Trains waiting for each other since the given step.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Deadlock {
+    step: usize,
+    cars: Vec<usize>,
+}
+
+impl Deadlock {
+    #[must_use]
+    pub fn new(step: usize, cars: Vec<usize>) -> Self {
+        Self { step, cars }
+    }
+
+    #[must_use]
+    pub fn get_step(&self) -> usize {
+        self.step
+    }
+
+    /// Ids of the trains in the circular wait, in increasing order.
+    #[must_use]
+    pub fn get_cars(&self) -> &[usize] {
+        &self.cars
+    }
+}
+
+/// A deadlock on the given line, raised by the simulation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadlockError {
+    line: usize,
+    deadlock: Deadlock,
+}
+
+impl DeadlockError {
+    #[must_use]
+    pub fn new(line: usize, deadlock: Deadlock) -> Self {
+        Self { line, deadlock }
+    }
+
+    #[must_use]
+    pub fn get_line(&self) -> usize {
+        self.line
+    }
+
+    #[must_use]
+    pub fn get_deadlock(&self) -> &Deadlock {
+        &self.deadlock
+    }
+}
+
+impl fmt::Display for DeadlockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}: trains {:?} wait for each other since step {}",
+            self.line, self.deadlock.cars, self.deadlock.step
+        )
+    }
+}
+
+impl std::error::Error for DeadlockError {}
+
+/// Find the cycles of the wait graph, where each train
+/// waits for at most another one. Each cycle is sorted.
+pub(super) fn find_cycles(waits: &HashMap<usize, usize>) -> Vec<Vec<usize>> {
+    let mut visited: HashMap<usize, usize> = HashMap::new();
+    let mut cycles = vec![];
+    let mut starts: Vec<usize> = waits.keys().copied().collect();
+    starts.sort_unstable();
+    for start in starts {
+        let mut car = start;
+        // follow the waits until a train already visited, or one
+        // that is not waiting
+        while !visited.contains_key(&car) {
+            visited.insert(car, start);
+            match waits.get(&car) {
+                Some(next) => car = *next,
+                None => break,
+            }
+        }
+        if visited.get(&car) == Some(&start) && waits.contains_key(&car) {
+            let mut cycle = vec![car];
+            let mut next = waits[&car];
+            while next != car {
+                cycle.push(next);
+                next = waits[&next];
+            }
+            cycle.sort_unstable();
+            cycles.push(cycle);
+        }
+    }
+    cycles
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_find_cycles() {
+        // 0 -> 1 -> 2 -> 0 is a cycle, 3 waits for it, 4 -> 5 is not
+        let waits = HashMap::from([(0, 1), (1, 2), (2, 0), (3, 0), (4, 5), (6, 7), (7, 6)]);
+        assert_eq!(find_cycles(&waits), [vec![0, 1, 2], vec![6, 7]]);
+        assert!(find_cycles(&HashMap::from([(4, 5)])).is_empty());
+    }
+
+    #[test]
+    fn test_error_message() {
+        let err = DeadlockError::new(1, Deadlock::new(40, vec![2, 3]));
+        assert_eq!(
+            err.to_string(),
+            "line 1: trains [2, 3] wait for each other since step 40"
+        );
+    }
+}
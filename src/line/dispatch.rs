@@ -0,0 +1,397 @@
+//! Decide when a terminus starts a new train. A terminus can
+//! start trains at a fixed headway, follow a timetable or follow
+//! a headway profile that changes with the time of day within
+//! the service span. Timetable departures are recorded, so that
+//! lateness caused by an occupied terminus is available after
+//! the simulation. Waiting for a train to come back into an
+//! empty depot is not counted as lateness.
+
+use crate::utils::counter::CyclicCounter;
+use std::collections::VecDeque;
+
+/// A train that left a terminus following the timetable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Departure {
+    scheduled: usize,
+    actual: usize,
+    lateness: usize,
+}
+
+impl Departure {
+    #[must_use]
+    pub fn new(scheduled: usize, actual: usize) -> Self {
+        Self {
+            scheduled,
+            actual,
+            lateness: actual - scheduled,
+        }
+    }
+
+    /// Set the steps the train waited for the terminus platform,
+    /// when it also waited for other reasons.
+    #[must_use]
+    pub fn set_lateness(mut self, steps: usize) -> Self {
+        self.lateness = steps;
+        self
+    }
+
+    /// Step set by the timetable.
+    #[must_use]
+    pub fn get_scheduled(&self) -> usize {
+        self.scheduled
+    }
+
+    /// Step when the train actually left.
+    #[must_use]
+    pub fn get_actual(&self) -> usize {
+        self.actual
+    }
+
+    /// Steps of delay with respect to the timetable
+    /// caused by an occupied terminus platform.
+    #[must_use]
+    pub fn lateness(&self) -> usize {
+        self.lateness
+    }
+}
+
+/// Departure steps from a terminus, in increasing order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timetable {
+    departures: VecDeque<usize>,
+    waited: usize,
+}
+
+impl Timetable {
+    #[must_use]
+    pub fn new<I>(departures: I) -> Self
+    where
+        I: IntoIterator<Item = usize>,
+    {
+        let mut departures: Vec<usize> = departures.into_iter().collect();
+        departures.sort_unstable();
+        Self {
+            departures: departures.into(),
+            waited: 0,
+        }
+    }
+
+    /// Number of departures still to perform.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.departures.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.departures.is_empty()
+    }
+
+    fn is_due(&self, step: usize) -> bool {
+        self.departures.front().is_some_and(|d| *d <= step)
+    }
+
+    fn depart(&mut self, step: usize) -> Option<Departure> {
+        if self.is_due(step) {
+            let waited = std::mem::take(&mut self.waited);
+            self.departures
+                .pop_front()
+                .map(|scheduled| Departure::new(scheduled, step).set_lateness(waited))
+        } else {
+            None
+        }
+    }
+
+    fn wait(&mut self, step: usize) {
+        if self.is_due(step) {
+            self.waited += 1;
+        }
+    }
+}
+
+/// Headway by time of day. Each period starts at the given
+/// step and lasts until the next one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeadwayProfile {
+    periods: Vec<(usize, usize)>,
+}
+
+impl HeadwayProfile {
+    /// Initialize a profile with the same headway all day long.
+    #[must_use]
+    pub fn new(headway: usize) -> Self {
+        Self {
+            periods: vec![(0, headway)],
+        }
+    }
+
+    /// Use the given headway from step start on, until the next period.
+    #[must_use]
+    pub fn add_period(mut self, start: usize, headway: usize) -> Self {
+        self.periods.retain(|(s, _)| *s != start);
+        let index = self.periods.partition_point(|(s, _)| *s < start);
+        self.periods.insert(index, (start, headway));
+        self
+    }
+
+    /// Headway in force at the given step.
+    #[must_use]
+    pub fn headway_at(&self, step: usize) -> usize {
+        let index = self.periods.partition_point(|(s, _)| *s <= step);
+        index.checked_sub(1).map_or(0, |i| self.periods[i].1)
+    }
+}
+
+/// Steps of the first and of the last train leaving a terminus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServiceSpan {
+    first: usize,
+    last: usize,
+}
+
+impl ServiceSpan {
+    #[must_use]
+    pub fn new(first: usize, last: usize) -> Self {
+        Self { first, last }
+    }
+
+    #[must_use]
+    pub fn get_first(&self) -> usize {
+        self.first
+    }
+
+    #[must_use]
+    pub fn get_last(&self) -> usize {
+        self.last
+    }
+
+    /// Check if a train can leave at the given step.
+    #[must_use]
+    pub fn contains(&self, step: usize) -> bool {
+        (self.first..=self.last).contains(&step)
+    }
+}
+
+/// Start trains within the service span, following a headway profile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Service {
+    profile: HeadwayProfile,
+    span: ServiceSpan,
+    next: usize,
+}
+
+impl Service {
+    #[must_use]
+    pub fn new(profile: HeadwayProfile, span: ServiceSpan) -> Self {
+        Self {
+            profile,
+            span,
+            next: span.first,
+        }
+    }
+
+    #[must_use]
+    pub fn get_profile(&self) -> &HeadwayProfile {
+        &self.profile
+    }
+
+    #[must_use]
+    pub fn get_span(&self) -> ServiceSpan {
+        self.span
+    }
+
+    fn is_due(&self, step: usize) -> bool {
+        self.span.contains(step) && step >= self.next
+    }
+
+    fn depart(&mut self, step: usize) {
+        self.next = step + self.profile.headway_at(step);
+    }
+
+    /// Trains needed at the given step to keep the headway on a
+    /// line whose round trip takes the given number of steps.
+    /// No train is needed outside the service span.
+    #[must_use]
+    pub fn trains_needed(&self, step: usize, round_trip: usize) -> usize {
+        if self.span.contains(step) {
+            round_trip.div_ceil(self.profile.headway_at(step).max(1))
+        } else {
+            0
+        }
+    }
+}
+
+/// Dispatch policy of a terminus.
+#[derive(Debug, PartialEq)]
+pub enum Dispatch {
+    /// Start a train each time the counter is done.
+    Headway(CyclicCounter),
+    /// Start a train at each timetable departure.
+    Timetable(Timetable),
+    /// Start trains following a headway profile.
+    Service(Service),
+}
+
+impl Dispatch {
+    /// Check if a train should start at the given step.
+    pub fn is_due(&self, step: usize) -> bool {
+        match self {
+            Self::Headway(counter) => counter.is_done(),
+            Self::Timetable(timetable) => timetable.is_due(step),
+            Self::Service(service) => service.is_due(step),
+        }
+    }
+
+    /// Register a train start at the given step. Return
+    /// the departure if it follows a timetable.
+    pub fn depart(&mut self, step: usize) -> Option<Departure> {
+        match self {
+            Self::Headway(_) => None,
+            Self::Timetable(timetable) => timetable.depart(step),
+            Self::Service(service) => {
+                service.depart(step);
+                None
+            }
+        }
+    }
+
+    /// Check if a due train waits in the depot while the terminus
+    /// platform is occupied. At a fixed headway trains leave at
+    /// each slot and queue at the platform.
+    pub fn holds_trains(&self) -> bool {
+        !matches!(self, Self::Headway(_))
+    }
+
+    /// Register a step in which a due train could not leave
+    /// because the terminus platform was occupied.
+    pub fn wait(&mut self, step: usize) {
+        if let Self::Timetable(timetable) = self {
+            timetable.wait(step);
+        }
+    }
+
+    /// Check if a train arriving at the terminus returns to the
+    /// depot, given the trains running and the round trip time of
+    /// the line. Timetable departures leave from the depot, so
+    /// trains always return there. With a service, trains return
+    /// when more are running than the headway needs, as when the
+    /// headway grows or the service ends. At a fixed headway
+    /// trains always turn around.
+    pub fn withdraws(&self, step: usize, running: usize, round_trip: usize) -> bool {
+        match self {
+            Self::Headway(_) => false,
+            Self::Timetable(_) => true,
+            Self::Service(service) => running > service.trains_needed(step, round_trip),
+        }
+    }
+
+    /// Advance the headway counter, if any.
+    pub fn step(&mut self) {
+        if let Self::Headway(counter) = self {
+            counter.count();
+        }
+    }
+}
+
+impl From<Timetable> for Dispatch {
+    fn from(timetable: Timetable) -> Self {
+        Self::Timetable(timetable)
+    }
+}
+
+impl From<Service> for Dispatch {
+    fn from(service: Service) -> Self {
+        Self::Service(service)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_timetable_dispatch() {
+        let mut dispatch: Dispatch = Timetable::new([10, 4, 7]).into();
+        for step in 0..4 {
+            assert!(!dispatch.is_due(step));
+            dispatch.step();
+        }
+        assert!(dispatch.is_due(4));
+        assert_eq!(dispatch.depart(4), Some(Departure::new(4, 4)));
+        assert!(!dispatch.is_due(5));
+
+        // The train leaves two steps late, waiting
+        // one of them for the terminus platform
+        assert!(dispatch.is_due(8));
+        dispatch.wait(6);
+        dispatch.wait(8);
+        let departure = dispatch.depart(9).unwrap();
+        assert_eq!(departure.get_scheduled(), 7);
+        assert_eq!(departure.get_actual(), 9);
+        assert_eq!(departure.lateness(), 1);
+
+        assert!(dispatch.depart(9).is_none());
+        assert_eq!(dispatch.depart(12).unwrap().lateness(), 0);
+        assert!(!dispatch.is_due(100));
+    }
+
+    #[test]
+    fn test_headway_dispatch() {
+        let mut dispatch = Dispatch::Headway(2.into());
+        assert!(!dispatch.is_due(0));
+        dispatch.step();
+        assert!(!dispatch.is_due(1));
+        dispatch.step();
+        assert!(dispatch.is_due(2));
+        assert!(dispatch.depart(2).is_none());
+        dispatch.step();
+        assert!(!dispatch.is_due(3));
+    }
+
+    #[test]
+    fn test_headway_profile() {
+        let profile = HeadwayProfile::new(10)
+            .add_period(20, 4)
+            .add_period(50, 8)
+            .add_period(20, 3);
+        assert_eq!(profile.headway_at(0), 10);
+        assert_eq!(profile.headway_at(19), 10);
+        assert_eq!(profile.headway_at(20), 3);
+        assert_eq!(profile.headway_at(49), 3);
+        assert_eq!(profile.headway_at(50), 8);
+        assert_eq!(profile.headway_at(500), 8);
+    }
+
+    #[test]
+    fn test_service_dispatch() {
+        let profile = HeadwayProfile::new(10).add_period(20, 4);
+        let mut dispatch: Dispatch = Service::new(profile, ServiceSpan::new(5, 30)).into();
+        let departures: Vec<usize> = (0..50)
+            .filter(|step| {
+                let due = dispatch.is_due(*step);
+                if due {
+                    dispatch.depart(*step);
+                }
+                due
+            })
+            .collect();
+        assert_eq!(departures, [5, 15, 25, 29]);
+    }
+
+    #[test]
+    fn test_withdraws() {
+        let profile = HeadwayProfile::new(10).add_period(20, 30);
+        let dispatch: Dispatch = Service::new(profile, ServiceSpan::new(5, 50)).into();
+        // a round trip of 60 steps needs 6 trains, then 2
+        assert!(!dispatch.withdraws(10, 6, 60));
+        assert!(dispatch.withdraws(10, 7, 60));
+        assert!(dispatch.withdraws(20, 3, 60));
+        assert!(!dispatch.withdraws(20, 2, 60));
+        // outside the service span
+        assert!(dispatch.withdraws(2, 1, 60));
+        assert!(dispatch.withdraws(51, 1, 60));
+        assert!(!Dispatch::Headway(2.into()).withdraws(10, 100, 60));
+        assert!(Dispatch::from(Timetable::new([])).withdraws(10, 1, 60));
+    }
+}
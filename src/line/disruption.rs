@@ -0,0 +1,204 @@
+//! Disruptions on a line: random train failures and scheduled
+//! segment blocks. The line records the delays they cause, including
+//! the ones spread to the trains stuck behind a disrupted one.
+
+use super::Duration;
+use super::LineDirection;
+use super::StationID;
+use crate::utils::random::SimRng;
+
+use rand::Rng;
+use serde::Serialize;
+
+/// Random train failures. Each step a running train fails with the
+/// given probability and then stands still for a repair time sampled
+/// uniformly between the given bounds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailureModel {
+    probability: f64,
+    min_repair: Duration,
+    max_repair: Duration,
+}
+
+impl FailureModel {
+    /// # Panics
+    /// If the probability is not in [0, 1] or the repair bounds
+    /// are not ordered.
+    #[must_use]
+    pub fn new(probability: f64, min_repair: Duration, max_repair: Duration) -> Self {
+        assert!(
+            (0. ..=1.).contains(&probability),
+            "failure probability must be in [0, 1]"
+        );
+        assert!(min_repair <= max_repair, "repair bounds are not ordered");
+        Self {
+            probability,
+            min_repair,
+            max_repair,
+        }
+    }
+
+    /// Repair time of a train failing in this step, if it fails.
+    pub fn sample(&self, rng: &mut SimRng) -> Option<Duration> {
+        rng.gen_bool(self.probability)
+            .then(|| rng.gen_range(self.min_repair..=self.max_repair))
+    }
+}
+
+/// Part of a line affected by a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockTarget {
+    /// The platforms of a station.
+    Station(StationID),
+    /// The track between two consecutive stations.
+    Link(StationID, StationID),
+}
+
+/// A block of part of the line from the start step up to the end
+/// one, excluded. Trains cannot enter the blocked segments and the
+/// trains inside them stand still. By default both directions are
+/// blocked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SegmentBlock {
+    target: BlockTarget,
+    direction: Option<LineDirection>,
+    start: usize,
+    end: usize,
+}
+
+impl SegmentBlock {
+    #[must_use]
+    pub fn new(target: BlockTarget, start: usize, end: usize) -> Self {
+        Self {
+            target,
+            direction: None,
+            start,
+            end,
+        }
+    }
+
+    /// Block only the trains running in the given direction.
+    #[must_use]
+    pub fn set_direction(mut self, dir: LineDirection) -> Self {
+        self.direction = Some(dir);
+        self
+    }
+
+    #[must_use]
+    pub fn get_target(&self) -> BlockTarget {
+        self.target
+    }
+
+    /// Check if the block stops trains running in
+    /// the given direction at the given step.
+    #[must_use]
+    pub fn blocks(&self, dir: LineDirection, step: usize) -> bool {
+        (self.start..self.end).contains(&step) && self.direction.is_none_or(|d| d == dir)
+    }
+}
+
+/// Why a train could not move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DelayCause {
+    /// The train itself failed.
+    Failure,
+    /// The train is in a blocked segment or in front of one.
+    Blocked,
+    /// The train waits behind a delayed train.
+    KnockOn,
+    /// The train is in a circular wait on single track.
+    Deadlock,
+    /// The train waits in a passing loop or station for
+    /// the single track ahead to clear.
+    PassingLoop,
+}
+
+/// Consecutive steps a train was delayed for the same cause.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Delay {
+    car: usize,
+    start: usize,
+    steps: usize,
+    cause: DelayCause,
+}
+
+impl Delay {
+    #[must_use]
+    pub fn new(car: usize, start: usize, cause: DelayCause) -> Self {
+        Self {
+            car,
+            start,
+            steps: 1,
+            cause,
+        }
+    }
+
+    #[must_use]
+    pub fn get_car(&self) -> usize {
+        self.car
+    }
+
+    #[must_use]
+    pub fn get_start(&self) -> usize {
+        self.start
+    }
+
+    #[must_use]
+    pub fn get_steps(&self) -> usize {
+        self.steps
+    }
+
+    #[must_use]
+    pub fn get_cause(&self) -> DelayCause {
+        self.cause
+    }
+
+    /// Extend the delay to the given step, if it follows it
+    /// with the same cause. Return true if extended.
+    pub fn extend(&mut self, step: usize, cause: DelayCause) -> bool {
+        if self.cause == cause && self.start + self.steps == step {
+            self.steps += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::utils::random::make_rng;
+
+    #[test]
+    fn test_failure_sample() {
+        let mut rng = make_rng(Some(1));
+        let never = FailureModel::new(0., 2, 4);
+        assert!((0..100).all(|_| never.sample(&mut rng).is_none()));
+        let always = FailureModel::new(1., 2, 4);
+        assert!((0..100).all(|_| always
+            .sample(&mut rng)
+            .is_some_and(|t| (2..=4).contains(&t))));
+    }
+
+    #[test]
+    fn test_segment_block() {
+        let block = SegmentBlock::new(BlockTarget::Station(1), 5, 8)
+            .set_direction(LineDirection::DirectionA);
+        assert!(!block.blocks(LineDirection::DirectionA, 4));
+        assert!(block.blocks(LineDirection::DirectionA, 5));
+        assert!(!block.blocks(LineDirection::DirectionB, 5));
+        assert!(!block.blocks(LineDirection::DirectionA, 8));
+    }
+
+    #[test]
+    fn test_delay_extend() {
+        let mut delay = Delay::new(0, 3, DelayCause::KnockOn);
+        assert!(delay.extend(4, DelayCause::KnockOn));
+        assert!(!delay.extend(5, DelayCause::Blocked));
+        assert!(!delay.extend(7, DelayCause::KnockOn));
+        assert_eq!(delay.get_steps(), 2);
+    }
+}
@@ -1,4 +1,5 @@
-use super::line_factory;
+use super::kinematics::{Kinematics, Motion};
+use super::line_factory::{self, LineOptions};
 use super::Duration;
 use super::StationID;
 use crate::passenger::callbacks;
@@ -10,6 +11,12 @@ pub struct FastLineFactoryConfig<Is, It> {
     split_len: Duration,
     depo_size: usize,
     train_delay: usize,
+    options: LineOptions,
+    branches: Vec<(StationID, Vec<StationID>, Vec<Duration>)>,
+    circular: bool,
+    kinematics: Option<Kinematics>,
+    speed_limits: Vec<Option<f64>>,
+    branch_speed_limits: Vec<(usize, Vec<Option<f64>>)>,
 }
 
 impl<Is, It> FastLineFactoryConfig<Is, It> {
@@ -28,10 +35,83 @@ impl<Is, It> FastLineFactoryConfig<Is, It> {
             split_len,
             depo_size,
             train_delay,
+            options: LineOptions::default(),
+            branches: Vec::new(),
+            circular: false,
+            kinematics: None,
+            speed_limits: Vec::new(),
+            branch_speed_limits: Vec::new(),
         }
     }
+
+    /// Set the options of the built line. Without a reference
+    /// speed, the top speed of the kinematics is used.
+    #[must_use]
+    pub fn set_options(mut self, options: LineOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Add a branch leaving the line after the junction station.
+    /// line_len contains the time from the junction to the first
+    /// station and then between the following stations.
+    ///
+    /// # Panics
+    /// When the line is built, if the junction is not an intermediate
+    /// station of the main line or if the line is a loop.
+    #[must_use]
+    pub fn add_branch(
+        mut self,
+        junction: StationID,
+        station_ids: Vec<StationID>,
+        line_len: Vec<Duration>,
+    ) -> Self {
+        self.branches.push((junction, station_ids, line_len));
+        self
+    }
+
+    /// Build a loop line, line_len then contains the time
+    /// from the last station back to the first one too.
+    #[must_use]
+    pub fn set_circular(mut self, value: bool) -> Self {
+        self.circular = value;
+        self
+    }
+
+    /// Derive the segment times from the train kinematics. line_len
+    /// and split_len, for the branches too, are then lengths in
+    /// meters, and each link is run from a stop to a stop. The top
+    /// speed is the default reference speed.
+    #[must_use]
+    pub fn set_kinematics(mut self, kinematics: Kinematics) -> Self {
+        self.kinematics = Some(kinematics);
+        self
+    }
+
+    /// Speed limit of each link of the main line, in order. Links
+    /// without a limit are run at the top speed of the trains.
+    /// Used only with kinematics.
+    #[must_use]
+    pub fn set_speed_limits(mut self, limits: Vec<Option<f64>>) -> Self {
+        self.speed_limits = limits;
+        self
+    }
+
+    /// Speed limit of each link of the given branch, in order.
+    /// Branches are numbered from 1 in insertion order.
+    /// Used only with kinematics.
+    #[must_use]
+    pub fn set_branch_speed_limits(mut self, branch: usize, limits: Vec<Option<f64>>) -> Self {
+        self.branch_speed_limits.push((branch, limits));
+        self
+    }
 }
 
+/// Build the line described by the configuration.
+///
+/// # Panics
+/// If a branch or one of the options does not fit the line,
+/// see [`line_factory::line_factory`].
 pub fn fast_line_factory<Is, It, Tc>(
     conf: FastLineFactoryConfig<Is, It>,
     total_station_count: usize,
@@ -54,11 +134,43 @@ where
     It: IntoIterator<Item = Duration>,
 {
     let station_info_iter = station_info_config_factory(conf.station_time, conf.station_ids);
-    let line_info_iter = line_info_config_factory(conf.split_len, conf.line_len);
-    line_factory::LineFactoryConfig::from_iter(station_info_iter, line_info_iter)
-        .set_depo_size(conf.depo_size)
-        .set_train_delay(conf.train_delay)
-        .set_total_station_count(tsc)
+    let kinematics = conf.kinematics.as_ref();
+    let line_info = line_info_config_factory(
+        conf.split_len,
+        conf.line_len,
+        kinematics,
+        &conf.speed_limits,
+    );
+    let options = match (conf.options.get_reference_speed(), conf.kinematics) {
+        (None, Some(kinematics)) => conf.options.set_reference_speed(kinematics.get_max_speed()),
+        _ => conf.options,
+    };
+    let config =
+        line_factory::LineFactoryConfig::from_iter(station_info_iter, line_info.into_iter())
+            .set_depo_size(conf.depo_size)
+            .set_train_delay(conf.train_delay)
+            .set_options(options)
+            .set_total_station_count(tsc)
+            .set_circular(conf.circular);
+    let (station_time, split_len) = (conf.station_time, conf.split_len);
+    let branch_speed_limits = conf.branch_speed_limits;
+    let speed_limits = |branch| {
+        branch_speed_limits
+            .iter()
+            .rev()
+            .find(|(b, _)| *b == branch)
+            .map_or(&[][..], |(_, limits)| &limits[..])
+    };
+    conf.branches.into_iter().enumerate().fold(
+        config,
+        |config, (i, (junction, stations, line_len))| {
+            config.add_branch(line_factory::BranchConfig::new(
+                junction,
+                station_info_config_factory(station_time, stations).collect(),
+                line_info_config_factory(split_len, line_len, kinematics, speed_limits(i + 1)),
+            ))
+        },
+    )
 }
 
 fn station_info_config_factory<I>(
@@ -75,12 +187,45 @@ where
 fn line_info_config_factory<I>(
     split_line: Duration,
     iter: I,
-) -> impl Iterator<Item = line_factory::LineInfoConfig>
+    kinematics: Option<&Kinematics>,
+    speed_limits: &[Option<f64>],
+) -> Vec<line_factory::LineInfoConfig>
 where
     I: IntoIterator<Item = Duration>,
 {
     iter.into_iter()
-        .map(move |len| BuildLineChunkConfig::new(split_line, len).collect())
+        .enumerate()
+        .map(|(i, len)| {
+            let chunks = BuildLineChunkConfig::new(split_line, len);
+            match kinematics {
+                Some(kinematics) => {
+                    let limit = speed_limits.get(i).copied().flatten();
+                    kinematic_chunks(chunks, kinematics, limit)
+                }
+                None => chunks.collect(),
+            }
+        })
+        .collect()
+}
+
+/// Replace the chunk lengths with the time needed to run them.
+fn kinematic_chunks(
+    mut chunks: BuildLineChunkConfig,
+    kinematics: &Kinematics,
+    speed_limit: Option<f64>,
+) -> line_factory::LineInfoConfig {
+    let (lengths, kinds): (Vec<_>, Vec<_>) = std::iter::from_fn(|| chunks.next_chunk()).unzip();
+    let speed = kinematics.peak_speed(lengths.iter().sum(), speed_limit);
+    kinematics
+        .chunk_times(&lengths, speed_limit)
+        .into_iter()
+        .zip(lengths)
+        .zip(kinds)
+        .map(|((time, length), kind)| {
+            line_factory::LineChunkConfig::new(time, kind)
+                .set_motion(Motion::new(length as f64, speed))
+        })
+        .collect()
 }
 
 pub const CHUNK_COUNT: usize = 3;
@@ -108,11 +253,8 @@ impl BuildLineChunkConfig {
             }
         }
     }
-}
 
-impl Iterator for BuildLineChunkConfig {
-    type Item = line_factory::LineChunkConfig;
-    fn next(&mut self) -> Option<Self::Item> {
+    fn next_chunk(&mut self) -> Option<(Duration, line_factory::LineChunkKind)> {
         if self.count == 0 {
             None
         } else {
@@ -124,12 +266,19 @@ impl Iterator for BuildLineChunkConfig {
             } else {
                 (self.base, line_factory::LineChunkKind::Single)
             };
-            let output = line_factory::LineChunkConfig::new(len, kind);
-            Some(output)
+            Some((len, kind))
         }
     }
 }
 
+impl Iterator for BuildLineChunkConfig {
+    type Item = line_factory::LineChunkConfig;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_chunk()
+            .map(|(len, kind)| line_factory::LineChunkConfig::new(len, kind))
+    }
+}
+
 #[cfg(test)]
 mod test {
 
@@ -171,4 +320,28 @@ mod test {
         );
         assert_eq!(blcc.next(), None);
     }
+
+    #[test]
+    fn test_kinematic_chunks() {
+        use line_factory::{LineChunkConfig, LineChunkKind};
+        let kinematics = Kinematics::new(1., 1., 20.).set_step_time(10.);
+        let lines = line_info_config_factory(1000, [1600, 100], Some(&kinematics), &[Some(10.)]);
+        // the limited link cruises at 10 m/s
+        let chunk = |time, kind, length| {
+            LineChunkConfig::new(time, kind).set_motion(Motion::new(length, 10.))
+        };
+        let expected: line_factory::LineInfoConfig = [
+            chunk(6, LineChunkKind::Single, 533.),
+            chunk(5, LineChunkKind::Double, 534.),
+            chunk(6, LineChunkKind::Single, 533.),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(lines[0], expected);
+        // the short link never reaches the limit
+        let expected = [chunk(2, LineChunkKind::Single, 100.)]
+            .into_iter()
+            .collect();
+        assert_eq!(lines[1], expected);
+    }
 }
@@ -0,0 +1,206 @@
+//! Train kinematics: derive the time needed to run along a link
+//! between two stations from its length and speed limit and from
+//! the acceleration, braking and top speed of the trains.
+//! Lengths are in meters, speeds in meters per second and rates
+//! in meters per second squared.
+
+use super::Duration;
+
+/// Motion parameters of the trains of a line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Kinematics {
+    acceleration: f64,
+    braking: f64,
+    max_speed: f64,
+    step_time: f64,
+}
+
+impl Kinematics {
+    /// Panic if any parameter is not positive.
+    #[must_use]
+    pub fn new(acceleration: f64, braking: f64, max_speed: f64) -> Self {
+        assert!(
+            acceleration > 0. && braking > 0. && max_speed > 0.,
+            "kinematic parameters must be positive"
+        );
+        Self {
+            acceleration,
+            braking,
+            max_speed,
+            step_time: 60.,
+        }
+    }
+
+    /// Set the simulated seconds in a step, one minute by default.
+    #[must_use]
+    pub fn set_step_time(mut self, seconds: f64) -> Self {
+        assert!(seconds > 0., "step time must be positive");
+        self.step_time = seconds;
+        self
+    }
+
+    #[must_use]
+    pub fn get_max_speed(&self) -> f64 {
+        self.max_speed
+    }
+
+    /// Steps needed to run each chunk of a link, given the length of
+    /// each chunk in order. Trains start from a stop at the beginning of
+    /// the link, so the first chunk is slower, and brake to stop at its
+    /// end. The speed never exceeds the speed limit, if any. Each chunk
+    /// takes at least a step.
+    #[must_use]
+    pub fn chunk_times(&self, chunks: &[usize], speed_limit: Option<f64>) -> Vec<Duration> {
+        let length = chunks.iter().sum::<usize>() as f64;
+        let profile = self.profile(length, speed_limit);
+        let mut position = 0;
+        let mut previous = 0;
+        chunks
+            .iter()
+            .map(|chunk| {
+                position += chunk;
+                let time = profile.time_at(position as f64) / self.step_time;
+                let time = time.round() as Duration;
+                let duration = time.saturating_sub(previous).max(1);
+                previous = time;
+                duration
+            })
+            .collect()
+    }
+
+    /// Steps needed to run a whole link.
+    #[must_use]
+    pub fn link_time(&self, length: usize, speed_limit: Option<f64>) -> Duration {
+        self.chunk_times(&[length], speed_limit)[0]
+    }
+
+    /// Top speed reached along a link of the given length.
+    #[must_use]
+    pub fn peak_speed(&self, length: usize, speed_limit: Option<f64>) -> f64 {
+        self.profile(length as f64, speed_limit).speed
+    }
+
+    /// Steps needed to run the given length at top speed,
+    /// at least a step.
+    #[must_use]
+    pub fn cruise_time(&self, length: usize) -> Duration {
+        self.steps(length as f64 / self.max_speed)
+    }
+
+    /// Steps needed to run, at top speed, the braking distance
+    /// from top speed plus the given margin: the separation a
+    /// moving block keeps between trains.
+    #[must_use]
+    pub fn braking_separation(&self, margin: f64) -> Duration {
+        let distance = self.max_speed.powi(2) / (2. * self.braking) + margin;
+        self.steps(distance / self.max_speed)
+    }
+
+    /// Seconds rounded up to whole steps, at least a step.
+    fn steps(&self, seconds: f64) -> Duration {
+        ((seconds / self.step_time).ceil() as Duration).max(1)
+    }
+
+    fn profile(&self, length: f64, speed_limit: Option<f64>) -> Profile {
+        let speed = speed_limit.map_or(self.max_speed, |limit| limit.min(self.max_speed));
+        // Too short to reach the speed: brake right after accelerating.
+        let (a, b) = (self.acceleration, self.braking);
+        let peak = (2. * length * a * b / (a + b)).sqrt();
+        Profile {
+            length,
+            speed: speed.min(peak),
+            acceleration: a,
+            braking: b,
+        }
+    }
+}
+
+/// Length of a chunk of a link and top speed
+/// reached along the link, used to estimate energy.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Motion {
+    length: f64,
+    speed: f64,
+}
+
+impl Motion {
+    #[must_use]
+    pub fn new(length: f64, speed: f64) -> Self {
+        Self { length, speed }
+    }
+
+    #[must_use]
+    pub fn get_length(&self) -> f64 {
+        self.length
+    }
+
+    #[must_use]
+    pub fn get_speed(&self) -> f64 {
+        self.speed
+    }
+}
+
+/// Speed profile from a stop to a stop: accelerate,
+/// cruise at constant speed and brake.
+struct Profile {
+    length: f64,
+    speed: f64,
+    acceleration: f64,
+    braking: f64,
+}
+
+impl Profile {
+    /// Seconds needed to reach the given position.
+    fn time_at(&self, position: f64) -> f64 {
+        let start = self.speed.powi(2) / (2. * self.acceleration);
+        let stop = self.length - self.speed.powi(2) / (2. * self.braking);
+        if position <= start {
+            (2. * position / self.acceleration).sqrt()
+        } else if position <= stop {
+            self.speed / self.acceleration + (position - start) / self.speed
+        } else {
+            let total = self.speed / self.acceleration
+                + (stop - start) / self.speed
+                + self.speed / self.braking;
+            let left = (self.length - position).max(0.);
+            total - (2. * left / self.braking).sqrt()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_link_time() {
+        let kinematics = Kinematics::new(1., 1., 20.).set_step_time(10.);
+        // 20 s to accelerate and brake, 200 m each, 60 s cruising
+        assert_eq!(kinematics.link_time(1600, None), 10);
+        // slower with a speed limit
+        assert_eq!(kinematics.link_time(1600, Some(10.)), 17);
+        // a short link never reaches the top speed
+        assert_eq!(kinematics.link_time(100, None), 2);
+        assert!((kinematics.peak_speed(100, None) - 10.).abs() < 1e-9);
+        assert_eq!(kinematics.peak_speed(1600, Some(10.)), 10.);
+    }
+
+    #[test]
+    fn test_chunk_times() {
+        let kinematics = Kinematics::new(1., 1., 20.).set_step_time(10.);
+        // the first and last chunks include the start and the stop
+        assert_eq!(kinematics.chunk_times(&[400, 800, 400], None), [3, 4, 3]);
+        assert_eq!(kinematics.chunk_times(&[1, 1, 1], None), [1, 1, 1]);
+    }
+
+    #[test]
+    fn test_separation() {
+        let kinematics = Kinematics::new(1., 1., 20.).set_step_time(10.);
+        assert_eq!(kinematics.cruise_time(400), 2);
+        assert_eq!(kinematics.cruise_time(10), 1);
+        // 200 m to brake from 20 m/s, 100 m margin: 15 s
+        assert_eq!(kinematics.braking_separation(100.), 2);
+        assert_eq!(kinematics.braking_separation(0.), 1);
+    }
+}
@@ -6,30 +6,65 @@
 //! and current occupation of stations and connecting
 //! railways.
 
+use super::deadlock::{self, Deadlock, DeadlockPolicy};
+use super::dispatch::{Departure, Dispatch, Service, Timetable};
+use super::disruption::{BlockTarget, Delay, DelayCause, FailureModel, SegmentBlock};
+use super::kinematics::Motion;
+use super::platform::PlatformStop;
+use super::service::ServicePattern;
+use super::signalling::Signalling;
 use super::Duration;
 use super::StationID;
 use crate::car;
+use crate::energy::{Energy, EnergyModel};
 use crate::fleet;
-use crate::passenger::callbacks;
+use crate::passenger::callbacks::{self, ActionContext};
+use crate::rolling_stock::RollingStock;
 use crate::station;
 use crate::station::{BoardPassengers, LandPassenger};
 use crate::utils::counter;
+use crate::utils::random::SimRng;
+
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
 
 /// Control the current state of
 /// a given metro line.
 pub struct Line<T> {
-    train_counter: counter::Counter,
     terminus_a: Terminus,
     terminus_b: Terminus,
     railway: Railway,
     fleet: fleet::Fleet<T>,
     network_size: usize,
+    train_capacity: usize,
+    door_throughput: Option<usize>,
+    rolling_stock: Vec<RollingStock>,
+    reference_speed: Option<f64>,
+    energy_model: Option<EnergyModel>,
+    energy: Energy,
+    step_energy: Energy,
+    current_step: usize,
+    next_car_id: usize,
+    service_log: Vec<fleet::CarService>,
+    patterns: Vec<ServicePattern>,
+    failures: Option<FailureModel>,
+    blocks: Vec<(Vec<usize>, SegmentBlock)>,
+    closures: Vec<(Vec<usize>, SegmentBlock)>,
+    rerouted: usize,
+    delays: Vec<Delay>,
+    open_delays: HashMap<usize, usize>,
+    deadlock_policy: DeadlockPolicy,
+    deadlocks: Vec<Deadlock>,
+    deadlocked: HashSet<usize>,
+    short_turns: HashMap<usize, (usize, (usize, usize))>,
+    platform_log: Vec<PlatformStop>,
 }
 
 /// Allow to specify if
 /// direction is from terminus 1 to terminus 2
 /// or vice versa
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum LineDirection {
     DirectionA,
     DirectionB,
@@ -61,56 +96,659 @@ impl<T> Line<T>
 where
     T: callbacks::PassengerAction + Send + Sync,
 {
-    pub fn new<C>(
-        counter: C,
+    pub fn new(
         terminus_a: Terminus,
         terminus_b: Terminus,
         railway: Railway,
         fleet: fleet::Fleet<T>,
         network_size: usize,
-    ) -> Self
-    where
-        C: Into<counter::Counter>,
-    {
+    ) -> Self {
         Line {
-            train_counter: counter.into(),
             terminus_a,
             terminus_b,
             railway,
             fleet,
             network_size,
+            train_capacity: usize::MAX,
+            door_throughput: None,
+            rolling_stock: Vec::new(),
+            reference_speed: None,
+            energy_model: None,
+            energy: Energy::default(),
+            step_energy: Energy::default(),
+            current_step: 0,
+            next_car_id: 0,
+            service_log: Vec::new(),
+            patterns: Vec::new(),
+            failures: None,
+            blocks: Vec::new(),
+            closures: Vec::new(),
+            rerouted: 0,
+            delays: Vec::new(),
+            open_delays: HashMap::new(),
+            deadlock_policy: DeadlockPolicy::default(),
+            deadlocks: Vec::new(),
+            deadlocked: HashSet::new(),
+            short_turns: HashMap::new(),
+            platform_log: Vec::new(),
         }
     }
 
+    /// Set the maximal number of passengers on board of each train.
+    #[must_use]
+    pub fn set_train_capacity(mut self, capacity: usize) -> Self {
+        self.train_capacity = capacity;
+        self
+    }
+
+    /// Set the number of passengers that can board or alight each
+    /// train in a step. Trains then stay in a station until all the
+    /// passengers are done, so crowded stops take longer.
+    #[must_use]
+    pub fn set_door_throughput(mut self, rate: usize) -> Self {
+        self.door_throughput = Some(rate);
+        self
+    }
+
+    /// Mix the given rolling stock classes in the fleet: trains take
+    /// the classes in turn, in the order they are started. The class
+    /// capacity replaces the train capacity.
+    #[must_use]
+    pub fn set_rolling_stock(mut self, classes: Vec<RollingStock>) -> Self {
+        self.rolling_stock = classes;
+        self
+    }
+
+    /// Speed the line segment times refer to. Trains of a class with
+    /// a lower top speed take proportionally longer on the line
+    /// segments. Without a reference speed the class speed is ignored.
+    #[must_use]
+    pub fn set_reference_speed(mut self, speed: f64) -> Self {
+        self.reference_speed = Some(speed);
+        self
+    }
+
+    /// Estimate the energy used by the trains with the given model.
+    /// Without a model no energy is accounted.
+    #[must_use]
+    pub fn set_energy_model(mut self, model: EnergyModel) -> Self {
+        self.energy_model = Some(model);
+        self
+    }
+
+    /// Let the running trains fail at random, see ``inject_failures``.
+    #[must_use]
+    pub fn set_failure_model(mut self, model: FailureModel) -> Self {
+        self.failures = Some(model);
+        self
+    }
+
+    /// Block part of the line during the block window.
+    ///
+    /// # Panics
+    /// If the block station is not on the line or the block
+    /// link does not connect two consecutive stations.
+    #[must_use]
+    pub fn add_segment_block(mut self, block: SegmentBlock) -> Self {
+        let target = block.get_target();
+        let segments = self
+            .railway
+            .target_segments(target)
+            .unwrap_or_else(|| panic!("block target {target:?} is not on the line"));
+        self.blocks.push((segments, block));
+        self
+    }
+
+    /// Choose how trains follow each other between stations,
+    /// each segment being a single block by default.
+    #[must_use]
+    pub fn set_signalling(mut self, signalling: Signalling) -> Self {
+        self.railway = self.railway.set_signalling(signalling);
+        self
+    }
+
+    /// Give a station as many platforms for the trains running in
+    /// the given direction, one by default. Trains take the lowest
+    /// numbered free platform and can leave in any order.
+    ///
+    /// # Panics
+    /// If the station is not on the line or the count is zero.
+    #[must_use]
+    pub fn set_platforms(mut self, station: StationID, dir: LineDirection, count: usize) -> Self {
+        assert!(count > 0, "a station needs a platform");
+        self.railway.set_platforms(station, dir, count);
+        self
+    }
+
+    /// Choose how trains meeting on single track are handled.
+    #[must_use]
+    pub fn set_deadlock_policy(mut self, policy: DeadlockPolicy) -> Self {
+        self.deadlock_policy = policy;
+        self
+    }
+
+    /// Close a station or a link from the start step up to the end
+    /// one, excluded. Trains run through a closed station without
+    /// stopping and turn back in front of a closed link. The
+    /// passengers on board get off at the next stop instead.
+    ///
+    /// # Panics
+    /// If the station is not on the line or the link does
+    /// not connect two consecutive stations.
+    #[must_use]
+    pub fn add_closure(mut self, target: BlockTarget, start: usize, end: usize) -> Self {
+        let segments = self
+            .railway
+            .target_segments(target)
+            .unwrap_or_else(|| panic!("closure target {target:?} is not on the line"));
+        self.closures
+            .push((segments, SegmentBlock::new(target, start, end)));
+        self
+    }
+
+    /// Choose how trains running in the given direction are started,
+    /// instead of the fixed train delay. Dispatch steps count from
+    /// the first call to ``step``.
+    #[must_use]
+    pub fn set_dispatch(mut self, dir: LineDirection, dispatch: Dispatch) -> Self {
+        self.get_terminus_mut(dir).set_dispatch(dispatch);
+        self
+    }
+
+    /// Assign the given service patterns to the trains leaving
+    /// each terminus, in turn. By default trains serve the whole line.
+    /// Trains leaving the end of the main line only follow the
+    /// patterns of the main line. Patterns that do not fit the line
+    /// are ignored.
+    #[must_use]
+    pub fn set_service_patterns(mut self, patterns: Vec<ServicePattern>) -> Self {
+        self.patterns = patterns;
+        self
+    }
+
+    /// Start trains running in the given direction following the timetable.
+    #[must_use]
+    pub fn set_timetable(self, dir: LineDirection, timetable: Timetable) -> Self {
+        self.set_dispatch(dir, timetable.into())
+    }
+
+    /// Start trains running in the given direction following
+    /// the headway profile, within the service span.
+    #[must_use]
+    pub fn set_service(self, dir: LineDirection, service: Service) -> Self {
+        self.set_dispatch(dir, service.into())
+    }
+
     /// Implement a simulation step. Move trains
     /// and, if it is possible, start a new train
     pub fn step(&mut self) {
+        self.step_energy = Energy::default();
         self.move_train();
         self.start_train();
         self.terminus_a.step();
         self.terminus_b.step();
-        // println!{"{:?}", self.railway.line}
+        self.current_step += 1;
+    }
+
+    /// Timetable departures of the trains running in the
+    /// given direction, in departure order.
+    #[must_use]
+    pub fn get_departures(&self, dir: LineDirection) -> &[Departure] {
+        self.get_terminus(dir).get_departures()
     }
 
-    /// Board passengers on the train from the current
-    /// station.
-    pub fn boarding_operations(&mut self, stats: &mut [station::Station<T>]) {
+    /// Land passengers from the train in the current station
+    /// and then board the waiting ones, at the given step.
+    /// line is the index of this line, reported to the passenger
+    /// callbacks. Return the number of passengers that reached
+    /// their final destination.
+    pub fn boarding_operations(
+        &mut self,
+        stats: &mut [station::Station<T>],
+        step: u32,
+        line: usize,
+    ) -> usize {
+        let mut delivered = 0;
         for car in self.fleet.in_station_car_iter() {
-            let station = &mut stats[car.get_current_station()];
-            station.board_passengers(car);
-            station.land_passenger(car);
+            let index = car.get_current_station();
+            let ctx = ActionContext::on_train(step, index, line, car.get_current_direction());
+            let station = &mut stats[index];
+            delivered += station.land_passenger(car, &ctx);
+            station.board_passengers(car, &ctx);
+        }
+        delivered
+    }
+
+    /// In service time of each car withdrawn into a depot,
+    /// in withdrawal order.
+    #[must_use]
+    pub fn get_service_log(&self) -> &[fleet::CarService] {
+        &self.service_log
+    }
+
+    /// Number of trains currently running on the line.
+    #[must_use]
+    pub fn running_trains(&self) -> usize {
+        self.fleet.len()
+    }
+
+    /// Number of passengers currently on board of the line's fleet.
+    #[must_use]
+    pub fn passenger_count(&self) -> usize {
+        self.fleet.passenger_count()
+    }
+
+    /// Number of passengers currently standing on board of the line's fleet.
+    #[must_use]
+    pub fn standing_count(&self) -> usize {
+        self.fleet.standing_count()
+    }
+
+    /// Energy used by the line's trains during the last step.
+    #[must_use]
+    pub fn get_step_energy(&self) -> Energy {
+        self.step_energy
+    }
+
+    /// Energy used by the line's trains since the beginning.
+    #[must_use]
+    pub fn get_energy(&self) -> Energy {
+        self.energy
+    }
+
+    /// Energy used by each train: first the withdrawn ones, in
+    /// withdrawal order, and then the running ones. Each item is
+    /// the car id, its rolling stock class and its energy.
+    pub fn train_energy(&self) -> impl Iterator<Item = (usize, Option<&str>, Energy)> {
+        let withdrawn = self
+            .service_log
+            .iter()
+            .map(|s| (s.get_car(), s.get_class(), s.get_energy()));
+        let running = self.fleet.cars_iter().map(|c| {
+            let class = c.get_rolling_stock().map(RollingStock::get_name);
+            (c.get_id(), class, c.get_energy())
+        });
+        withdrawn.chain(running)
+    }
+
+    /// Let each running train fail with the line failure model,
+    /// if any. A failed train stands still until repaired.
+    pub fn inject_failures(&mut self, rng: &mut SimRng) {
+        if let Some(model) = &self.failures {
+            for train in self.fleet.running_cars_iter() {
+                if !train.is_held() {
+                    if let Some(repair) = model.sample(rng) {
+                        train.hold(repair);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Delays caused by failures and blocks, including the ones
+    /// spread to the following trains, in order of start.
+    #[must_use]
+    pub fn get_delays(&self) -> &[Delay] {
+        &self.delays
+    }
+
+    /// Circular waits detected so far, in detection order.
+    #[must_use]
+    pub fn get_deadlocks(&self) -> &[Deadlock] {
+        &self.deadlocks
+    }
+
+    /// Number of passengers on board sent to another
+    /// stop because of a closure.
+    #[must_use]
+    pub fn rerouted_count(&self) -> usize {
+        self.rerouted
+    }
+
+    /// Platform taken by each train stopping at a station, in
+    /// arrival order. Trains leaving a terminus are logged at
+    /// the platform they start from.
+    #[must_use]
+    pub fn get_platform_log(&self) -> &[PlatformStop] {
+        &self.platform_log
+    }
+
+    /// Shortest time between two trains running in the same
+    /// direction, set by the slowest segment of the line.
+    #[must_use]
+    pub fn min_headway(&self) -> Duration {
+        self.railway.min_headway()
+    }
+
+    /// Trains per hour the line can run at its shortest headway.
+    #[must_use]
+    pub fn capacity(&self, steps_per_hour: usize) -> f64 {
+        steps_per_hour as f64 / self.min_headway() as f64
+    }
+
+    /// Trains per hour that ran through the busiest segment
+    /// of the line so far.
+    #[must_use]
+    pub fn throughput(&self, steps_per_hour: usize) -> f64 {
+        if self.current_step == 0 {
+            return 0.;
         }
+        (self.railway.max_passages() * steps_per_hour) as f64 / self.current_step as f64
     }
 
-    /// Step each train on the line.
+    /// Rolling stock classes mixed in the fleet.
+    #[must_use]
+    pub fn get_rolling_stock(&self) -> &[RollingStock] {
+        &self.rolling_stock
+    }
+
+    /// Step each train on the line. A train ready to turn
+    /// around at a terminus that can receive it goes back
+    /// into the depot instead. Trains that cannot move wait
+    /// for the train in their way, circular waits are deadlocks.
     fn move_train(&mut self) {
-        for train in self.fleet.running_cars_iter() {
+        let step = self.current_step;
+        let mut withdrawn = vec![];
+        let mut delayed = vec![];
+        let mut waits = HashMap::new();
+        let disrupted: Vec<usize> = self
+            .fleet
+            .cars_iter()
+            .filter(|c| c.is_disrupted())
+            .map(car::Car::get_current_segment)
+            .collect();
+        let closed = self.closed_stations(step);
+        let running = self.fleet.len();
+        // set the fleet aside, so that each train can update the line
+        let mut fleet = std::mem::replace(&mut self.fleet, fleet::Fleet::new(0));
+        for (index, train) in fleet.running_cars_iter().enumerate() {
+            let mut cause = train.is_held().then_some(DelayCause::Failure);
+            let curr = train.get_current_segment();
+            if is_blocked(&self.blocks, curr, train.get_current_direction(), step) {
+                // trains in a blocked segment stand still
+                delayed.push((train.get_id(), cause.unwrap_or(DelayCause::Blocked)));
+                train.set_disrupted(true);
+                continue;
+            }
             if train.run_step() {
-                if let Some(NextStepInfo { time, kind, loc }) = self.railway.next_step(train) {
-                    train.next_step(time, kind, loc);
+                self.turn_back_before_closed_link(train, &closed);
+                if self.can_withdraw(train, running - withdrawn.len()) {
+                    withdrawn.push(index);
+                } else if let Some((hold, other)) = self.hold_cause(train) {
+                    cause = Some(hold);
+                    if let Some(other) = other {
+                        waits.insert(train.get_id(), other);
+                    }
+                } else if let Some(info) = self.railway.next_step(train, &closed, step) {
+                    self.enter_segment(train, info, &closed);
+                } else {
+                    let next = self.railway.get_next_trunk(train);
+                    if let Some(other) = self.railway.blocking_train(train) {
+                        waits.insert(train.get_id(), other);
+                    }
+                    if next.is_some_and(|next| disrupted.contains(&next)) {
+                        cause = Some(DelayCause::KnockOn);
+                    }
                 }
             }
+            train.set_disrupted(cause.is_some());
+            if let Some(cause) = cause {
+                delayed.push((train.get_id(), cause));
+            }
+        }
+        self.fleet = fleet;
+        let deadlocked = self.find_deadlocks(&waits);
+        delayed.retain(|(car, _)| !deadlocked.contains(car));
+        for train in self.fleet.running_cars_iter() {
+            if deadlocked.contains(&train.get_id()) {
+                train.set_disrupted(true);
+                delayed.push((train.get_id(), DelayCause::Deadlock));
+            }
+        }
+        for (car, cause) in delayed {
+            self.record_delay(car, cause);
+        }
+        for index in withdrawn.into_iter().rev() {
+            self.withdraw_train(index);
+        }
+    }
+
+    /// Turn the train back when the link ahead is closed and the
+    /// one it stands on is open, sending its passengers to the
+    /// first stop in the new direction.
+    fn turn_back_before_closed_link(&mut self, train: &mut car::Car<T>, closed: &[StationID]) {
+        let step = self.current_step;
+        let closed_link = |segment| {
+            self.closures.iter().any(|(segments, closure)| {
+                matches!(closure.get_target(), BlockTarget::Link(..))
+                    && segments.contains(&segment)
+                    && closure.blocks(LineDirection::DirectionA, step)
+            })
+        };
+        let curr = train.get_current_segment();
+        if train.is_swapping()
+            || !self.railway.get_next_trunk(train).is_some_and(closed_link)
+            || closed_link(curr)
+        {
+            return;
+        }
+        train.turn_back();
+        let stop = self
+            .railway
+            .first_stop(train, train.get_next_direction(), curr, closed);
+        if let Some(stop) = stop {
+            self.rerouted += train.redirect_passengers(None, stop);
+        }
+    }
+
+    /// Check if the empty train turning around at a terminus
+    /// goes back into its depot, given the trains still running.
+    fn can_withdraw(&self, train: &car::Car<T>, running: usize) -> bool {
+        let next_dir = train.get_next_direction();
+        train.is_swapping()
+            && train.get_current_segment() == self.railway.start_index(next_dir)
+            && train.passenger_count() == 0
+            && self.get_terminus(next_dir).can_receive_train(
+                self.current_step,
+                running,
+                self.railway.round_trip(),
+            )
+    }
+
+    /// Reason for the train to stay where it is, although the
+    /// segment ahead may have room, with the train it waits for.
+    fn hold_cause(&self, train: &car::Car<T>) -> Option<(DelayCause, Option<usize>)> {
+        let next_dir = train.get_next_direction();
+        let step = self.current_step;
+        if self
+            .railway
+            .get_next_trunk(train)
+            .is_some_and(|next| is_blocked(&self.blocks, next, next_dir, step))
+        {
+            return Some((DelayCause::Blocked, None));
+        }
+        if self.deadlock_policy != DeadlockPolicy::PassingLoopPriority {
+            return None;
+        }
+        if let Some((segment, dir)) = self.railway.single_track_wait(train) {
+            let other = self.railway.line[segment].last_train(dir);
+            return Some((DelayCause::PassingLoop, other));
+        }
+        if let Some(other) = self.railway.oncoming_train(train) {
+            return Some((DelayCause::PassingLoop, Some(other)));
+        }
+        let next = self.railway.next_loop(train)?;
+        let curr = train.get_current_segment();
+        let entering_full = self
+            .turnback_sections()
+            .into_iter()
+            .any(|(branch, section)| {
+                let segments = self.railway.turnback_section(branch, section);
+                matches!(self.railway.line[curr], Segment::Double(..))
+                    && segments.contains(&next)
+                    && !segments.contains(&curr)
+                    && self.section_full(branch, section)
+            });
+        entering_full.then_some((DelayCause::PassingLoop, None))
+    }
+
+    /// Sections of the line between a segment where trains turn back
+    /// running in ``DirectionA`` and one where trains turn back running
+    /// in ``DirectionB``, other than the whole branches: the trains in
+    /// such a section can wait for each other in a circle. Lines
+    /// without short turns have none.
+    fn turnback_sections(&self) -> Vec<(usize, (usize, usize))> {
+        let turnbacks: Vec<_> = self
+            .patterns
+            .iter()
+            .filter(|p| self.railway.fits(p))
+            .filter_map(|p| self.railway.turnback_segments(p))
+            .collect();
+        if turnbacks.is_empty() {
+            return Vec::new();
         }
+        self.railway.turnback_sections(&turnbacks)
+    }
+
+    /// Check if one more train in the section would fill it while
+    /// no train on its way out of it stands at its ends: the trains
+    /// in it could then wait for each other.
+    fn section_full(&self, branch: usize, section: (usize, usize)) -> bool {
+        let trains = self.railway.section_trains(branch, section) + 1;
+        let slots = self.railway.section_slots(branch, section);
+        trains > slots
+            || trains == slots
+                && self
+                    .railway
+                    .section_exits(branch, section)
+                    .into_iter()
+                    .all(|(car, end, dir)| {
+                        self.short_turns.get(&car).is_some_and(|(_, turnback)| {
+                            dir.choose_direction(turnback.0, turnback.1) == end
+                        })
+                    })
+    }
+
+    /// Check if the trains turning back in the short-turn section
+    /// already take all its tracks but one, or all but two when other
+    /// trains run through it.
+    fn section_served(&self, branch: usize, turnback: (usize, usize)) -> bool {
+        let turning = self
+            .short_turns
+            .values()
+            .filter(|section| **section == (branch, turnback))
+            .count();
+        let shared = self.patterns.iter().any(|p| {
+            self.railway.fits(p)
+                && (p.get_branch() != branch || self.railway.turnback_segments(p) != Some(turnback))
+        });
+        turning + 2 + usize::from(shared) > self.railway.section_slots(branch, turnback)
+    }
+
+    /// Update the train that just entered a segment: its running
+    /// time, its energy, its next stop on a loop and the platform
+    /// log. Passengers going to a closed station it passes
+    /// through are sent to the next stop.
+    fn enter_segment(&mut self, train: &mut car::Car<T>, info: NextStepInfo, closed: &[StationID]) {
+        let step = self.current_step;
+        let next_dir = train.get_next_direction();
+        let time = match (info.kind, self.reference_speed, train.get_rolling_stock()) {
+            (SegmentType::Line, Some(speed), Some(stock)) => stock.running_time(info.time, speed),
+            _ => info.time,
+        };
+        let departing = train.in_station();
+        train.next_step(time, info.kind, info.loc);
+        if let Some(model) = &self.energy_model {
+            let energy = model.segment_energy(train, departing, time, info.motion);
+            train.add_energy(energy);
+            self.step_energy += energy;
+            self.energy += energy;
+        }
+        if let Some(destination) = info.destination {
+            train.set_destination(destination);
+        }
+        match (info.kind, info.platform) {
+            (SegmentType::Station(i) | SegmentType::Terminus(i), Some(platform)) => {
+                self.platform_log.push(PlatformStop::new(
+                    train.get_id(),
+                    i,
+                    next_dir,
+                    platform,
+                    step,
+                ));
+            }
+            // passing through a closed station
+            (SegmentType::Station(i), None) if closed.contains(&i) => {
+                let segment = train.get_current_segment();
+                let stop = self.railway.first_stop(train, next_dir, segment, closed);
+                if let Some(stop) = stop {
+                    self.rerouted += train.redirect_passengers(Some(i), stop);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Find the trains in a circular wait, given the train each
+    /// waiting train waits for. Log the deadlocks not found in
+    /// the previous step.
+    fn find_deadlocks(&mut self, waits: &HashMap<usize, usize>) -> HashSet<usize> {
+        let cycles = deadlock::find_cycles(waits);
+        for cycle in &cycles {
+            if !cycle.iter().all(|car| self.deadlocked.contains(car)) {
+                self.deadlocks
+                    .push(Deadlock::new(self.current_step, cycle.clone()));
+            }
+        }
+        self.deadlocked = cycles.into_iter().flatten().collect();
+        self.deadlocked.clone()
+    }
+
+    /// Stations closed at the given step.
+    fn closed_stations(&self, step: usize) -> Vec<StationID> {
+        self.closures
+            .iter()
+            .filter(|(_, closure)| closure.blocks(LineDirection::DirectionA, step))
+            .filter_map(|(_, closure)| match closure.get_target() {
+                BlockTarget::Station(s) => Some(s),
+                BlockTarget::Link(..) => None,
+            })
+            .collect()
+    }
+
+    /// Log a step of delay of the given car, extending
+    /// its last delay if it has the same cause.
+    fn record_delay(&mut self, car: usize, cause: DelayCause) {
+        let step = self.current_step;
+        if let Some(&index) = self.open_delays.get(&car) {
+            if self.delays[index].extend(step, cause) {
+                return;
+            }
+        }
+        self.open_delays.insert(car, self.delays.len());
+        self.delays.push(Delay::new(car, step, cause));
+    }
+
+    /// Move the train back into the depot of the terminus where it
+    /// stands and log its service time.
+    fn withdraw_train(&mut self, index: usize) {
+        let train = self.fleet.withdraw_train(index);
+        self.short_turns.remove(&train.get_id());
+        let segment = train.get_current_segment();
+        self.railway.line[segment].leave(train.get_id(), train.get_current_direction());
+        self.get_terminus_mut(train.get_next_direction())
+            .receive_train();
+        let service = fleet::CarService::new(
+            train.get_id(),
+            train.get_departure_step(),
+            self.current_step,
+        );
+        let service = service.set_energy(train.get_energy());
+        let service = match train.get_rolling_stock() {
+            Some(stock) => service.set_class(stock.get_name()),
+            None => service,
+        };
+        self.service_log.push(service);
     }
 
     /// Start train on each direction
@@ -119,38 +757,138 @@ where
         self.try_start_new_train(LineDirection::DirectionB);
     }
 
-    /// Try to start a train in a given direction
+    /// Try to start a train in a given direction. At a fixed headway
+    /// a train due to leave queues at the terminus platform. Otherwise,
+    /// or with the passing loop priority, it waits while the platform is
+    /// occupied, which makes it late, or while the passing loop priority
+    /// holds it in the depot, which does not.
     fn try_start_new_train(&mut self, dir: LineDirection) {
-        if self.can_start_new_train(dir) {
+        let step = self.current_step;
+        if !self.can_start_new_train(dir) {
+            return;
+        }
+        let holds = self.get_terminus(dir).holds_trains()
+            || self.deadlock_policy == DeadlockPolicy::PassingLoopPriority;
+        if holds && !self.railway.can_start(dir, step) {
+            self.get_terminus_mut(dir).wait(step);
+        } else if !self.start_held(dir) {
             self.start_new_train(dir);
         }
     }
 
-    /// Check if it is possible to start a new train
-    fn can_start_new_train(&self, dir: LineDirection) -> bool {
-        if self.train_counter.is_done() {
+    /// With the passing loop priority, a train leaves the depot
+    /// only after the trains turning around in the terminus. Taking
+    /// their platform would fill the last free track of the line.
+    /// Likewise, it does not enter a short-turn section with a single
+    /// free track, and a train turning back early does not leave
+    /// while the ones turning back in the same section fill it.
+    fn start_held(&self, dir: LineDirection) -> bool {
+        if self.deadlock_policy != DeadlockPolicy::PassingLoopPriority {
             return false;
         }
-        if !self.railway.get_terminus(dir).is_free(dir) {
-            return false;
+        if self.railway.turning_train(dir).is_some() {
+            return true;
+        }
+        let route = self.next_route(dir);
+        if let Some(turnback) = route.get_turnback() {
+            if self.section_served(route.get_branch(), turnback) {
+                return true;
+            }
         }
-        self.get_terminus(dir).can_start_new_train()
+        let start = self.get_terminus_index(dir);
+        self.turnback_sections()
+            .into_iter()
+            .any(|(branch, section)| {
+                self.railway
+                    .turnback_section(branch, section)
+                    .contains(&start)
+                    && self.section_full(branch, section)
+            })
+    }
+
+    /// Check if a train is due to leave and available in the depot
+    fn can_start_new_train(&self, dir: LineDirection) -> bool {
+        self.get_terminus(dir)
+            .can_start_new_train(self.current_step)
     }
 
     /// actually start a train
     fn start_new_train(&mut self, dir: LineDirection) {
-        self.get_terminus_mut(dir).add_new_train();
-        self.train_counter.step();
+        let step = self.current_step;
+        let route = self.next_route(dir);
+        if let Some(turnback) = route.get_turnback() {
+            self.short_turns
+                .insert(self.next_car_id, (route.get_branch(), turnback));
+        }
+        self.get_terminus_mut(dir).add_new_train(step);
         let station_index = self.get_terminus(dir).get_station_id();
+        let destination = match dir {
+            _ if self.railway.is_circular() => station_index,
+            LineDirection::DirectionA => self.get_terminus(dir.other()).get_station_id(),
+            LineDirection::DirectionB => self.railway.branch_ends()[route.get_branch()],
+        };
         let segment_index = self.get_terminus_index(dir);
-        let location = car::CarLocation::station(segment_index, station_index);
-        let duration = self.railway.get_segment_duration(dir, segment_index);
-        let car = car::Car::new(station_index, location, dir, self.network_size, duration);
+        let closed = self.closed_stations(step);
+        let platform = self.railway.line[segment_index].enter(self.next_car_id, dir, step);
+        // on a loop the first station can be closed,
+        // trains then leave it without stopping
+        let (location, duration) = if closed.contains(&station_index) {
+            (car::CarLocation::segment(segment_index), PASS_THROUGH_TIME)
+        } else {
+            self.platform_log.push(PlatformStop::new(
+                self.next_car_id,
+                station_index,
+                dir,
+                platform,
+                step,
+            ));
+            (
+                car::CarLocation::station(segment_index, station_index),
+                self.railway.get_segment_duration(dir, segment_index),
+            )
+        };
+        let mut car = car::Car::new(destination, location, dir, self.network_size, duration)
+            .set_capacity(self.train_capacity)
+            .set_id(self.next_car_id)
+            .set_departure_step(step)
+            .set_route(route);
+        if let Some(rate) = self.door_throughput {
+            car = car.set_door_throughput(rate);
+        }
+        if !self.rolling_stock.is_empty() {
+            let class = self.next_car_id % self.rolling_stock.len();
+            car = car.set_rolling_stock(self.rolling_stock[class].clone());
+        }
+        if self.railway.is_circular() {
+            let next_stop = self.railway.next_stop(&car, dir, segment_index, &closed);
+            car.set_destination(next_stop);
+        }
+        self.next_car_id += 1;
         self.fleet.start_train(car);
     }
 
+    /// Route of the next train leaving in the given direction.
+    /// Trains only take passengers going to the stations they
+    /// stop at, even if they serve the whole line.
+    fn next_route(&self, dir: LineDirection) -> car::CarRoute {
+        let patterns: Vec<&ServicePattern> = self
+            .patterns
+            .iter()
+            .filter(|p| dir == LineDirection::DirectionB || p.get_branch() == 0)
+            .filter(|p| self.railway.fits(p))
+            .collect();
+        let full = ServicePattern::full();
+        let pattern = if patterns.is_empty() {
+            &full
+        } else {
+            let started = self.get_terminus(dir).get_started();
+            patterns[started % patterns.len()]
+        };
+        self.railway.make_route(pattern, self.network_size)
+    }
+
     fn get_terminus_index(&self, dir: LineDirection) -> usize {
-        dir.choose_direction(self.railway.last_index(), 0)
+        self.railway.start_index(dir)
     }
 
     fn get_terminus(&self, dir: LineDirection) -> &'_ Terminus {
@@ -163,15 +901,160 @@ where
 }
 
 /// Implement the railway line. A Railway line is made of
-/// trunks.
+/// trunks. The main line runs from the first to the last segment;
+/// each branch shares the main line up to a junction and then
+/// continues on its own segments up to its terminus. On a loop
+/// the last segment is linked back to the first one.
 #[derive(Debug)]
 pub struct Railway {
     line: Vec<Segment>,
+    branches: Vec<Vec<usize>>,
+    depth: Vec<usize>,
+    circular: bool,
+    signalling: Signalling,
 }
 
 impl Railway {
     pub fn new(line: Vec<Segment>) -> Self {
-        Railway { line }
+        let main: Vec<usize> = (0..line.len()).collect();
+        Railway {
+            line,
+            depth: main.clone(),
+            branches: vec![main],
+            circular: false,
+            signalling: Signalling::default(),
+        }
+    }
+
+    /// Initialize a loop railway: trains leaving the last segment
+    /// in ``DirectionB`` enter the first one, and vice versa. Trains
+    /// of both directions start from the first segment.
+    pub fn new_loop(line: Vec<Segment>) -> Self {
+        Railway {
+            circular: true,
+            ..Self::new(line)
+        }
+    }
+
+    fn is_circular(&self) -> bool {
+        self.circular
+    }
+
+    /// Choose how trains follow each other between stations.
+    #[must_use]
+    pub fn set_signalling(mut self, signalling: Signalling) -> Self {
+        self.signalling = signalling;
+        self
+    }
+
+    /// Give the given station as many platforms in the given direction.
+    ///
+    /// # Panics
+    /// If the station is not on the railway.
+    fn set_platforms(&mut self, station: StationID, dir: LineDirection, count: usize) {
+        let segments = self
+            .target_segments(BlockTarget::Station(station))
+            .unwrap_or_else(|| panic!("station {station} is not on the line"));
+        for segment in segments {
+            self.line[segment].set_platforms(dir, count);
+        }
+    }
+
+    /// Check if a train can start in the given direction at the given step.
+    fn can_start(&self, dir: LineDirection, step: usize) -> bool {
+        self.line[self.start_index(dir)].can_enter(dir, step, &self.signalling)
+    }
+
+    /// Shortest time between two trains entering the same segment:
+    /// the dwell time at stations, shared among their platforms,
+    /// and the separation the signalling allows between stations.
+    fn min_headway(&self) -> Duration {
+        self.line
+            .iter()
+            .flat_map(|segment| {
+                [LineDirection::DirectionA, LineDirection::DirectionB].map(|dir| {
+                    let info = segment.choose_segment_info(dir);
+                    match info.get_type() {
+                        SegmentType::Line => self.signalling.min_headway(info.get_duration()),
+                        _ => info.get_duration().div_ceil(info.platforms),
+                    }
+                })
+            })
+            .max()
+            .unwrap_or_default()
+            .max(1)
+    }
+
+    /// Steps a train takes to run the main line in both directions.
+    fn round_trip(&self) -> Duration {
+        self.branches[0]
+            .iter()
+            .map(|i| {
+                let segment = &self.line[*i];
+                segment.get_duration(LineDirection::DirectionA)
+                    + segment.get_duration(LineDirection::DirectionB)
+            })
+            .sum()
+    }
+
+    /// Number of trains that entered the busiest segment.
+    fn max_passages(&self) -> usize {
+        self.line
+            .iter()
+            .flat_map(|segment| match segment {
+                Segment::Single(info) => [info.get_passages(), 0],
+                Segment::Double(a, b) => [a.get_passages(), b.get_passages()],
+            })
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Add a branch leaving the main line after the given station.
+    /// The segments run from the junction to the branch terminus.
+    /// Branches are numbered from 1 in insertion order, the main
+    /// line being branch 0.
+    ///
+    /// # Panics
+    /// If the junction is not an intermediate station of the main
+    /// line or if the railway is a loop.
+    #[must_use]
+    pub fn add_branch(mut self, junction: StationID, segments: Vec<Segment>) -> Self {
+        assert!(!self.circular, "a loop cannot have branches");
+        let junction = self
+            .find_station_on(0, junction)
+            .filter(|i| *i != 0 && *i != self.last_index())
+            .unwrap_or_else(|| panic!("junction {junction} is not on the main line"));
+        let mut path = self.branches[0][..=junction].to_vec();
+        for segment in segments {
+            self.depth.push(path.len());
+            path.push(self.line.len());
+            self.line.push(segment);
+        }
+        self.branches.push(path);
+        self
+    }
+
+    /// Number of branches, main line included.
+    fn branch_count(&self) -> usize {
+        self.branches.len()
+    }
+
+    /// Last station of each branch, in branch order.
+    fn branch_ends(&self) -> Vec<StationID> {
+        self.branches
+            .iter()
+            .map(|path| {
+                path.iter()
+                    .rev()
+                    .find_map(
+                        |i| match self.line[*i].get_type(LineDirection::DirectionA) {
+                            SegmentType::Terminus(s) | SegmentType::Station(s) => Some(s),
+                            SegmentType::Line => None,
+                        },
+                    )
+                    .unwrap_or_default()
+            })
+            .collect()
     }
 
     fn get_segment_duration(&self, dir: LineDirection, index: usize) -> usize {
@@ -182,60 +1065,521 @@ impl Railway {
     /// Check if it is possible to occupy the next trunk (relative to direction)
     /// If it is possible perform the actual truck state update and return info
     /// about the next step
-    fn next_step<T>(&mut self, train: &car::Car<T>) -> Option<NextStepInfo> {
-        if self.is_free(train) {
-            Some(self.update_car_location(train))
+    fn next_step<T>(
+        &mut self,
+        train: &car::Car<T>,
+        closed: &[StationID],
+        step: usize,
+    ) -> Option<NextStepInfo> {
+        let next = self.get_next_trunk(train)?;
+        if self.can_move(train, step) {
+            Some(self.update_car_location(train, next, closed, step))
         } else {
             None
         }
     }
 
-    /// Check if the next step is free or not.
-    fn is_free<T>(&self, train: &car::Car<T>) -> bool {
-        let next = self.get_next_trunk(train);
-        self.line[next].is_free(train.get_next_direction())
+    /// Check if the train can leave its segment, the first
+    /// one to leave, and enter the next one at the given step.
+    fn can_move<T>(&self, train: &car::Car<T>, step: usize) -> bool {
+        let curr = train.get_current_segment();
+        let Some(next) = self.get_next_trunk(train) else {
+            return false;
+        };
+        self.line[curr].is_leading(train.get_id(), train.get_current_direction())
+            && self.line[next].can_enter(train.get_next_direction(), step, &self.signalling)
+    }
+
+    /// Train the given one is waiting for, if it cannot move:
+    /// the train ahead in its segment or the last one that
+    /// entered the next segment.
+    fn blocking_train<T>(&self, train: &car::Car<T>) -> Option<usize> {
+        let id = train.get_id();
+        let curr = &self.line[train.get_current_segment()];
+        let blocking = if curr.is_leading(id, train.get_current_direction()) {
+            let next = self.get_next_trunk(train)?;
+            self.line[next].last_train(train.get_next_direction())
+        } else {
+            curr.first_train(train.get_current_direction())
+        };
+        blocking.filter(|other| *other != id)
     }
 
-    /// update truck state: free previous and occupy current.
-    fn update_car_location<T>(&mut self, train: &car::Car<T>) -> NextStepInfo {
-        let next = self.get_next_trunk(train);
+    /// update truck state: free previous and occupy next.
+    /// A train passes through the stations it does not serve, and
+    /// the closed ones, without dwelling there. On a loop, a train
+    /// stopping at a station takes the passengers going to its next stop.
+    fn update_car_location<T>(
+        &mut self,
+        train: &car::Car<T>,
+        next: usize,
+        closed: &[StationID],
+        step: usize,
+    ) -> NextStepInfo {
         let curr_dir = train.get_current_direction();
         let next_dir = train.get_next_direction();
         let curr = train.get_current_segment();
-        self.line[next].set_occupied(next_dir);
-        self.line[curr].set_free(curr_dir);
-        NextStepInfo {
-            kind: self.line[next].get_type(next_dir),
-            time: self.line[next].get_duration(next_dir),
-            loc: self.line[next].make_location(next_dir, next),
+        self.line[curr].leave(train.get_id(), curr_dir);
+        let platform = self.line[next].enter(train.get_id(), next_dir, step);
+        let kind = self.line[next].get_type(next_dir);
+        match kind {
+            SegmentType::Station(i) if !train.serves(i) || closed.contains(&i) => NextStepInfo {
+                kind,
+                time: PASS_THROUGH_TIME,
+                loc: car::CarLocation::segment(next),
+                destination: None,
+                platform: None,
+                motion: None,
+            },
+            _ => NextStepInfo {
+                kind,
+                time: self.line[next].get_duration(next_dir),
+                loc: self.line[next].make_location(next_dir, next),
+                destination: self
+                    .is_circular()
+                    .then(|| self.next_stop(train, next_dir, next, closed)),
+                platform: Some(platform),
+                // a train stopping brakes from the speed of the link it ran
+                motion: match kind {
+                    SegmentType::Line => self.line[next].get_motion(next_dir),
+                    _ => self.line[curr].get_motion(curr_dir),
+                },
+            },
         }
     }
 
-    /// Get terminus for given direction
-    fn get_terminus(&self, dir: LineDirection) -> &'_ Segment {
-        dir.choose_direction(self.line.first().unwrap(), self.line.last().unwrap())
+    /// First station after the given segment, running in the
+    /// given direction, where the train stops.
+    fn next_stop<T>(
+        &self,
+        train: &car::Car<T>,
+        dir: LineDirection,
+        segment: usize,
+        closed: &[StationID],
+    ) -> StationID {
+        let len = self.line.len();
+        (1..=len)
+            .map(|i| match dir {
+                LineDirection::DirectionA => (segment + len - i) % len,
+                LineDirection::DirectionB => (segment + i) % len,
+            })
+            .find_map(|i| match self.line[i].get_type(dir) {
+                SegmentType::Station(s) | SegmentType::Terminus(s)
+                    if train.serves(s) && !closed.contains(&s) =>
+                {
+                    Some(s)
+                }
+                _ => None,
+            })
+            .unwrap_or(train.get_destination())
+    }
+
+    /// First station from the given segment on, running in the given
+    /// direction along the train branch, where the train stops.
+    fn first_stop<T>(
+        &self,
+        train: &car::Car<T>,
+        dir: LineDirection,
+        segment: usize,
+        closed: &[StationID],
+    ) -> Option<StationID> {
+        let path = &self.branches[train.get_branch()];
+        let depth = self.depth[segment];
+        let len = path.len();
+        let positions: Vec<usize> = match dir {
+            LineDirection::DirectionA if self.circular => {
+                (0..len).map(|i| (depth + len - i) % len).collect()
+            }
+            LineDirection::DirectionB if self.circular => {
+                (0..len).map(|i| (depth + i) % len).collect()
+            }
+            LineDirection::DirectionA => (0..=depth).rev().collect(),
+            LineDirection::DirectionB => (depth..len).collect(),
+        };
+        positions
+            .into_iter()
+            .find_map(|i| match self.line[path[i]].get_type(dir) {
+                SegmentType::Station(s) | SegmentType::Terminus(s)
+                    if train.serves(s) && !closed.contains(&s) =>
+                {
+                    Some(s)
+                }
+                _ => None,
+            })
+    }
+
+    /// Get terminus for given direction, the one
+    /// reached by the trains running in it.
+    fn get_terminus(&self, dir: LineDirection) -> &'_ Segment {
+        dir.choose_direction(&self.line[0], &self.line[self.last_index()])
+    }
+
+    /// Index of the segment where trains running in the given
+    /// direction start.
+    fn start_index(&self, dir: LineDirection) -> usize {
+        if self.circular {
+            0
+        } else {
+            dir.choose_direction(self.last_index(), 0)
+        }
+    }
+
+    /// Index of the last segment of the main line.
+    fn last_index(&self) -> usize {
+        self.branches[0].len() - 1
+    }
+
+    /// Index of the segment of the given station on the given branch.
+    fn find_station_on(&self, branch: usize, station: StationID) -> Option<usize> {
+        self.branches[branch].iter().copied().find(|i| {
+            matches!(
+                self.line[*i].get_type(LineDirection::DirectionA),
+                SegmentType::Station(s) | SegmentType::Terminus(s) if s == station
+            )
+        })
+    }
+
+    /// Segments of a block target. A link is made of the segments
+    /// between two consecutive stations of any branch, on a loop
+    /// the last station is consecutive to the first one.
+    fn target_segments(&self, target: BlockTarget) -> Option<Vec<usize>> {
+        match target {
+            BlockTarget::Station(s) => (0..self.branch_count())
+                .find_map(|b| self.find_station_on(b, s))
+                .map(|i| vec![i]),
+            BlockTarget::Link(a, b) => (0..self.branch_count()).find_map(|branch| {
+                let path = &self.branches[branch];
+                let a = self.depth[self.find_station_on(branch, a)?];
+                let b = self.depth[self.find_station_on(branch, b)?];
+                let (first, last) = (a.min(b), a.max(b));
+                let mut links = vec![path[first + 1..last].to_vec()];
+                if self.circular {
+                    links.push(
+                        path[last + 1..]
+                            .iter()
+                            .chain(&path[..first])
+                            .copied()
+                            .collect(),
+                    );
+                }
+                links.into_iter().find(|link| {
+                    !link.is_empty()
+                        && link.iter().all(|i| {
+                            self.line[*i].get_type(LineDirection::DirectionA) == SegmentType::Line
+                        })
+                })
+            }),
+        }
+    }
+
+    /// Check if a pattern runs on a branch of the railway, turns
+    /// back at stations of its branch and skips intermediate
+    /// stations only. A loop has no turnback and no skipped station.
+    fn fits(&self, pattern: &ServicePattern) -> bool {
+        let branch = pattern.get_branch();
+        if branch >= self.branch_count() {
+            return false;
+        }
+        let turnback = pattern.get_turnback();
+        if self.circular && !pattern.get_skipped().is_empty() {
+            return false;
+        }
+        if let Some((first, last)) = turnback {
+            if self.circular
+                || self.find_station_on(branch, first).is_none()
+                || self.find_station_on(branch, last).is_none()
+            {
+                return false;
+            }
+        }
+        pattern.get_skipped().iter().all(|station| {
+            let is_turnback = turnback.is_some_and(|(a, b)| a == *station || b == *station);
+            !is_turnback
+                && self.find_station_on(branch, *station).is_some_and(|i| {
+                    i != 0
+                        && matches!(
+                            self.line[i].get_type(LineDirection::DirectionA),
+                            SegmentType::Station(_)
+                        )
+                })
+        })
+    }
+
+    /// Build the route of a train following the given pattern,
+    /// that must fit the railway.
+    fn make_route(&self, pattern: &ServicePattern, network_size: usize) -> car::CarRoute {
+        let path = &self.branches[pattern.get_branch()];
+        let turnback = self.turnback_segments(pattern);
+        let (first, last) = match turnback {
+            Some((first, last)) => (self.depth[first], self.depth[last]),
+            None => (0, path.len() - 1),
+        };
+        let mut stops = vec![false; network_size];
+        for segment in &path[first..=last] {
+            if let SegmentType::Station(i) | SegmentType::Terminus(i) =
+                self.line[*segment].get_type(LineDirection::DirectionA)
+            {
+                stops[i] = true;
+            }
+        }
+        for station in pattern.get_skipped() {
+            stops[*station] = false;
+        }
+        let mut route = car::CarRoute::new(stops);
+        if let Some(turnback) = turnback {
+            route = route.set_turnback(turnback);
+        }
+        if self.branch_count() > 1 {
+            route
+                .set_branch(pattern.get_branch())
+                .set_branch_ends(self.branch_ends())
+        } else {
+            route
+        }
+    }
+
+    /// Segments where a train following the given pattern, that
+    /// must fit the railway, turns back before the termini: the
+    /// one closer to the start of its branch first.
+    fn turnback_segments(&self, pattern: &ServicePattern) -> Option<(usize, usize)> {
+        let branch = pattern.get_branch();
+        let (first, last) = pattern.get_turnback()?;
+        let first = self.find_station_on(branch, first)?;
+        let last = self.find_station_on(branch, last)?;
+        if self.depth[first] <= self.depth[last] {
+            Some((first, last))
+        } else {
+            Some((last, first))
+        }
+    }
+
+    /// Segment the train enters next, if any: a train at the end
+    /// of its branch has nowhere to go unless it turns around.
+    fn get_next_trunk<T>(&self, train: &car::Car<T>) -> Option<usize> {
+        let curr = train.get_current_segment();
+        if train.is_swapping() {
+            Some(curr)
+        } else {
+            self.next_on_path(train.get_branch(), curr, train.get_next_direction())
+        }
+    }
+
+    /// Segment after the given one on the branch path, running
+    /// in the given direction, if any.
+    fn next_on_path(&self, branch: usize, segment: usize, dir: LineDirection) -> Option<usize> {
+        let path = &self.branches[branch];
+        let depth = self.depth[segment];
+        let len = path.len();
+        match dir {
+            LineDirection::DirectionA if self.circular => Some(path[(depth + len - 1) % len]),
+            LineDirection::DirectionB if self.circular => Some(path[(depth + 1) % len]),
+            LineDirection::DirectionA => depth.checked_sub(1).map(|i| path[i]),
+            LineDirection::DirectionB => path.get(depth + 1).copied(),
+        }
+    }
+
+    /// If the train is about to enter single track it cannot clear,
+    /// return the occupied segment, and the direction, in its way: a
+    /// single track segment ahead or the segment right after them.
+    fn single_track_wait<T>(&self, train: &car::Car<T>) -> Option<(usize, LineDirection)> {
+        let mut segment = self.get_next_trunk(train)?;
+        if train.is_swapping() || !matches!(self.line[segment], Segment::Single(_)) {
+            return None;
+        }
+        let dir = train.get_next_direction();
+        let branch = train.get_branch();
+        for _ in 0..self.branches[branch].len() {
+            segment = self.next_on_path(branch, segment, dir)?;
+            if !self.line[segment].has_room(dir) {
+                return Some((segment, dir));
+            }
+            if !matches!(self.line[segment], Segment::Single(_)) {
+                return None;
+            }
+        }
+        None
+    }
+
+    /// Train turning around in the terminus where trains running in
+    /// the given direction start: it comes from the other side and is
+    /// queued for the platform a train leaving the depot would take.
+    fn turning_train(&self, dir: LineDirection) -> Option<usize> {
+        if self.circular {
+            return None;
+        }
+        self.get_terminus(dir.other()).first_train(dir.other())
+    }
+
+    /// Train on the single track behind a train turning back, running
+    /// toward the last free platform the latter is about to take.
+    fn oncoming_train<T>(&self, train: &car::Car<T>) -> Option<usize> {
+        if !train.is_swapping() {
+            return None;
+        }
+        let dir = train.get_next_direction();
+        let branch = train.get_branch();
+        let station = train.get_current_segment();
+        let info = self.line[station].choose_segment_info(dir);
+        if info.trains.len() + 1 < info.slots() {
+            return None;
+        }
+        let mut segment = station;
+        for _ in 0..self.branches[branch].len() {
+            segment = self.next_on_path(branch, segment, dir.other())?;
+            let Segment::Single(info) = &self.line[segment] else {
+                return None;
+            };
+            if let Some(other) = info.trains.iter().find(|o| o.dir == dir) {
+                return Some(other.car);
+            }
+        }
+        None
+    }
+
+    /// Station or passing loop the train reaches next: the segment
+    /// ahead, or the one after the single track ahead.
+    fn next_loop<T>(&self, train: &car::Car<T>) -> Option<usize> {
+        if train.is_swapping() {
+            return None;
+        }
+        let dir = train.get_next_direction();
+        let branch = train.get_branch();
+        let mut segment = self.get_next_trunk(train)?;
+        for _ in 0..self.branches[branch].len() {
+            if !matches!(self.line[segment], Segment::Single(_)) {
+                return Some(segment);
+            }
+            segment = self.next_on_path(branch, segment, dir)?;
+        }
+        None
+    }
+
+    /// Segments of the branch between the ends of the
+    /// section, both included.
+    fn turnback_section(&self, branch: usize, section: (usize, usize)) -> &[usize] {
+        &self.branches[branch][self.depth[section.0]..=self.depth[section.1]]
+    }
+
+    /// Trains the stations and passing loops of a
+    /// section can hold, in both directions.
+    fn section_slots(&self, branch: usize, section: (usize, usize)) -> usize {
+        self.turnback_section(branch, section)
+            .iter()
+            .map(|i| match &self.line[*i] {
+                Segment::Single(_) => 0,
+                Segment::Double(a, b) => a.slots() + b.slots(),
+            })
+            .sum()
+    }
+
+    /// Trains in a section, or on the single track
+    /// around it running into the section.
+    fn section_trains(&self, branch: usize, section: (usize, usize)) -> usize {
+        let inside: usize = self
+            .turnback_section(branch, section)
+            .iter()
+            .map(|i| self.line[*i].train_count())
+            .sum();
+        let ends = [
+            (section.0, LineDirection::DirectionA),
+            (section.1, LineDirection::DirectionB),
+        ];
+        let mut coming = 0;
+        for (mut segment, dir) in ends {
+            while let Some(next) = self.next_on_path(branch, segment, dir) {
+                let Segment::Single(info) = &self.line[next] else {
+                    break;
+                };
+                coming += info.trains.iter().filter(|o| o.dir != dir).count();
+                segment = next;
+            }
+        }
+        inside + coming
     }
 
-    fn last_index(&self) -> usize {
-        self.line.len() - 1
+    /// Trains at the ends of a section, with the end and their
+    /// direction, that leave it from there unless they turn back: at
+    /// its first segment running in ``DirectionA``, at its last one
+    /// running in ``DirectionB``.
+    fn section_exits(
+        &self,
+        branch: usize,
+        section: (usize, usize),
+    ) -> Vec<(usize, usize, LineDirection)> {
+        let ends = [
+            (section.0, LineDirection::DirectionA),
+            (section.1, LineDirection::DirectionB),
+        ];
+        ends.into_iter()
+            .filter(|(end, dir)| self.next_on_path(branch, *end, *dir).is_some())
+            .flat_map(|(end, dir)| {
+                self.line[end]
+                    .choose_segment_info(dir)
+                    .trains
+                    .iter()
+                    .map(move |o| (o.car, end, dir))
+            })
+            .collect()
     }
-    fn get_next_trunk<T>(&self, train: &car::Car<T>) -> usize {
-        let curr = train.get_current_segment();
-        if train.is_swapping() {
-            curr
-        } else {
-            match train.get_next_direction() {
-                LineDirection::DirectionA => curr - 1,
-                LineDirection::DirectionB => curr + 1,
+
+    /// Sections between the given turnback segments, or the termini,
+    /// with a branch they lie on: each starts where trains turn back
+    /// running in ``DirectionA`` and ends where trains turn back
+    /// running in ``DirectionB``. Whole branches are left out.
+    fn turnback_sections(&self, turnbacks: &[(usize, usize)]) -> Vec<(usize, (usize, usize))> {
+        let firsts = turnbacks
+            .iter()
+            .map(|t| t.0)
+            .chain(self.branches.iter().map(|path| path[0]));
+        let lasts: Vec<usize> = turnbacks
+            .iter()
+            .map(|t| t.1)
+            .chain(self.branches.iter().map(|path| path[path.len() - 1]))
+            .collect();
+        let mut sections: Vec<(usize, (usize, usize))> = Vec::new();
+        for first in firsts {
+            for last in &lasts {
+                let section = (first, *last);
+                let branch = self.branches.iter().position(|path| {
+                    path.contains(&first)
+                        && path.contains(last)
+                        && self.depth[first] <= self.depth[*last]
+                        && section != (path[0], path[path.len() - 1])
+                });
+                if let Some(branch) = branch {
+                    if sections.iter().all(|(_, s)| *s != section) {
+                        sections.push((branch, section));
+                    }
+                }
             }
         }
+        sections
     }
 }
 
+/// Check if any of the blocks stops trains running in
+/// the given direction on the given segment at the given step.
+fn is_blocked(
+    blocks: &[(Vec<usize>, SegmentBlock)],
+    segment: usize,
+    dir: LineDirection,
+    step: usize,
+) -> bool {
+    blocks
+        .iter()
+        .any(|(segments, block)| segments.contains(&segment) && block.blocks(dir, step))
+}
+
+/// Steps spent by a train passing through a station without stopping.
+const PASS_THROUGH_TIME: Duration = 0;
+
 struct NextStepInfo {
     kind: SegmentType,
     time: usize,
     loc: car::CarLocation,
+    destination: Option<StationID>,
+    platform: Option<usize>,
+    motion: Option<Motion>,
 }
 
 /// A terminus station can used
@@ -248,7 +1592,9 @@ struct NextStepInfo {
 pub struct Terminus {
     station_id: usize,
     depo_counter: counter::Counter,
-    train_counter: counter::CyclicCounter,
+    dispatch: Dispatch,
+    departures: Vec<Departure>,
+    started: usize,
 }
 
 impl Terminus {
@@ -260,24 +1606,65 @@ impl Terminus {
         Self {
             station_id: id,
             depo_counter: depo_size.into(),
-            train_counter: train_delay.into(),
+            dispatch: Dispatch::Headway(train_delay.into()),
+            departures: Vec::new(),
+            started: 0,
         }
     }
 
-    fn can_start_new_train(&self) -> bool {
+    fn set_dispatch(&mut self, dispatch: Dispatch) {
+        self.dispatch = dispatch;
+    }
+
+    fn can_start_new_train(&self, step: usize) -> bool {
         if self.depo_counter.is_done() {
             false
         } else {
-            self.train_counter.is_done()
+            self.dispatch.is_due(step)
         }
     }
 
     fn step(&mut self) {
-        self.train_counter.count();
+        self.dispatch.step();
+    }
+
+    /// Check if a due train waits in the depot for the platform.
+    fn holds_trains(&self) -> bool {
+        self.dispatch.holds_trains()
+    }
+
+    /// Count a step in which the due train waits for the platform.
+    fn wait(&mut self, step: usize) {
+        self.dispatch.wait(step);
     }
 
-    fn add_new_train(&mut self) {
+    fn add_new_train(&mut self, step: usize) {
         self.depo_counter.step();
+        self.started += 1;
+        if let Some(departure) = self.dispatch.depart(step) {
+            self.departures.push(departure);
+        }
+    }
+
+    fn get_departures(&self) -> &[Departure] {
+        &self.departures
+    }
+
+    /// Number of trains started so far.
+    fn get_started(&self) -> usize {
+        self.started
+    }
+
+    /// Check if a train arriving here at the given step should go
+    /// back into the depot, when the given trains are running on a
+    /// line with the given round trip time. The depot can only
+    /// receive trains when there is room left.
+    fn can_receive_train(&self, step: usize, running: usize, round_trip: usize) -> bool {
+        !self.depo_counter.is_reset() && self.dispatch.withdraws(step, running, round_trip)
+    }
+
+    fn receive_train(&mut self) {
+        self.depo_counter.step_back();
     }
 
     /// Provide global station index of this terminus.
@@ -297,16 +1684,48 @@ pub enum Segment {
 }
 
 impl Segment {
-    fn is_free(&self, dir: LineDirection) -> bool {
-        self.choose_segment_info(dir).is_free()
+    fn can_enter(&self, dir: LineDirection, step: usize, signalling: &Signalling) -> bool {
+        self.choose_segment_info(dir)
+            .can_enter(dir, step, signalling)
     }
 
-    fn set_occupied(&mut self, dir: LineDirection) {
-        self.choose_segment_info_mut(dir).set_occupied();
+    fn enter(&mut self, car: usize, dir: LineDirection, step: usize) -> usize {
+        self.choose_segment_info_mut(dir).enter(car, dir, step)
     }
 
-    fn set_free(&mut self, dir: LineDirection) {
-        self.choose_segment_info_mut(dir).set_free();
+    fn has_room(&self, dir: LineDirection) -> bool {
+        self.choose_segment_info(dir).has_room()
+    }
+
+    fn set_platforms(&mut self, dir: LineDirection, count: usize) {
+        self.choose_segment_info_mut(dir).platforms = count;
+    }
+
+    fn leave(&mut self, car: usize, dir: LineDirection) {
+        self.choose_segment_info_mut(dir).leave(car);
+    }
+
+    fn is_leading(&self, car: usize, dir: LineDirection) -> bool {
+        self.choose_segment_info(dir).is_leading(car)
+    }
+
+    /// Train that entered the track used in the given direction last.
+    fn last_train(&self, dir: LineDirection) -> Option<usize> {
+        self.choose_segment_info(dir).trains.back().map(|o| o.car)
+    }
+
+    /// Trains in the segment, in both directions.
+    fn train_count(&self) -> usize {
+        match self {
+            Self::Single(ti) => ti.trains.len(),
+            Self::Double(d1, d2) => d1.trains.len() + d2.trains.len(),
+        }
+    }
+
+    /// Train on the track used in the given direction
+    /// that entered first.
+    fn first_train(&self, dir: LineDirection) -> Option<usize> {
+        self.choose_segment_info(dir).trains.front().map(|o| o.car)
     }
 
     fn get_duration(&self, dir: LineDirection) -> usize {
@@ -321,6 +1740,10 @@ impl Segment {
         self.choose_segment_info(dir).get_type()
     }
 
+    fn get_motion(&self, dir: LineDirection) -> Option<Motion> {
+        self.choose_segment_info(dir).get_motion()
+    }
+
     fn choose_segment_info_mut(&mut self, dir: LineDirection) -> &'_ mut SegmentInfo {
         match self {
             Self::Single(ti) => ti,
@@ -337,34 +1760,122 @@ impl Segment {
 }
 
 /// Information about a network segment
-/// type of segment, status and duration to
-/// to traverse it
+/// type of segment and duration to
+/// traverse it. The trains in the segment are
+/// kept in the order they entered it, stations
+/// hold a train on each platform. A segment
+/// without trains is free.
 #[derive(PartialEq, Debug)]
 pub struct SegmentInfo {
     kind: SegmentType,
-    stat: SegmentStatus,
     duration: Duration,
+    platforms: usize,
+    trains: VecDeque<Occupant>,
+    passages: usize,
+    motion: Option<Motion>,
+}
+
+/// A train in a segment, with its direction, its
+/// platform and the step it entered the segment.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Occupant {
+    car: usize,
+    dir: LineDirection,
+    platform: usize,
+    since: usize,
 }
 
 impl SegmentInfo {
     pub fn new(kind: SegmentType, duration: Duration) -> Self {
         Self {
             kind,
-            stat: SegmentStatus::Free,
             duration,
+            platforms: 1,
+            trains: VecDeque::new(),
+            passages: 0,
+            motion: None,
         }
     }
 
+    /// Set the length of the segment and the speed trains
+    /// reach on its link, used by the energy model.
+    #[must_use]
+    pub fn set_motion(mut self, motion: Motion) -> Self {
+        self.motion = Some(motion);
+        self
+    }
+
     fn is_free(&self) -> bool {
-        matches! {self.stat, SegmentStatus::Free}
+        self.trains.is_empty()
+    }
+
+    /// Check if a train running in the given direction can enter
+    /// at the given step. Line segments hold trains following each
+    /// other in the same direction as the signalling allows,
+    /// stations a train on each platform.
+    fn can_enter(&self, dir: LineDirection, step: usize, signalling: &Signalling) -> bool {
+        let Some(last) = self.trains.back() else {
+            return true;
+        };
+        match self.kind {
+            _ if last.dir != dir => false,
+            SegmentType::Line => {
+                let (gap, capacity) = signalling.admission(self.duration);
+                self.trains.len() < capacity && step >= last.since + gap
+            }
+            SegmentType::Station(_) | SegmentType::Terminus(_) => {
+                self.trains.len() < self.platforms
+            }
+        }
+    }
+
+    /// Check if a station has a free platform,
+    /// or if any other segment is free.
+    fn has_room(&self) -> bool {
+        match self.kind {
+            SegmentType::Line => self.is_free(),
+            _ => self.is_free() || self.trains.len() < self.platforms,
+        }
     }
 
-    fn set_occupied(&mut self) {
-        self.stat = SegmentStatus::Occupied;
+    /// Trains that can wait in the segment: one on
+    /// each platform of a station, one on a line.
+    fn slots(&self) -> usize {
+        match self.kind {
+            SegmentType::Line => 1,
+            _ => self.platforms,
+        }
+    }
+
+    /// Add the train to the segment and return
+    /// the lowest numbered free platform it takes.
+    fn enter(&mut self, car: usize, dir: LineDirection, step: usize) -> usize {
+        let platform = (0..)
+            .find(|p| self.trains.iter().all(|o| o.platform != *p))
+            .unwrap_or_default();
+        self.trains.push_back(Occupant {
+            car,
+            dir,
+            platform,
+            since: step,
+        });
+        self.passages += 1;
+        platform
+    }
+
+    fn leave(&mut self, car: usize) {
+        self.trains.retain(|o| o.car != car);
+    }
+
+    /// Trains leave a line segment in the order they
+    /// entered it, and a station in any order.
+    fn is_leading(&self, car: usize) -> bool {
+        self.kind != SegmentType::Line || self.trains.front().is_none_or(|o| o.car == car)
     }
 
-    fn set_free(&mut self) {
-        self.stat = SegmentStatus::Free;
+    /// Number of trains that entered the segment so far.
+    fn get_passages(&self) -> usize {
+        self.passages
     }
 
     fn get_duration(&self) -> usize {
@@ -375,6 +1886,10 @@ impl SegmentInfo {
         self.kind
     }
 
+    fn get_motion(&self) -> Option<Motion> {
+        self.motion
+    }
+
     fn make_location(&self, index: usize) -> car::CarLocation {
         match self.kind {
             SegmentType::Station(i) | SegmentType::Terminus(i) => {
@@ -395,23 +1910,15 @@ pub enum SegmentType {
     Line,
 }
 
-/// A segment can be free or occupied
-#[derive(PartialEq, Debug)]
-pub enum SegmentStatus {
-    Free,
-    Occupied,
-}
-
-impl Default for SegmentStatus {
-    fn default() -> Self {
-        Self::Free
-    }
-}
-
 #[cfg(test)]
 mod test {
 
+    use super::super::dispatch::{HeadwayProfile, ServiceSpan};
     use super::super::fast_line_factory;
+    use super::super::line_factory::{
+        self, LineChunkConfig, LineChunkKind, LineFactoryConfig, LineInfoConfig, LineOptions,
+        StationInfoConfig,
+    };
     use super::*;
     use std::collections::HashSet;
 
@@ -440,16 +1947,16 @@ mod test {
             for (id, seg) in $segments.iter().enumerate() {
                 if id_set.contains(&id) {
                     assert!(
-                        !seg.is_free(LineDirection::DirectionA)
-                            || !seg.is_free(LineDirection::DirectionB),
+                        seg.last_train(LineDirection::DirectionA).is_some()
+                            || seg.last_train(LineDirection::DirectionB).is_some(),
                         "{:?} {}",
                         $segments,
                         id
                     );
                 } else {
                     assert!(
-                        seg.is_free(LineDirection::DirectionA)
-                            && seg.is_free(LineDirection::DirectionB),
+                        seg.last_train(LineDirection::DirectionA).is_none()
+                            && seg.last_train(LineDirection::DirectionB).is_none(),
                         "{:?} {}",
                         $segments,
                         id
@@ -462,7 +1969,7 @@ mod test {
     macro_rules! test_is_free {
         ($railway: expr, $index: expr, $dir: expr, $free: expr) => {
             let car = fast_make_car($index, $dir);
-            assert_eq!($railway.is_free(&car), $free);
+            assert_eq!($railway.can_move(&car, 0), $free);
         };
     }
 
@@ -650,37 +2157,701 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_timetable_departures() {
+        let cfg = fast_line_factory::FastLineFactoryConfig::new(0..=3, 6, [4, 4, 4], 6, 4, 0)
+            .set_options(
+                LineOptions::default()
+                    .set_timetable(LineDirection::DirectionA, Timetable::new([2, 4]))
+                    .set_timetable(LineDirection::DirectionB, Timetable::new([])),
+            );
+        let mut line: Line<()> = fast_line_factory::fast_line_factory(cfg, 4);
+        for _ in 0..3 {
+            line.step();
+        }
+        assert_eq!(line.fleet.len(), 1);
+        assert_eq!(
+            line.get_departures(LineDirection::DirectionA),
+            [Departure::new(2, 2)]
+        );
+        // the second train waits for the first one
+        // to leave the terminus platform
+        for _ in 3..30 {
+            line.step();
+        }
+        assert_eq!(line.fleet.len(), 2);
+        let departures = line.get_departures(LineDirection::DirectionA);
+        assert_eq!(departures[1], Departure::new(4, 9));
+        assert_eq!(departures[1].lateness(), 5);
+        assert!(line.get_departures(LineDirection::DirectionB).is_empty());
+    }
+
+    #[test]
+    fn test_timetable_empty_depot() {
+        // a single train in each depot: the second departure waits
+        // for the train coming from the other terminus
+        let cfg = fast_line_factory::FastLineFactoryConfig::new(0..=3, 6, [4, 4, 4], 6, 1, 0)
+            .set_options(
+                LineOptions::default()
+                    .set_timetable(LineDirection::DirectionA, Timetable::new([2, 4]))
+                    .set_timetable(LineDirection::DirectionB, Timetable::new([0])),
+            );
+        let mut line: Line<()> = fast_line_factory::fast_line_factory(cfg, 4);
+        for _ in 0..100 {
+            line.step();
+        }
+        let departures = line.get_departures(LineDirection::DirectionA);
+        assert_eq!(departures.len(), 2);
+        assert_eq!(departures[1].get_actual(), 43);
+        assert_eq!(departures[1].lateness(), 0);
+    }
+
+    #[test]
+    fn test_timetable_held_departure() {
+        // the train due at step 26 is held while the train from the
+        // other terminus turns around, then waits for the platform
+        // the latter takes: only the second wait makes it late
+        let cfg = fast_line_factory::FastLineFactoryConfig::new(0..3, 5, [6, 6], 7, 2, 0)
+            .set_options(
+                LineOptions::default()
+                    .set_deadlock_policy(DeadlockPolicy::PassingLoopPriority)
+                    .set_timetable(LineDirection::DirectionA, Timetable::new([26]))
+                    .set_timetable(LineDirection::DirectionB, Timetable::new([0])),
+            );
+        let mut line: Line<()> = fast_line_factory::fast_line_factory(cfg, 3);
+        for _ in 0..50 {
+            line.step();
+        }
+        let departures = line.get_departures(LineDirection::DirectionA);
+        assert_eq!(departures, [Departure::new(26, 38).set_lateness(6)]);
+    }
+
+    #[test]
+    fn test_service_span() {
+        let service = Service::new(
+            HeadwayProfile::new(20).add_period(20, 10),
+            ServiceSpan::new(5, 45),
+        );
+        let cfg = fast_line_factory::FastLineFactoryConfig::new(0..=3, 2, [2, 2, 2], 6, 8, 0)
+            .set_options(
+                LineOptions::default()
+                    .set_service(LineDirection::DirectionA, service)
+                    .set_timetable(LineDirection::DirectionB, Timetable::new([])),
+            );
+        let mut line: Line<()> = fast_line_factory::fast_line_factory(cfg, 4);
+        let mut starts = vec![];
+        for step in 0..100 {
+            let before = line.fleet.len();
+            line.step();
+            if line.fleet.len() > before {
+                starts.push(step);
+            }
+        }
+        assert_eq!(starts, [5, 25, 35, 45]);
+    }
+
+    #[test]
+    fn test_withdraw_trains() {
+        let service = || Service::new(HeadwayProfile::new(10), ServiceSpan::new(0, 30));
+        let cfg = fast_line_factory::FastLineFactoryConfig::new(0..=3, 2, [2, 2, 2], 6, 2, 0)
+            .set_options(
+                LineOptions::default()
+                    .set_service(LineDirection::DirectionA, service())
+                    .set_service(LineDirection::DirectionB, service()),
+            );
+        let mut line: Line<()> = fast_line_factory::fast_line_factory(cfg, 4);
+        let mut peak = 0;
+        for _ in 0..200 {
+            line.step();
+            peak = peak.max(line.running_trains());
+        }
+        assert_eq!(peak, 4);
+        assert_eq!(line.running_trains(), 0);
+        assert!(line.terminus_a.depo_counter.is_reset());
+        assert!(line.terminus_b.depo_counter.is_reset());
+        assert!(line.railway.line.iter().all(|s| {
+            s.last_train(LineDirection::DirectionA).is_none()
+                && s.last_train(LineDirection::DirectionB).is_none()
+        }));
+
+        // more departures than trains in the depots:
+        // withdrawn trains are started again
+        let log = line.get_service_log();
+        assert!(log.len() > 4);
+        let mut ids: Vec<usize> = log.iter().map(fleet::CarService::get_car).collect();
+        ids.sort_unstable();
+        assert_eq!(ids, (0..line.next_car_id).collect::<Vec<_>>());
+        for service in log {
+            assert!(service.in_service_time() > 0);
+            assert!(service.get_end() > service.get_start());
+        }
+    }
+
+    #[test]
+    fn test_withdraw_on_headway_drop() {
+        // a round trip of 28 steps needs 6 trains every 5 steps,
+        // then a single one every 40 steps
+        let profile = || HeadwayProfile::new(5).add_period(80, 40);
+        let service = || Service::new(profile(), ServiceSpan::new(0, 200));
+        let cfg = fast_line_factory::FastLineFactoryConfig::new(0..=3, 2, [2, 2, 2], 6, 2, 0)
+            .set_options(
+                LineOptions::default()
+                    .set_service(LineDirection::DirectionA, service())
+                    .set_service(LineDirection::DirectionB, service()),
+            );
+        let mut line: Line<()> = fast_line_factory::fast_line_factory(cfg, 4);
+        assert_eq!(line.railway.round_trip(), 28);
+        for _ in 0..80 {
+            line.step();
+        }
+        // the depots are empty, still no train is withdrawn
+        assert_eq!(line.running_trains(), 4);
+        assert!(line.get_service_log().is_empty());
+        for _ in 80..110 {
+            line.step();
+        }
+        // the trains exceeding the new headway go back to the depots
+        assert_eq!(line.running_trains(), 1);
+        let log = line.get_service_log();
+        assert_eq!(log.len(), 5);
+        assert!(log.iter().all(|s| s.get_end() > 80));
+    }
+
+    #[test]
+    fn test_headway_keeps_trains() {
+        let cfg = fast_line_factory::FastLineFactoryConfig::new(0..=3, 2, [2, 2, 2], 6, 2, 3);
+        let mut line: Line<()> = fast_line_factory::fast_line_factory(cfg, 4);
+        for _ in 0..200 {
+            line.step();
+        }
+        assert_eq!(line.running_trains(), 4);
+        assert!(line.get_service_log().is_empty());
+    }
+
+    #[test]
+    fn test_short_turn_pattern() {
+        let cfg = fast_line_factory::FastLineFactoryConfig::new(0..=4, 2, [2, 2, 2, 2], 6, 2, 0)
+            .set_options(
+                LineOptions::default()
+                    .set_service_patterns(vec![
+                        ServicePattern::short_turn(0, 2),
+                        ServicePattern::full(),
+                    ])
+                    .set_timetable(LineDirection::DirectionB, Timetable::new([0, 1]))
+                    .set_timetable(LineDirection::DirectionA, Timetable::new([])),
+            );
+        let mut line: Line<()> = fast_line_factory::fast_line_factory(cfg, 5);
+        let mut farthest = [0; 2];
+        for _ in 0..100 {
+            line.step();
+            for car in line.fleet.running_cars_iter() {
+                let id = car.get_id();
+                farthest[id] = farthest[id].max(car.get_current_segment());
+            }
+        }
+        // the first train turns back at station 2, the second one
+        // runs to the terminus
+        assert_eq!(farthest, [4, 8]);
+        let mut cars: Vec<usize> = line
+            .get_service_log()
+            .iter()
+            .map(fleet::CarService::get_car)
+            .collect();
+        cars.sort_unstable();
+        assert_eq!(cars, [0, 1]);
+    }
+
+    #[test]
+    fn test_skip_stop_pattern() {
+        let express = ServicePattern::full().set_skipped(vec![1, 3]);
+        let cfg = fast_line_factory::FastLineFactoryConfig::new(0..=4, 2, [2, 2, 2, 2], 6, 2, 0)
+            .set_options(
+                LineOptions::default()
+                    .set_service_patterns(vec![express, ServicePattern::full()])
+                    .set_timetable(LineDirection::DirectionB, Timetable::new([0, 1]))
+                    .set_timetable(LineDirection::DirectionA, Timetable::new([])),
+            );
+        let mut line: Line<()> = fast_line_factory::fast_line_factory(cfg, 5);
+        let mut stops = [HashSet::new(), HashSet::new()];
+        let mut steps = [0; 2];
+        for _ in 0..100 {
+            line.step();
+            for car in line.fleet.running_cars_iter() {
+                steps[car.get_id()] += 1;
+                if car.in_station() {
+                    stops[car.get_id()].insert(car.get_current_station());
+                }
+            }
+        }
+        assert_eq!(stops[0], HashSet::from([0, 2, 4]));
+        assert_eq!(stops[1], HashSet::from([0, 1, 2, 3, 4]));
+        // the express train spends less time on the line
+        assert!(steps[0] < steps[1]);
+        assert_eq!(line.get_service_log().len(), 2);
+    }
+
+    #[test]
+    fn test_branch_pattern() {
+        let cfg = fast_line_factory::FastLineFactoryConfig::new(0..=4, 2, [2, 2, 2, 2], 6, 2, 0)
+            .add_branch(2, vec![5, 6], vec![2, 2])
+            .set_options(
+                LineOptions::default()
+                    .set_service_patterns(vec![
+                        ServicePattern::full(),
+                        ServicePattern::full().set_branch(1),
+                    ])
+                    .set_timetable(LineDirection::DirectionB, Timetable::new([0, 1]))
+                    .set_timetable(LineDirection::DirectionA, Timetable::new([])),
+            );
+        let mut line: Line<()> = fast_line_factory::fast_line_factory(cfg, 7);
+        let mut stops = [HashSet::new(), HashSet::new()];
+        for _ in 0..100 {
+            line.step();
+            for car in line.fleet.running_cars_iter() {
+                if car.in_station() {
+                    stops[car.get_id()].insert(car.get_current_station());
+                }
+            }
+        }
+        assert_eq!(stops[0], HashSet::from([0, 1, 2, 3, 4]));
+        assert_eq!(stops[1], HashSet::from([0, 1, 2, 5, 6]));
+        assert_eq!(line.get_service_log().len(), 2);
+        assert!(line.railway.line.iter().all(|s| {
+            s.last_train(LineDirection::DirectionA).is_none()
+                && s.last_train(LineDirection::DirectionB).is_none()
+        }));
+    }
+
+    #[test]
+    fn test_branch_speed_limits() {
+        use super::super::kinematics::Kinematics;
+        let branch_time = |limits: Vec<Option<f64>>| {
+            let kinematics = Kinematics::new(1., 1., 20.).set_step_time(10.);
+            let cfg =
+                fast_line_factory::FastLineFactoryConfig::new(0..=2, 2, [1600, 1600], 1000, 1, 0)
+                    .set_kinematics(kinematics)
+                    .add_branch(1, vec![3], vec![1600])
+                    .set_branch_speed_limits(1, limits);
+            let line: Line<()> = fast_line_factory::fast_line_factory(cfg, 4);
+            let main = line.railway.branches[0].len();
+            line.railway.line[main..]
+                .iter()
+                .filter(|s| s.get_type(LineDirection::DirectionA) == SegmentType::Line)
+                .map(|s| s.get_duration(LineDirection::DirectionA))
+                .sum::<usize>()
+        };
+        // the limited branch link cruises at 10 m/s
+        assert_eq!(branch_time(vec![]), 10);
+        assert_eq!(branch_time(vec![Some(10.)]), 17);
+    }
+
+    #[test]
+    fn test_loop_line() {
+        let cfg = fast_line_factory::FastLineFactoryConfig::new(0..4, 2, [2, 2, 2, 2], 6, 1, 0)
+            .set_circular(true);
+        let mut line: Line<()> = fast_line_factory::fast_line_factory(cfg, 4);
+        let mut visits = [vec![], vec![]];
+        for _ in 0..100 {
+            line.step();
+            for car in line.fleet.running_cars_iter() {
+                assert!(!car.is_swapping());
+                if car.in_station() {
+                    let visited = &mut visits[car.get_id()];
+                    let station = car.get_current_station();
+                    if visited.last() != Some(&station) {
+                        visited.push(station);
+                    }
+                    // trains take the passengers for the next stop
+                    let dir = car.get_current_direction();
+                    let next = dir.choose_direction((station + 3) % 4, (station + 1) % 4);
+                    assert_eq!(car.get_destination(), next);
+                }
+            }
+        }
+        // one train runs anticlockwise, the other clockwise
+        let anticlockwise: Vec<usize> = [0, 3, 2, 1].into_iter().cycle().take(6).collect();
+        let clockwise: Vec<usize> = (0..4).cycle().take(6).collect();
+        assert_eq!(visits[0][..6], anticlockwise);
+        assert_eq!(visits[1][..6], clockwise);
+    }
+
+    #[test]
+    fn test_loop_line_closure() {
+        let cfg = fast_line_factory::FastLineFactoryConfig::new(0..4, 2, [2, 2, 2, 2], 6, 1, 0)
+            .set_circular(true)
+            .set_options(LineOptions::default().add_closure(BlockTarget::Station(0), 0, 50));
+        let mut line: Line<()> = fast_line_factory::fast_line_factory(cfg, 4);
+        let mut stops = vec![];
+        for step in 0..100 {
+            line.step();
+            for car in line.fleet.running_cars_iter().filter(|c| c.in_station()) {
+                stops.push((step, car.get_current_station()));
+            }
+        }
+        // the trains start from the closed station without stopping
+        // there, and stop once it opens
+        assert!(stops.iter().all(|(step, s)| *s != 0 || *step >= 50));
+        assert!(stops.iter().any(|(_, s)| *s == 0));
+        assert!(line
+            .get_platform_log()
+            .iter()
+            .all(|p| p.get_station() != 0 || p.get_step() >= 50));
+    }
+
+    #[test]
+    fn test_mixed_fleet() {
+        let slow = RollingStock::new("slow", 10, 20).set_max_speed(10.);
+        let fast = RollingStock::new("fast", 20, 40);
+        let cfg = fast_line_factory::FastLineFactoryConfig::new(0..4, 2, [2, 2, 2, 2], 6, 1, 0)
+            .set_circular(true)
+            .set_options(
+                LineOptions::default()
+                    .set_reference_speed(20.)
+                    .set_rolling_stock(vec![slow, fast]),
+            );
+        let mut line: Line<()> = fast_line_factory::fast_line_factory(cfg, 4);
+        let mut visits = [vec![], vec![]];
+        for _ in 0..60 {
+            line.step();
+            for car in line.fleet.running_cars_iter() {
+                if car.in_station() {
+                    let visited = &mut visits[car.get_id()];
+                    let station = car.get_current_station();
+                    if visited.last() != Some(&station) {
+                        visited.push(station);
+                    }
+                }
+            }
+        }
+        let classes: Vec<(usize, usize)> = line
+            .fleet
+            .running_cars_iter()
+            .map(|c| (c.get_id(), c.get_capacity()))
+            .collect();
+        assert_eq!(classes, [(0, 30), (1, 60)]);
+        // the slow train takes twice as long between stations
+        assert!(visits[0].len() < visits[1].len(), "{visits:?}");
+    }
+
+    #[test]
+    fn test_line_block() {
+        let block = SegmentBlock::new(BlockTarget::Link(1, 2), 4, 30)
+            .set_direction(LineDirection::DirectionB);
+        let cfg = fast_line_factory::FastLineFactoryConfig::new(0..4, 1, [2, 2, 2], 2, 1, 3)
+            .set_options(LineOptions::default().add_segment_block(block));
+        let mut line: Line<()> = fast_line_factory::fast_line_factory(cfg, 4);
+        for _ in 0..40 {
+            line.step();
+        }
+        // the train leaving the first station waits in front of
+        // the link until the block ends
+        let delays = line.get_delays();
+        assert_eq!(delays.len(), 1);
+        assert_eq!(delays[0].get_car(), 1);
+        assert_eq!(delays[0].get_cause(), DelayCause::Blocked);
+        assert_eq!(delays[0].get_start() + delays[0].get_steps(), 30);
+    }
+
+    #[test]
+    fn test_line_closure() {
+        let cfg = fast_line_factory::FastLineFactoryConfig::new(0..=4, 2, [2, 2, 2, 2], 6, 2, 0)
+            .set_options(
+                LineOptions::default()
+                    .add_closure(BlockTarget::Station(1), 0, 100)
+                    .add_closure(BlockTarget::Link(3, 4), 0, 50)
+                    .set_timetable(LineDirection::DirectionB, Timetable::new([0, 60]))
+                    .set_timetable(LineDirection::DirectionA, Timetable::new([])),
+            );
+        let mut line: Line<()> = fast_line_factory::fast_line_factory(cfg, 5);
+        let mut stops = [vec![], vec![]];
+        for _ in 0..150 {
+            line.step();
+            for car in line.fleet.running_cars_iter().filter(|c| c.in_station()) {
+                let station = car.get_current_station();
+                let stops = &mut stops[car.get_id()];
+                if stops.last() != Some(&station) {
+                    stops.push(station);
+                }
+            }
+        }
+        // the first train runs through station 1 and turns back in front
+        // of the closed link, the second one runs after the link opens
+        // and stops at station 1 on its way back, once it opens too
+        assert_eq!(stops[0], [0, 2, 3, 2, 0]);
+        assert_eq!(stops[1], [0, 2, 3, 4, 3, 2, 1, 0]);
+        assert_eq!(line.get_service_log().len(), 2);
+    }
+
+    #[test]
+    fn test_line_deadlock() {
+        let make_line = |policy| {
+            let cfg = fast_line_factory::FastLineFactoryConfig::new(0..4, 1, [2, 2, 2], 2, 3, 2)
+                .set_options(LineOptions::default().set_deadlock_policy(policy));
+            fast_line_factory::fast_line_factory::<_, _, ()>(cfg, 4)
+        };
+        let mut line = make_line(DeadlockPolicy::Fail);
+        for _ in 0..100 {
+            line.step();
+        }
+        // trains of both directions meet on single track
+        assert_eq!(line.get_deadlocks(), [Deadlock::new(15, vec![0, 1, 2, 3])]);
+        assert!(line
+            .get_delays()
+            .iter()
+            .filter(|d| d.get_cause() == DelayCause::Deadlock)
+            .all(|d| d.get_start() == 15 && d.get_steps() == 85));
+
+        let mut line = make_line(DeadlockPolicy::PassingLoopPriority);
+        let mut moves = 0;
+        for _ in 0..100 {
+            let before: Vec<usize> = line
+                .fleet
+                .cars_iter()
+                .map(car::Car::get_current_segment)
+                .collect();
+            line.step();
+            let after = line.fleet.cars_iter().map(car::Car::get_current_segment);
+            moves += before.iter().zip(after).filter(|(b, a)| *b != a).count();
+        }
+        assert!(line.get_deadlocks().is_empty());
+        assert!(line
+            .get_delays()
+            .iter()
+            .any(|d| d.get_cause() == DelayCause::PassingLoop));
+        assert!(moves > 100);
+    }
+
+    #[test]
+    fn test_line_deadlock_full_depots() {
+        // three stations on single track, with more trains
+        // in the depots than platforms on the line
+        let make_line = |policy| {
+            let cfg = fast_line_factory::FastLineFactoryConfig::new(0..3, 5, [6, 6], 7, 4, 11)
+                .set_options(LineOptions::default().set_deadlock_policy(policy));
+            fast_line_factory::fast_line_factory::<_, _, ()>(cfg, 3)
+        };
+        let mut line = make_line(DeadlockPolicy::Fail);
+        for _ in 0..500 {
+            line.step();
+        }
+        assert!(!line.get_deadlocks().is_empty());
+
+        let mut line = make_line(DeadlockPolicy::PassingLoopPriority);
+        for _ in 0..500 {
+            line.step();
+            // one of the six platforms is always left free
+            assert!(line.running_trains() < 6);
+        }
+        assert!(line.get_deadlocks().is_empty());
+        assert!(line.get_platform_log().len() > 100);
+    }
+
+    #[test]
+    fn test_line_deadlock_short_turn() {
+        // every other train turns back at the middle station,
+        // closing the single track between it and the terminus
+        for depot in [4, 6] {
+            let options = LineOptions::default()
+                .set_deadlock_policy(DeadlockPolicy::PassingLoopPriority)
+                .set_service_patterns(vec![
+                    ServicePattern::full(),
+                    ServicePattern::short_turn(0, 1),
+                ]);
+            let cfg = fast_line_factory::FastLineFactoryConfig::new(0..3, 5, [6, 6], 7, depot, 11)
+                .set_options(options);
+            let mut line = fast_line_factory::fast_line_factory::<_, _, ()>(cfg, 3);
+            for _ in 0..1000 {
+                line.step();
+            }
+            assert!(line.get_deadlocks().is_empty(), "depot of {depot}");
+            // trains still stop at the turnback station
+            assert!(line
+                .get_platform_log()
+                .iter()
+                .any(|s| s.get_station() == 1 && s.get_step() > 900));
+        }
+    }
+
+    #[test]
+    fn test_line_signalling() {
+        let make_line = |signalling| {
+            let stations = (0..4).map(|i| StationInfoConfig::new(i, 1)).collect();
+            let links = (0..3)
+                .map(|_| LineInfoConfig::new(vec![LineChunkConfig::new(6, LineChunkKind::Double)]))
+                .collect();
+            let cfg = LineFactoryConfig::new(stations, links, 4, 6, 1)
+                .set_options(LineOptions::default().set_signalling(signalling));
+            line_factory::line_factory::<()>(cfg)
+        };
+        let mut measures = vec![];
+        for signalling in [
+            Signalling::default(),
+            Signalling::fixed_block(3),
+            Signalling::moving_block(1),
+        ] {
+            let mut line = make_line(signalling);
+            for _ in 0..120 {
+                line.step();
+            }
+            assert!(line.get_deadlocks().is_empty());
+            measures.push((line.min_headway(), line.capacity(60), line.throughput(60)));
+        }
+        // shorter blocks, and even more a moving block, let
+        // trains follow each other closer on double track
+        let headways: Vec<Duration> = measures.iter().map(|m| m.0).collect();
+        assert_eq!(headways, [6, 3, 1]);
+        assert_eq!(measures[0].1, 10.);
+        assert!(measures
+            .iter()
+            .all(|(_, capacity, throughput)| throughput <= capacity));
+        assert!(measures.windows(2).all(|m| m[0].2 < m[1].2));
+    }
+
+    #[test]
+    fn test_line_platforms() {
+        let make_line = |platforms| {
+            let stations = [1, 6, 1, 1]
+                .into_iter()
+                .enumerate()
+                .map(|(i, dwell)| StationInfoConfig::new(i, dwell))
+                .collect();
+            let links = (0..3)
+                .map(|_| LineInfoConfig::new(vec![LineChunkConfig::new(2, LineChunkKind::Double)]))
+                .collect();
+            let cfg = LineFactoryConfig::new(stations, links, 4, 6, 1).set_options(
+                LineOptions::default()
+                    .set_platforms(1, LineDirection::DirectionA, platforms)
+                    .set_platforms(1, LineDirection::DirectionB, platforms),
+            );
+            line_factory::line_factory::<()>(cfg)
+        };
+        let mut single = make_line(1);
+        let mut triple = make_line(3);
+        for _ in 0..120 {
+            single.step();
+            triple.step();
+        }
+        // the long dwell at station 1 sets the headway
+        assert_eq!(single.min_headway(), 6);
+        assert_eq!(triple.min_headway(), 2);
+        assert!(single.throughput(60) < triple.throughput(60));
+        let platforms = |line: &Line<()>| {
+            let mut used: Vec<usize> = line
+                .get_platform_log()
+                .iter()
+                .filter(|s| s.get_station() == 1)
+                .map(PlatformStop::get_platform)
+                .collect();
+            used.sort_unstable();
+            used.dedup();
+            used
+        };
+        assert_eq!(platforms(&single), [0]);
+        assert_eq!(platforms(&triple), [0, 1, 2]);
+        // trains leave the terminus from its platform
+        let first = triple.get_platform_log()[0];
+        assert!([0, 3].contains(&first.get_station()));
+        assert_eq!(first.get_platform(), 0);
+    }
+
+    #[test]
+    fn test_line_failures() {
+        let cfg = fast_line_factory::FastLineFactoryConfig::new(0..5, 1, [2, 2, 2, 2], 2, 2, 2)
+            .set_options(LineOptions::default().set_failure_model(FailureModel::new(0.05, 4, 8)));
+        let mut line: Line<()> = fast_line_factory::fast_line_factory(cfg, 5);
+        let mut rng = crate::utils::random::make_rng(Some(3));
+        for _ in 0..200 {
+            line.inject_failures(&mut rng);
+            line.step();
+        }
+        let delays = line.get_delays();
+        assert!(delays
+            .iter()
+            .filter(|d| d.get_cause() == DelayCause::Failure)
+            .all(|d| d.get_steps() >= 4));
+        // the trains behind a failed one are delayed too
+        assert!(delays.iter().any(|d| d.get_cause() == DelayCause::KnockOn));
+        assert!(delays.iter().all(|d| d.get_cause() != DelayCause::Blocked));
+    }
+
+    #[test]
+    fn test_unfit_patterns() {
+        let make_line = |pattern: ServicePattern, circular| {
+            let stations = if circular { 0..=3 } else { 0..=4 };
+            let cfg = fast_line_factory::FastLineFactoryConfig::new(stations, 2, [2; 4], 6, 2, 0)
+                .set_circular(circular)
+                .set_options(LineOptions::default().set_service_patterns(vec![pattern]));
+            let line: Line<()> = fast_line_factory::fast_line_factory(cfg, 8);
+            line
+        };
+        let unfit = [
+            (ServicePattern::short_turn(0, 2), true),
+            (ServicePattern::full().set_skipped(vec![1]), true),
+            (ServicePattern::full().set_branch(1), false),
+            (ServicePattern::full().set_skipped(vec![4]), false),
+            (ServicePattern::short_turn(0, 7), false),
+        ];
+        for (pattern, circular) in unfit {
+            let mut line = make_line(pattern.clone(), circular);
+            assert!(!line.railway.fits(&pattern), "{pattern:?}");
+            // the trains serve the whole line
+            line.step();
+            let train = line.fleet.cars_iter().next().unwrap();
+            assert!((0..=3).all(|s| train.serves(s)), "{pattern:?}");
+        }
+        let line = make_line(ServicePattern::short_turn(1, 3), false);
+        assert!(line.railway.fits(&ServicePattern::short_turn(1, 3)));
+    }
+
+    #[test]
+    fn test_branch_ends() {
+        let station = |kind| Segment::Double(SegmentInfo::new(kind, 2), SegmentInfo::new(kind, 2));
+        let line = || Segment::Single(SegmentInfo::new(SegmentType::Line, 2));
+        let railway = Railway::new(vec![
+            station(SegmentType::Terminus(0)),
+            line(),
+            station(SegmentType::Station(1)),
+            line(),
+            station(SegmentType::Terminus(2)),
+        ])
+        .add_branch(1, vec![line()])
+        .add_branch(1, vec![line(), station(SegmentType::Terminus(3))]);
+        // the first branch ends with a link: its last station is the junction
+        assert_eq!(railway.branch_ends(), [2, 1, 3]);
+    }
+
     #[test]
     fn test_terminus_can_start() {
         // 4 train in depo, 3 steps between
         // each new train
         let mut term = Terminus::new(0, 4, 3);
-        for _ in 0..4 {
-            assert!(!term.can_start_new_train());
+        for i in 0..4 {
+            assert!(!term.can_start_new_train(i));
             term.step();
 
-            assert!(!term.can_start_new_train());
+            assert!(!term.can_start_new_train(i));
             term.step();
 
-            assert!(!term.can_start_new_train());
+            assert!(!term.can_start_new_train(i));
             term.step();
 
-            assert!(term.can_start_new_train());
+            assert!(term.can_start_new_train(i));
             term.step();
-            term.add_new_train();
+            term.add_new_train(i);
         }
+        assert!(term.get_departures().is_empty());
 
-        assert!(!term.can_start_new_train());
+        assert!(!term.can_start_new_train(0));
         term.step();
-        assert!(!term.can_start_new_train());
+        assert!(!term.can_start_new_train(0));
         term.step();
-        assert!(!term.can_start_new_train());
+        assert!(!term.can_start_new_train(0));
         term.step();
-        assert!(!term.can_start_new_train());
+        assert!(!term.can_start_new_train(0));
         term.step();
-        assert!(!term.can_start_new_train());
+        assert!(!term.can_start_new_train(0));
         term.step();
-        assert!(!term.can_start_new_train());
+        assert!(!term.can_start_new_train(0));
         term.step();
     }
 
@@ -688,13 +2859,13 @@ mod test {
     fn text_next_step_railway() {
         let mut railway = init_railway();
         let car = fast_make_car(0, LineDirection::DirectionB);
-        assert!(railway.next_step(&car).is_none());
+        assert!(railway.next_step(&car, &[], 0).is_none());
         let car = fast_make_car(1, LineDirection::DirectionB);
-        assert!(railway.next_step(&car).is_none());
+        assert!(railway.next_step(&car, &[], 0).is_none());
         let car = fast_make_car(1, LineDirection::DirectionA);
-        let res = railway.next_step(&car);
+        let res = railway.next_step(&car, &[], 0);
         assert! {
-            matches!{res, Some(NextStepInfo{kind, time, loc})
+            matches!{res, Some(NextStepInfo{kind, time, loc, ..})
                 if kind == SegmentType::Line &&
                     time == 0 &&
                     matches!{loc, car::CarLocation::Segment{index}
@@ -709,19 +2880,11 @@ mod test {
         let railway = init_railway();
         assert_eq!(
             *railway.get_terminus(LineDirection::DirectionA),
-            Segment::Single(SegmentInfo {
-                stat: SegmentStatus::Free,
-                kind: SegmentType::Line,
-                duration: 0
-            })
+            Segment::Single(init_segment_info())
         );
         assert_eq!(
             *railway.get_terminus(LineDirection::DirectionB),
-            Segment::Single(SegmentInfo {
-                stat: SegmentStatus::Occupied,
-                kind: SegmentType::Line,
-                duration: 0
-            })
+            Segment::Single(occupied_segment_info())
         );
     }
 
@@ -730,13 +2893,19 @@ mod test {
         let railway = init_railway();
         test_is_free!(railway, 1, LineDirection::DirectionA, true);
         test_is_free!(railway, 1, LineDirection::DirectionB, false);
+        // nothing after the end of the line
+        let car = fast_make_car(2, LineDirection::DirectionB);
+        assert!(railway.get_next_trunk(&car).is_none());
+        test_is_free!(railway, 2, LineDirection::DirectionB, false);
     }
 
     #[test]
     fn test_update_railway_position() {
         let mut railway = init_railway();
         let car = fast_make_car(1, LineDirection::DirectionA);
-        let NextStepInfo { kind, time, loc } = railway.update_car_location(&car);
+        let NextStepInfo {
+            kind, time, loc, ..
+        } = railway.update_car_location(&car, 0, &[], 0);
         assert_eq!(time, 0);
         assert!(matches! {kind, SegmentType::Line});
 
@@ -749,78 +2918,70 @@ mod test {
 
     #[test]
     fn test_check_free_segment() {
-        let single_segment = Segment::Single(init_segment_info(SegmentStatus::Occupied));
-        assert!(!single_segment.is_free(LineDirection::DirectionA));
-        assert!(!single_segment.is_free(LineDirection::DirectionB));
+        let single_segment = Segment::Single(occupied_segment_info());
+        assert!(!single_segment.has_room(LineDirection::DirectionA));
+        assert!(!single_segment.has_room(LineDirection::DirectionB));
 
-        let single_segment = Segment::Single(init_segment_info(SegmentStatus::Free));
-        assert!(single_segment.is_free(LineDirection::DirectionA));
-        assert!(single_segment.is_free(LineDirection::DirectionB));
+        let single_segment = Segment::Single(init_segment_info());
+        assert!(single_segment.has_room(LineDirection::DirectionA));
+        assert!(single_segment.has_room(LineDirection::DirectionB));
 
-        let double_segment = Segment::Double(
-            init_segment_info(SegmentStatus::Free),
-            init_segment_info(SegmentStatus::Occupied),
-        );
-        assert!(double_segment.is_free(LineDirection::DirectionA));
-        assert!(!double_segment.is_free(LineDirection::DirectionB));
+        let double_segment = Segment::Double(init_segment_info(), occupied_segment_info());
+        assert!(double_segment.has_room(LineDirection::DirectionA));
+        assert!(!double_segment.has_room(LineDirection::DirectionB));
 
-        let double_segment = Segment::Double(
-            init_segment_info(SegmentStatus::Occupied),
-            init_segment_info(SegmentStatus::Free),
-        );
-        assert!(!double_segment.is_free(LineDirection::DirectionA));
-        assert!(double_segment.is_free(LineDirection::DirectionB));
+        let double_segment = Segment::Double(occupied_segment_info(), init_segment_info());
+        assert!(!double_segment.has_room(LineDirection::DirectionA));
+        assert!(double_segment.has_room(LineDirection::DirectionB));
     }
 
     #[test]
-    fn test_set_free_segment() {
-        let mut single_segment = Segment::Single(init_segment_info(SegmentStatus::Occupied));
-        single_segment.set_free(LineDirection::DirectionA);
-        assert!(single_segment.is_free(LineDirection::DirectionA));
-        assert!(single_segment.is_free(LineDirection::DirectionB));
-
-        let mut double_segment = Segment::Double(
-            init_segment_info(SegmentStatus::Occupied),
-            init_segment_info(SegmentStatus::Free),
-        );
-
-        double_segment.set_free(LineDirection::DirectionA);
-        assert!(double_segment.is_free(LineDirection::DirectionA));
-        assert!(double_segment.is_free(LineDirection::DirectionB));
+    fn test_leave_segment() {
+        let mut single_segment = Segment::Single(occupied_segment_info());
+        single_segment.leave(0, LineDirection::DirectionA);
+        assert!(single_segment.has_room(LineDirection::DirectionA));
+        assert!(single_segment.has_room(LineDirection::DirectionB));
+
+        let mut double_segment = Segment::Double(occupied_segment_info(), init_segment_info());
+        double_segment.leave(0, LineDirection::DirectionA);
+        assert!(double_segment.has_room(LineDirection::DirectionA));
+        assert!(double_segment.has_room(LineDirection::DirectionB));
     }
 
     #[test]
-    fn test_set_occupied_segment() {
-        let mut single_segment = Segment::Single(init_segment_info(SegmentStatus::Occupied));
-        single_segment.set_occupied(LineDirection::DirectionA);
-        assert!(!single_segment.is_free(LineDirection::DirectionA));
-        assert!(!single_segment.is_free(LineDirection::DirectionB));
-
-        let mut double_segment = Segment::Double(
-            init_segment_info(SegmentStatus::Occupied),
-            init_segment_info(SegmentStatus::Free),
-        );
+    fn test_enter_segment() {
+        let mut single_segment = Segment::Single(init_segment_info());
+        single_segment.enter(0, LineDirection::DirectionA, 0);
+        assert!(!single_segment.has_room(LineDirection::DirectionA));
+        assert!(!single_segment.has_room(LineDirection::DirectionB));
+
+        let mut double_segment = Segment::Double(occupied_segment_info(), init_segment_info());
+        double_segment.enter(1, LineDirection::DirectionB, 0);
+        assert!(!double_segment.has_room(LineDirection::DirectionA));
+        assert!(!double_segment.has_room(LineDirection::DirectionB));
+        // the segment is free once the last train leaves
+        double_segment.leave(0, LineDirection::DirectionA);
+        assert!(double_segment.has_room(LineDirection::DirectionA));
+    }
 
-        double_segment.set_occupied(LineDirection::DirectionB);
-        assert!(!double_segment.is_free(LineDirection::DirectionA));
-        assert!(!double_segment.is_free(LineDirection::DirectionB));
+    fn init_segment_info() -> SegmentInfo {
+        SegmentInfo::new(SegmentType::Line, 0)
     }
 
-    fn init_segment_info(stat: SegmentStatus) -> SegmentInfo {
-        SegmentInfo {
-            kind: SegmentType::Line,
-            stat,
-            duration: 0,
-        }
+    // A segment taken by the train of fast_make_car.
+    fn occupied_segment_info() -> SegmentInfo {
+        let mut info = init_segment_info();
+        info.enter(0, LineDirection::DirectionA, 0);
+        info
     }
 
     fn init_railway() -> Railway {
         let line = vec![
-            Segment::Single(init_segment_info(SegmentStatus::Free)),
-            Segment::Single(init_segment_info(SegmentStatus::Occupied)),
-            Segment::Single(init_segment_info(SegmentStatus::Occupied)),
+            Segment::Single(init_segment_info()),
+            Segment::Single(occupied_segment_info()),
+            Segment::Single(occupied_segment_info()),
         ];
-        Railway { line }
+        Railway::new(line)
     }
 
     fn fast_make_car(index: usize, dir: LineDirection) -> car::Car<()> {
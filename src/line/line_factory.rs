@@ -1,8 +1,16 @@
+use super::deadlock::DeadlockPolicy;
+use super::dispatch::{Dispatch, Service, Timetable};
+use super::disruption::{BlockTarget, FailureModel, SegmentBlock};
+use super::kinematics::Motion;
 use super::line;
+use super::service::ServicePattern;
+use super::signalling::Signalling;
 use super::Duration;
 use super::StationID;
+use crate::energy::EnergyModel;
 use crate::fleet;
 use crate::passenger::callbacks;
+use crate::rolling_stock::RollingStock;
 use crate::utils::mixed_iterator;
 
 pub struct LineFactoryConfig {
@@ -11,6 +19,9 @@ pub struct LineFactoryConfig {
     total_staion_count: usize,
     depo_size: usize,
     train_delay: usize,
+    options: LineOptions,
+    branches: Vec<BranchConfig>,
+    circular: bool,
 }
 
 impl LineFactoryConfig {
@@ -29,6 +40,9 @@ impl LineFactoryConfig {
 
             depo_size,
             train_delay,
+            options: LineOptions::default(),
+            branches: Vec::new(),
+            circular: false,
         }
     }
 
@@ -58,6 +72,260 @@ impl LineFactoryConfig {
         self.train_delay = value;
         self
     }
+
+    /// Set the options of the built line.
+    #[must_use]
+    pub fn set_options(mut self, options: LineOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Add a branch to the line. Branches are numbered from 1
+    /// in insertion order.
+    ///
+    /// # Panics
+    /// When the line is built, if the junction is not an intermediate
+    /// station of the main line or if the line is a loop.
+    #[must_use]
+    pub fn add_branch(mut self, branch: BranchConfig) -> Self {
+        self.branches.push(branch);
+        self
+    }
+
+    /// Build a loop line: the last line connects the last station
+    /// back to the first one, so there must be a line for each
+    /// station. Trains of both directions start from the first
+    /// station and never turn back.
+    #[must_use]
+    pub fn set_circular(mut self, value: bool) -> Self {
+        self.circular = value;
+        self
+    }
+}
+
+/// Options of a line that do not change its layout, shared by
+/// the line factory configurations. Each one is passed to the
+/// matching setter of [`super::Line`] when the line is built.
+#[derive(Default)]
+pub struct LineOptions {
+    train_capacity: Option<usize>,
+    door_throughput: Option<usize>,
+    rolling_stock: Vec<RollingStock>,
+    reference_speed: Option<f64>,
+    energy_model: Option<EnergyModel>,
+    failures: Option<FailureModel>,
+    deadlock_policy: DeadlockPolicy,
+    signalling: Signalling,
+    platforms: Vec<(StationID, line::LineDirection, usize)>,
+    blocks: Vec<SegmentBlock>,
+    closures: Vec<(BlockTarget, usize, usize)>,
+    dispatch: Vec<(line::LineDirection, Dispatch)>,
+    patterns: Vec<ServicePattern>,
+}
+
+impl LineOptions {
+    /// See [`super::Line::set_train_capacity`].
+    #[must_use]
+    pub fn set_train_capacity(mut self, value: usize) -> Self {
+        self.train_capacity = Some(value);
+        self
+    }
+
+    /// See [`super::Line::set_door_throughput`].
+    #[must_use]
+    pub fn set_door_throughput(mut self, value: usize) -> Self {
+        self.door_throughput = Some(value);
+        self
+    }
+
+    /// See [`super::Line::set_rolling_stock`].
+    #[must_use]
+    pub fn set_rolling_stock(mut self, classes: Vec<RollingStock>) -> Self {
+        self.rolling_stock = classes;
+        self
+    }
+
+    /// See [`super::Line::set_reference_speed`].
+    #[must_use]
+    pub fn set_reference_speed(mut self, speed: f64) -> Self {
+        self.reference_speed = Some(speed);
+        self
+    }
+
+    #[must_use]
+    pub fn get_reference_speed(&self) -> Option<f64> {
+        self.reference_speed
+    }
+
+    /// See [`super::Line::set_energy_model`].
+    #[must_use]
+    pub fn set_energy_model(mut self, model: EnergyModel) -> Self {
+        self.energy_model = Some(model);
+        self
+    }
+
+    /// See [`super::Line::set_failure_model`].
+    #[must_use]
+    pub fn set_failure_model(mut self, model: FailureModel) -> Self {
+        self.failures = Some(model);
+        self
+    }
+
+    /// See [`super::Line::set_deadlock_policy`].
+    #[must_use]
+    pub fn set_deadlock_policy(mut self, policy: DeadlockPolicy) -> Self {
+        self.deadlock_policy = policy;
+        self
+    }
+
+    /// See [`super::Line::set_signalling`].
+    #[must_use]
+    pub fn set_signalling(mut self, signalling: Signalling) -> Self {
+        self.signalling = signalling;
+        self
+    }
+
+    /// See [`super::Line::set_platforms`].
+    ///
+    /// # Panics
+    /// When the line is built, if the station is not
+    /// on the line or the count is zero.
+    #[must_use]
+    pub fn set_platforms(
+        mut self,
+        station: StationID,
+        dir: line::LineDirection,
+        count: usize,
+    ) -> Self {
+        self.platforms.push((station, dir, count));
+        self
+    }
+
+    /// See [`super::Line::add_segment_block`].
+    ///
+    /// # Panics
+    /// When the line is built, if the block station is not on the
+    /// line or the block link does not connect two consecutive stations.
+    #[must_use]
+    pub fn add_segment_block(mut self, block: SegmentBlock) -> Self {
+        self.blocks.push(block);
+        self
+    }
+
+    /// See [`super::Line::add_closure`].
+    ///
+    /// # Panics
+    /// When the line is built, if the station is not on the line
+    /// or the link does not connect two consecutive stations.
+    #[must_use]
+    pub fn add_closure(mut self, target: BlockTarget, start: usize, end: usize) -> Self {
+        self.closures.push((target, start, end));
+        self
+    }
+
+    /// See [`super::Line::set_dispatch`].
+    #[must_use]
+    pub fn set_dispatch(mut self, dir: line::LineDirection, dispatch: Dispatch) -> Self {
+        self.dispatch.push((dir, dispatch));
+        self
+    }
+
+    /// See [`super::Line::set_timetable`].
+    #[must_use]
+    pub fn set_timetable(self, dir: line::LineDirection, timetable: Timetable) -> Self {
+        self.set_dispatch(dir, timetable.into())
+    }
+
+    /// See [`super::Line::set_service`].
+    #[must_use]
+    pub fn set_service(self, dir: line::LineDirection, service: Service) -> Self {
+        self.set_dispatch(dir, service.into())
+    }
+
+    /// See [`super::Line::set_service_patterns`].
+    #[must_use]
+    pub fn set_service_patterns(mut self, patterns: Vec<ServicePattern>) -> Self {
+        self.patterns = patterns;
+        self
+    }
+
+    fn apply<T>(self, line: super::Line<T>) -> super::Line<T>
+    where
+        T: callbacks::PassengerAction,
+    {
+        let line = match self.train_capacity {
+            Some(capacity) => line.set_train_capacity(capacity),
+            None => line,
+        };
+        let line = match self.door_throughput {
+            Some(rate) => line.set_door_throughput(rate),
+            None => line,
+        };
+        let line = match self.reference_speed {
+            Some(speed) => line.set_reference_speed(speed),
+            None => line,
+        };
+        let line = match self.energy_model {
+            Some(model) => line.set_energy_model(model),
+            None => line,
+        };
+        let line = match self.failures {
+            Some(model) => line.set_failure_model(model),
+            None => line,
+        };
+        let line = line
+            .set_rolling_stock(self.rolling_stock)
+            .set_service_patterns(self.patterns)
+            .set_deadlock_policy(self.deadlock_policy)
+            .set_signalling(self.signalling);
+        let line = self
+            .platforms
+            .into_iter()
+            .fold(line, |line, (station, dir, count)| {
+                line.set_platforms(station, dir, count)
+            });
+        let line = self
+            .blocks
+            .into_iter()
+            .fold(line, super::Line::add_segment_block);
+        let line = self
+            .closures
+            .into_iter()
+            .fold(line, |line, (target, start, end)| {
+                line.add_closure(target, start, end)
+            });
+        self.dispatch
+            .into_iter()
+            .fold(line, |line, (dir, dispatch)| {
+                line.set_dispatch(dir, dispatch)
+            })
+    }
+}
+
+/// A branch leaving the main line after the junction station.
+/// The first line connects the junction to the first station
+/// of the branch, the last station is the branch terminus.
+pub struct BranchConfig {
+    junction: StationID,
+    station_duration: Vec<StationInfoConfig>,
+    line_duration: Vec<LineInfoConfig>,
+}
+
+impl BranchConfig {
+    /// A branch has a line before each station, extra
+    /// stations or lines are ignored.
+    #[must_use]
+    pub fn new(
+        junction: StationID,
+        station_duration: Vec<StationInfoConfig>,
+        line_duration: Vec<LineInfoConfig>,
+    ) -> Self {
+        Self {
+            junction,
+            station_duration,
+            line_duration,
+        }
+    }
 }
 
 pub struct StationInfoConfig {
@@ -81,6 +349,7 @@ impl StationInfoConfig {
     }
 }
 
+#[derive(Debug, PartialEq)]
 pub struct LineInfoConfig {
     chunks: Vec<LineChunkConfig>,
 }
@@ -105,12 +374,25 @@ impl std::iter::FromIterator<LineChunkConfig> for LineInfoConfig {
 pub struct LineChunkConfig {
     duration: Duration,
     kind: LineChunkKind,
+    motion: Option<Motion>,
 }
 
 impl LineChunkConfig {
     #[must_use]
     pub fn new(duration: Duration, kind: LineChunkKind) -> Self {
-        Self { duration, kind }
+        Self {
+            duration,
+            kind,
+            motion: None,
+        }
+    }
+
+    /// Set the length of the chunk and the speed trains reach
+    /// on its link, used by the energy model.
+    #[must_use]
+    pub fn set_motion(mut self, motion: Motion) -> Self {
+        self.motion = Some(motion);
+        self
     }
 }
 
@@ -120,6 +402,11 @@ pub enum LineChunkKind {
     Double,
 }
 
+/// Build the line described by the configuration.
+///
+/// # Panics
+/// If a branch, a platform, a block or a closure of the
+/// configuration does not fit the line.
 #[must_use]
 pub fn line_factory<T>(config: LineFactoryConfig) -> super::Line<T>
 where
@@ -129,28 +416,37 @@ where
         &config.station_duration,
         config.depo_size,
         config.train_delay,
+        config.circular,
     );
-    let railway = railway_factory(config.station_duration, config.line_duration);
+    let railway = if config.circular {
+        loop_railway_factory(config.station_duration, config.line_duration)
+    } else {
+        railway_factory(
+            config.station_duration,
+            config.line_duration,
+            config.branches,
+        )
+    };
     let train_count = 2 * config.depo_size;
     let fleet = fleet::Fleet::new(train_count);
 
-    super::Line::new(
-        train_count,
-        term_a,
-        term_b,
-        railway,
-        fleet,
-        config.total_staion_count,
-    )
+    let line = super::Line::new(term_a, term_b, railway, fleet, config.total_staion_count);
+    config.options.apply(line)
 }
 
 fn terminus_factory(
     station_ics: &[StationInfoConfig],
     d: usize,
     t: usize,
+    circular: bool,
 ) -> (line::Terminus, line::Terminus) {
     let term_a = build_terminus(station_ics.first().unwrap(), d, t);
-    let term_b = build_terminus(station_ics.last().unwrap(), d, t);
+    let last = if circular {
+        station_ics.first()
+    } else {
+        station_ics.last()
+    };
+    let term_b = build_terminus(last.unwrap(), d, t);
     (term_a, term_b)
 }
 
@@ -162,10 +458,46 @@ fn build_terminus(info: &StationInfoConfig, d: usize, t: usize) -> line::Terminu
 fn railway_factory(
     station_ics: Vec<StationInfoConfig>,
     line_ics: Vec<LineInfoConfig>,
+    branches: Vec<BranchConfig>,
 ) -> line::Railway {
     let station_ics = set_terminus(station_ics);
     let line = segment_vector_factory(station_ics, line_ics);
-    line::Railway::new(line)
+    branches
+        .into_iter()
+        .fold(line::Railway::new(line), |railway, branch| {
+            let junction = branch.junction;
+            railway.add_branch(junction, branch_segment_factory(branch))
+        })
+}
+
+fn loop_railway_factory(
+    station_ics: Vec<StationInfoConfig>,
+    line_ics: Vec<LineInfoConfig>,
+) -> line::Railway {
+    assert_eq!(
+        station_ics.len(),
+        line_ics.len(),
+        "a loop needs a line for each station"
+    );
+    line::Railway::new_loop(segment_vector_factory(station_ics, line_ics))
+}
+
+fn branch_segment_factory(branch: BranchConfig) -> Vec<line::Segment> {
+    let mut station_ics = branch.station_duration;
+    station_ics.truncate(branch.line_duration.len());
+    if let Some(last) = station_ics.last_mut() {
+        last.set_terminus();
+    }
+    branch
+        .line_duration
+        .iter()
+        .zip(station_ics.iter())
+        .flat_map(|(line_ic, station_ic)| {
+            let mut segments = rails_all_segment_factory(line_ic);
+            segments.push(station_segment_factory(station_ic));
+            segments
+        })
+        .collect()
 }
 
 fn set_terminus(mut station_ics: Vec<StationInfoConfig>) -> Vec<StationInfoConfig> {
@@ -221,18 +553,21 @@ fn rails_all_segment_factory(line_ic: &LineInfoConfig) -> Vec<line::Segment> {
 }
 
 fn rail_segment_factory(chunk: &LineChunkConfig) -> line::Segment {
-    let dur = chunk.duration;
     match chunk.kind {
-        LineChunkKind::Single => line::Segment::Single(rail_segment_info_factory(dur)),
+        LineChunkKind::Single => line::Segment::Single(rail_segment_info_factory(chunk)),
         LineChunkKind::Double => line::Segment::Double(
-            rail_segment_info_factory(dur),
-            rail_segment_info_factory(dur),
+            rail_segment_info_factory(chunk),
+            rail_segment_info_factory(chunk),
         ),
     }
 }
 
-fn rail_segment_info_factory(duration: Duration) -> line::SegmentInfo {
-    line::SegmentInfo::new(line::SegmentType::Line, duration)
+fn rail_segment_info_factory(chunk: &LineChunkConfig) -> line::SegmentInfo {
+    let info = line::SegmentInfo::new(line::SegmentType::Line, chunk.duration);
+    match chunk.motion {
+        Some(motion) => info.set_motion(motion),
+        None => info,
+    }
 }
 
 #[cfg(test)]
@@ -250,7 +585,7 @@ mod test {
             })
             .collect();
 
-        let (ta, tb) = terminus_factory(&station_ics, 10, 4);
+        let (ta, tb) = terminus_factory(&station_ics, 10, 4, false);
         let expect_ta = line::Terminus::new(0, 10, 4);
         let expect_tb = line::Terminus::new(3, 10, 4);
         assert_eq!(ta, expect_ta);
@@ -277,6 +612,7 @@ mod test {
                         } else {
                             LineChunkKind::Single
                         },
+                        motion: None,
                     })
                     .collect()
             })
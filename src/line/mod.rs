@@ -1,6 +1,14 @@
+pub mod deadlock;
+pub mod dispatch;
+pub mod disruption;
 pub mod fast_line_factory;
+pub mod kinematics;
+#[allow(clippy::module_inception)]
 mod line;
 pub mod line_factory;
+pub mod platform;
+pub mod service;
+pub mod signalling;
 pub use line::{Line, LineDirection, SegmentType};
 
 type Duration = usize;
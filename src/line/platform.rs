@@ -0,0 +1,62 @@
+//! Station platforms. A station has one platform for each direction
+//! unless the line gives it more, letting as many trains stand at
+//! the station at once. Each train entering a station takes the
+//! lowest numbered free platform of its direction.
+
+use super::{LineDirection, StationID};
+use serde::Serialize;
+
+/// A train standing at a station platform from the given step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PlatformStop {
+    car: usize,
+    station: StationID,
+    direction: LineDirection,
+    platform: usize,
+    step: usize,
+}
+
+impl PlatformStop {
+    #[must_use]
+    pub fn new(
+        car: usize,
+        station: StationID,
+        direction: LineDirection,
+        platform: usize,
+        step: usize,
+    ) -> Self {
+        Self {
+            car,
+            station,
+            direction,
+            platform,
+            step,
+        }
+    }
+
+    #[must_use]
+    pub fn get_car(&self) -> usize {
+        self.car
+    }
+
+    #[must_use]
+    pub fn get_station(&self) -> StationID {
+        self.station
+    }
+
+    #[must_use]
+    pub fn get_direction(&self) -> LineDirection {
+        self.direction
+    }
+
+    /// Platform index among those of the direction, from 0.
+    #[must_use]
+    pub fn get_platform(&self) -> usize {
+        self.platform
+    }
+
+    #[must_use]
+    pub fn get_step(&self) -> usize {
+        self.step
+    }
+}
@@ -0,0 +1,71 @@
+//! Service patterns: the part of a line served by a train.
+//! Trains following a short turn pattern turn back at
+//! intermediate stations instead of running to the termini.
+//! Express trains pass through the skipped stations without
+//! stopping. On a branched line each pattern runs on a branch.
+
+use super::StationID;
+
+/// Part of the line served by a train.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ServicePattern {
+    turnback: Option<(StationID, StationID)>,
+    skipped: Vec<StationID>,
+    branch: usize,
+}
+
+impl ServicePattern {
+    /// Serve the whole line, from terminus to terminus.
+    #[must_use]
+    pub fn full() -> Self {
+        Self::default()
+    }
+
+    /// Serve only the stations between first and last, included:
+    /// trains turn back there. The order of the two stations does
+    /// not matter.
+    #[must_use]
+    pub fn short_turn(first: StationID, last: StationID) -> Self {
+        Self {
+            turnback: Some((first, last)),
+            skipped: Vec::new(),
+            branch: 0,
+        }
+    }
+
+    /// Pass through the given stations without stopping.
+    #[must_use]
+    pub fn set_skipped(mut self, skipped: Vec<StationID>) -> Self {
+        self.skipped = skipped;
+        self
+    }
+
+    /// Turnback stations, if any.
+    #[must_use]
+    pub fn get_turnback(&self) -> Option<(StationID, StationID)> {
+        self.turnback
+    }
+
+    /// Run on the given branch, 0 being the main line.
+    #[must_use]
+    pub fn set_branch(mut self, branch: usize) -> Self {
+        self.branch = branch;
+        self
+    }
+
+    #[must_use]
+    pub fn get_branch(&self) -> usize {
+        self.branch
+    }
+
+    #[must_use]
+    pub fn get_skipped(&self) -> &[StationID] {
+        &self.skipped
+    }
+
+    /// Check if trains stop at each station of the main line.
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        self.turnback.is_none() && self.skipped.is_empty() && self.branch == 0
+    }
+}
@@ -0,0 +1,80 @@
+//! Signalling: how close trains can follow each other on the
+//! segments between stations. With fixed blocks a segment is split
+//! into blocks and a train enters a block only when it is empty.
+//! With a moving block a train follows the previous one at its
+//! braking distance. Station platforms hold one train at a time
+//! in both cases.
+
+use super::Duration;
+
+/// Separation between trains running in the same direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signalling {
+    /// Blocks of the given length, in steps of running time.
+    /// Segments shorter than a block are a single block.
+    FixedBlock(Duration),
+    /// A train enters a segment the given number of steps after
+    /// the previous one.
+    MovingBlock(Duration),
+}
+
+impl Default for Signalling {
+    /// Each segment is a single block.
+    fn default() -> Self {
+        Self::FixedBlock(Duration::MAX)
+    }
+}
+
+impl Signalling {
+    /// # Panics
+    /// If the block length is zero.
+    #[must_use]
+    pub fn fixed_block(length: Duration) -> Self {
+        assert!(length > 0, "block length must be positive");
+        Self::FixedBlock(length)
+    }
+
+    /// # Panics
+    /// If the separation is zero.
+    #[must_use]
+    pub fn moving_block(separation: Duration) -> Self {
+        assert!(separation > 0, "train separation must be positive");
+        Self::MovingBlock(separation)
+    }
+
+    /// Steps the last train entered in a segment of the given
+    /// duration must have spent there before another one enters,
+    /// and the number of trains the segment can hold.
+    #[must_use]
+    pub fn admission(&self, duration: Duration) -> (Duration, usize) {
+        match *self {
+            Self::FixedBlock(length) if length < duration => (length, duration.div_ceil(length)),
+            Self::FixedBlock(_) => (duration, 1),
+            Self::MovingBlock(separation) => (separation, duration / separation + 1),
+        }
+    }
+
+    /// Shortest time between two trains entering a segment
+    /// of the given duration.
+    #[must_use]
+    pub fn min_headway(&self, duration: Duration) -> Duration {
+        self.admission(duration).0
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_admission() {
+        assert_eq!(Signalling::default().admission(6), (6, 1));
+        assert_eq!(Signalling::fixed_block(6).admission(6), (6, 1));
+        assert_eq!(Signalling::fixed_block(4).admission(6), (4, 2));
+        assert_eq!(Signalling::fixed_block(2).admission(6), (2, 3));
+        assert_eq!(Signalling::moving_block(2).admission(6), (2, 4));
+        assert_eq!(Signalling::moving_block(8).admission(6), (8, 1));
+        assert_eq!(Signalling::moving_block(1).min_headway(6), 1);
+    }
+}
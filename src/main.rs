@@ -0,0 +1,169 @@
+//! Command line runner: load a scenario, run it one or more times
+//! and write the indicators, the time series, the energy use, the
+//! train delays, the platforms taken and the line capacity of each
+//! run into an output directory.
+
+use clap::Parser;
+use rayon::prelude::*;
+use simtro::engine::Simulation;
+use simtro::output::{self, Kpi, OutputFormat};
+use simtro::report::{
+    self, EnergyReport, LineCapacity, LineDelay, LinePlatformStop, SimulationReport,
+};
+use simtro::scenario::Scenario;
+
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(version, about = "Run a metro simulation scenario")]
+struct Args {
+    /// Scenario file, TOML or JSON.
+    scenario: PathBuf,
+    /// Directory for the output files, created if missing.
+    #[arg(short, long, default_value = "output")]
+    output: PathBuf,
+    /// Seed of the first replication, the following ones use
+    /// the next seeds. Defaults to the scenario seed, or a random one.
+    #[arg(short, long)]
+    seed: Option<u64>,
+    /// Number of steps to simulate. Defaults to the scenario time span.
+    #[arg(long)]
+    steps: Option<u32>,
+    /// Number of independent runs.
+    #[arg(short, long, default_value_t = 1)]
+    replications: usize,
+    /// Output format, repeat the option for more than one.
+    #[arg(short, long, default_values_t = [OutputFormat::Csv])]
+    format: Vec<OutputFormat>,
+}
+
+struct Run {
+    kpi: Kpi,
+    report: SimulationReport,
+    energy: EnergyReport,
+    delays: Vec<LineDelay>,
+    platforms: Vec<LinePlatformStop>,
+    capacity: Vec<LineCapacity>,
+}
+
+fn main() -> ExitCode {
+    match run(&Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("simtro: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    let scenario = Scenario::from_file(&args.scenario)?;
+    let steps = args.steps.unwrap_or_else(|| scenario.steps());
+    let seed = args
+        .seed
+        .or(scenario.seed)
+        .unwrap_or_else(rand::random::<u64>);
+
+    let runs: Vec<Run> = (0..args.replications)
+        .into_par_iter()
+        .map(|i| replication(&scenario, steps, i, seed.wrapping_add(i as u64)))
+        .collect();
+
+    fs::create_dir_all(&args.output)?;
+    let names: Vec<String> = (0..scenario.station_count())
+        .map(|s| scenario.station_name(s))
+        .collect();
+    let kpis: Vec<Kpi> = runs.iter().map(|r| r.kpi.clone()).collect();
+    for format in &args.format {
+        let path = output_file(&args.output, "summary", *format);
+        output::write_kpis(&mut BufWriter::new(File::create(path)?), &kpis, *format)?;
+        for run in &runs {
+            let name = format!("timeseries_{}", run.kpi.replication);
+            let path = output_file(&args.output, &name, *format);
+            let mut out = BufWriter::new(File::create(path)?);
+            output::write_time_series(&mut out, &run.report, &names, *format)?;
+            let name = format!("energy_{}", run.kpi.replication);
+            let path = output_file(&args.output, &name, *format);
+            let mut out = BufWriter::new(File::create(path)?);
+            output::write_energy(&mut out, &run.energy, *format)?;
+            let name = format!("delays_{}", run.kpi.replication);
+            let path = output_file(&args.output, &name, *format);
+            let mut out = BufWriter::new(File::create(path)?);
+            output::write_delays(&mut out, &run.delays, *format)?;
+            let name = format!("platforms_{}", run.kpi.replication);
+            let path = output_file(&args.output, &name, *format);
+            let mut out = BufWriter::new(File::create(path)?);
+            output::write_platforms(&mut out, &run.platforms, *format)?;
+            let name = format!("capacity_{}", run.kpi.replication);
+            let path = output_file(&args.output, &name, *format);
+            let mut out = BufWriter::new(File::create(path)?);
+            output::write_capacity(&mut out, &run.capacity, *format)?;
+        }
+    }
+
+    for kpi in &kpis {
+        let s = &kpi.summary;
+        println!(
+            "replication {} (seed {}): {} steps, generated {}, delivered {}, denied {}, rerouted {}, stranded {}, deadlocks {}, waiting {}, on board {}",
+            kpi.replication,
+            kpi.seed,
+            s.steps,
+            s.generated,
+            s.delivered,
+            s.denied,
+            s.rerouted,
+            s.stranded,
+            s.deadlocks,
+            s.final_waiting,
+            s.final_on_board
+        );
+    }
+    Ok(())
+}
+
+/// Run the scenario once with the given seed. The seed drives
+/// both the traffic profile noise and the simulation.
+fn replication(scenario: &Scenario, steps: u32, index: usize, seed: u64) -> Run {
+    let mut scenario = scenario.clone();
+    scenario.seed = Some(seed);
+    let network = scenario.network();
+    let mut simulation = Simulation::new(
+        scenario.passenger_factory(),
+        network.stations::<()>(),
+        scenario.lines(),
+        (),
+    )
+    .set_closures(network.get_closures())
+    .set_seed(seed);
+    // a deadlock stops the replication, reported in its results
+    if let Err(err) = simulation.try_run_for(steps) {
+        eprintln!("replication {index} (seed {seed}): {err}");
+    }
+    let journeys = simulation.take_journeys();
+    let energy = EnergyReport::new(
+        simulation.lines(),
+        simulation.report(),
+        scenario.steps_per_hour(),
+    );
+    let delays = report::line_delays(simulation.lines());
+    let platforms = report::line_platforms(simulation.lines());
+    let capacity = report::line_capacity(simulation.lines(), scenario.steps_per_hour());
+    let report = simulation.into_report();
+    let kpi = Kpi::new(index, seed, &report, &journeys);
+    Run {
+        kpi,
+        report,
+        energy,
+        delays,
+        platforms,
+        capacity,
+    }
+}
+
+fn output_file(dir: &Path, name: &str, format: OutputFormat) -> PathBuf {
+    dir.join(name).with_extension(format.extension())
+}
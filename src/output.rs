@@ -0,0 +1,553 @@
+//! Write simulation results to disk. Each run produces a row of
+//! key performance indicators, a step by step time series, its
+//! energy use, the train delays, the platforms taken at the stations
+//! and the line capacity, all available as CSV or JSON.
+
+use crate::passenger::journey::Journey;
+use crate::report::{
+    EnergyReport, LineCapacity, LineDelay, LinePlatformStop, RunSummary, SimulationReport,
+};
+
+use serde::Serialize;
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+/// Supported output formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Csv,
+    Json,
+}
+
+impl OutputFormat {
+    /// File extension for this format.
+    #[must_use]
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown output format '{s}', expected csv or json")),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+/// Key performance indicators of a single run.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Kpi {
+    pub replication: usize,
+    pub seed: u64,
+    #[serde(flatten)]
+    pub summary: RunSummary,
+    /// Average steps from origin to destination of delivered passengers.
+    pub mean_travel_time: Option<f64>,
+    /// Average steps spent on a platform by delivered passengers.
+    pub mean_wait_time: Option<f64>,
+}
+
+impl Kpi {
+    #[must_use]
+    pub fn new(
+        replication: usize,
+        seed: u64,
+        report: &SimulationReport,
+        journeys: &[Journey],
+    ) -> Self {
+        let complete: Vec<&Journey> = journeys.iter().filter(|j| j.is_complete()).collect();
+        let mean_travel_time = mean(complete.iter().filter_map(|j| j.travel_time()));
+        let mean_wait_time = mean(complete.iter().map(|j| j.wait_time()));
+        Self {
+            replication,
+            seed,
+            summary: report.summary(),
+            mean_travel_time,
+            mean_wait_time,
+        }
+    }
+
+    const HEADER: [&'static str; 17] = [
+        "replication",
+        "seed",
+        "steps",
+        "generated",
+        "delivered",
+        "denied",
+        "rerouted",
+        "stranded",
+        "deadlocks",
+        "peak_waiting",
+        "peak_on_board",
+        "final_waiting",
+        "final_on_board",
+        "mean_travel_time",
+        "mean_wait_time",
+        "traction",
+        "regenerated",
+    ];
+
+    fn csv_row(&self) -> String {
+        let s = &self.summary;
+        let values = [
+            self.replication.to_string(),
+            self.seed.to_string(),
+            s.steps.to_string(),
+            s.generated.to_string(),
+            s.delivered.to_string(),
+            s.denied.to_string(),
+            s.rerouted.to_string(),
+            s.stranded.to_string(),
+            s.deadlocks.to_string(),
+            s.peak_waiting.to_string(),
+            s.peak_on_board.to_string(),
+            s.final_waiting.to_string(),
+            s.final_on_board.to_string(),
+            optional(self.mean_travel_time),
+            optional(self.mean_wait_time),
+            s.traction.to_string(),
+            s.regenerated.to_string(),
+        ];
+        values.join(",")
+    }
+}
+
+fn mean<I: Iterator<Item = u32>>(iter: I) -> Option<f64> {
+    let (count, sum) = iter.fold((0usize, 0f64), |(c, s), v| (c + 1, s + f64::from(v)));
+    if count == 0 {
+        None
+    } else {
+        Some(sum / count as f64)
+    }
+}
+
+fn optional(value: Option<f64>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// Write one row of indicators per run.
+pub fn write_kpis<W: Write>(out: &mut W, kpis: &[Kpi], format: OutputFormat) -> io::Result<()> {
+    match format {
+        OutputFormat::Csv => {
+            writeln!(out, "{}", Kpi::HEADER.join(","))?;
+            for kpi in kpis {
+                writeln!(out, "{}", kpi.csv_row())?;
+            }
+            Ok(())
+        }
+        OutputFormat::Json => write_json(out, kpis),
+    }
+}
+
+/// Write the step by step report. In CSV the waiting passengers
+/// get a column per station, labelled with the given names,
+/// the on board passengers and the net energy a column per line.
+pub fn write_time_series<W: Write>(
+    out: &mut W,
+    report: &SimulationReport,
+    station_names: &[String],
+    format: OutputFormat,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Csv => write_time_series_csv(out, report, station_names),
+        OutputFormat::Json => write_json(out, report.get_steps()),
+    }
+}
+
+fn write_time_series_csv<W: Write>(
+    out: &mut W,
+    report: &SimulationReport,
+    station_names: &[String],
+) -> io::Result<()> {
+    let lines = report
+        .get_steps()
+        .first()
+        .map_or(0, |s| s.get_on_board().len());
+    let header = [
+        "step",
+        "generated",
+        "delivered",
+        "denied",
+        "rerouted",
+        "stranded",
+        "deadlocks",
+        "waiting",
+        "on_board",
+    ]
+    .into_iter()
+    .map(String::from)
+    .chain(
+        station_names
+            .iter()
+            .map(|n| csv_field(&format!("waiting_{n}")).into_owned()),
+    )
+    .chain((0..lines).map(|i| format!("on_board_line_{i}")))
+    .chain(["traction".to_string(), "regenerated".to_string()])
+    .chain((0..lines).map(|i| format!("energy_line_{i}")));
+    writeln!(out, "{}", header.collect::<Vec<_>>().join(","))?;
+    for step in report.get_steps() {
+        let total = step.total_energy();
+        let row = [
+            step.get_step() as usize,
+            step.get_generated(),
+            step.get_delivered(),
+            step.get_denied(),
+            step.get_rerouted(),
+            step.get_stranded(),
+            step.get_deadlocks(),
+            step.total_waiting(),
+            step.total_on_board(),
+        ]
+        .into_iter()
+        .chain(step.get_waiting().iter().copied())
+        .chain(step.get_on_board().iter().copied())
+        .map(|v| v.to_string())
+        .chain([total.get_traction(), total.get_regenerated()].map(|v| v.to_string()))
+        .chain(step.get_energy().iter().map(|e| e.net().to_string()));
+        writeln!(out, "{}", row.collect::<Vec<_>>().join(","))?;
+    }
+    Ok(())
+}
+
+/// Write the energy used during a run. In CSV each row has a scope:
+/// a train, with its line index, car id and class, a line or an hour.
+pub fn write_energy<W: Write>(
+    out: &mut W,
+    energy: &EnergyReport,
+    format: OutputFormat,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Csv => write_energy_csv(out, energy),
+        OutputFormat::Json => write_json(out, energy),
+    }
+}
+
+fn write_energy_csv<W: Write>(out: &mut W, energy: &EnergyReport) -> io::Result<()> {
+    writeln!(out, "scope,index,car,class,traction,regenerated")?;
+    for train in &energy.trains {
+        let class = csv_field(train.class.as_deref().unwrap_or_default());
+        let e = &train.energy;
+        writeln!(
+            out,
+            "train,{},{},{class},{},{}",
+            train.line,
+            train.car,
+            e.get_traction(),
+            e.get_regenerated()
+        )?;
+    }
+    let totals = [("line", &energy.lines), ("hour", &energy.hours)];
+    for (scope, values) in totals {
+        for (index, e) in values.iter().enumerate() {
+            writeln!(
+                out,
+                "{scope},{index},,,{},{}",
+                e.get_traction(),
+                e.get_regenerated()
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Write the train delays, a row for each delay.
+pub fn write_delays<W: Write>(
+    out: &mut W,
+    delays: &[LineDelay],
+    format: OutputFormat,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Csv => {
+            writeln!(out, "line,car,start,steps,cause")?;
+            for LineDelay { line, delay } in delays {
+                let cause = serde_json::to_value(delay.get_cause())?;
+                writeln!(
+                    out,
+                    "{line},{},{},{},{}",
+                    delay.get_car(),
+                    delay.get_start(),
+                    delay.get_steps(),
+                    cause.as_str().unwrap_or_default()
+                )?;
+            }
+            Ok(())
+        }
+        OutputFormat::Json => write_json(out, delays),
+    }
+}
+
+/// Write the platforms taken at the stations, a row for each stop.
+pub fn write_platforms<W: Write>(
+    out: &mut W,
+    stops: &[LinePlatformStop],
+    format: OutputFormat,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Csv => {
+            writeln!(out, "line,car,station,direction,platform,step")?;
+            for LinePlatformStop { line, stop } in stops {
+                let direction = serde_json::to_value(stop.get_direction())?;
+                writeln!(
+                    out,
+                    "{line},{},{},{},{},{}",
+                    stop.get_car(),
+                    stop.get_station(),
+                    direction.as_str().unwrap_or_default(),
+                    stop.get_platform(),
+                    stop.get_step()
+                )?;
+            }
+            Ok(())
+        }
+        OutputFormat::Json => write_json(out, stops),
+    }
+}
+
+/// Write the capacity of each line.
+pub fn write_capacity<W: Write>(
+    out: &mut W,
+    capacity: &[LineCapacity],
+    format: OutputFormat,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Csv => {
+            writeln!(out, "line,min_headway,capacity,throughput")?;
+            for c in capacity {
+                writeln!(
+                    out,
+                    "{},{},{},{}",
+                    c.line, c.min_headway, c.capacity, c.throughput
+                )?;
+            }
+            Ok(())
+        }
+        OutputFormat::Json => write_json(out, capacity),
+    }
+}
+
+/// Quote a CSV field holding a separator, a quote or a line break,
+/// doubling the quotes inside it.
+fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+fn write_json<W: Write, T: Serialize + ?Sized>(out: &mut W, value: &T) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *out, value)?;
+    writeln!(out)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::energy::Energy;
+    use crate::line::disruption::{Delay, DelayCause};
+    use crate::line::platform::PlatformStop;
+    use crate::line::LineDirection;
+    use crate::report::{StepReport, TrainEnergy};
+
+    fn make_report() -> SimulationReport {
+        let mut report = SimulationReport::new(2);
+        report.push(
+            StepReport::new(0, 3, vec![2, 1], vec![0], 0).set_energy(vec![Energy::new(1., 0.)]),
+        );
+        report.push(
+            StepReport::new(1, 1, vec![1, 0], vec![2], 1)
+                .set_denied(1)
+                .set_closure_effects(2, 1)
+                .set_deadlocks(1)
+                .set_energy(vec![Energy::new(0.5, 0.5)]),
+        );
+        report
+    }
+
+    fn make_journeys() -> Vec<Journey> {
+        let mut journey = Journey::new(0, 0, 0, 1);
+        journey.board(2, 0);
+        journey.alight(6, 1);
+        vec![journey, Journey::new(1, 1, 1, 0)]
+    }
+
+    #[test]
+    fn test_format_parse() {
+        assert_eq!("CSV".parse(), Ok(OutputFormat::Csv));
+        assert_eq!("json".parse(), Ok(OutputFormat::Json));
+        assert!("yaml".parse::<OutputFormat>().is_err());
+    }
+
+    #[test]
+    fn test_kpi() {
+        let kpi = Kpi::new(2, 7, &make_report(), &make_journeys());
+        assert_eq!(kpi.summary.generated, 4);
+        assert_eq!(kpi.mean_travel_time, Some(6.));
+        assert_eq!(kpi.mean_wait_time, Some(2.));
+
+        let mut out = Vec::new();
+        write_kpis(&mut out, std::slice::from_ref(&kpi), OutputFormat::Csv).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let mut rows = out.lines();
+        assert_eq!(rows.next().unwrap(), Kpi::HEADER.join(","));
+        assert_eq!(
+            rows.next().unwrap(),
+            "2,7,2,4,1,1,2,1,1,3,2,1,2,6,2,1.5,0.5"
+        );
+
+        let mut out = Vec::new();
+        write_kpis(&mut out, &[kpi], OutputFormat::Json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json[0]["delivered"], 1);
+        assert_eq!(json[0]["mean_travel_time"], 6.);
+    }
+
+    #[test]
+    fn test_time_series() {
+        let names = vec![String::from("a"), String::from("b")];
+        let mut out = Vec::new();
+        write_time_series(&mut out, &make_report(), &names, OutputFormat::Csv).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let rows: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            rows,
+            [
+                "step,generated,delivered,denied,rerouted,stranded,deadlocks,waiting,on_board,waiting_a,waiting_b,on_board_line_0,traction,regenerated,energy_line_0",
+                "0,3,0,0,0,0,0,3,0,2,1,0,1,0,1",
+                "1,1,1,1,2,1,1,1,2,1,0,2,0.5,0.5,0",
+            ]
+        );
+
+        let mut out = Vec::new();
+        write_time_series(&mut out, &make_report(), &names, OutputFormat::Json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json[1]["waiting"], serde_json::json!([1, 0]));
+    }
+
+    #[test]
+    fn test_quoted_names() {
+        let names = vec![String::from("Main St, North"), String::from("\"Old\" Town")];
+        let mut out = Vec::new();
+        write_time_series(&mut out, &make_report(), &names, OutputFormat::Csv).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let header = out.lines().next().unwrap();
+        assert!(header.contains(",\"waiting_Main St, North\",\"waiting_\"\"Old\"\" Town\","));
+        assert_eq!(csv_field("plain"), "plain");
+    }
+
+    #[test]
+    fn test_energy() {
+        let energy = EnergyReport {
+            trains: vec![TrainEnergy {
+                line: 0,
+                car: 3,
+                class: Some(String::from("long")),
+                energy: Energy::new(2., 1.),
+            }],
+            lines: vec![Energy::new(2., 1.)],
+            hours: vec![Energy::new(1.5, 0.5), Energy::new(0.5, 0.5)],
+        };
+        let mut out = Vec::new();
+        write_energy(&mut out, &energy, OutputFormat::Csv).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let rows: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            rows,
+            [
+                "scope,index,car,class,traction,regenerated",
+                "train,0,3,long,2,1",
+                "line,0,,,2,1",
+                "hour,0,,,1.5,0.5",
+                "hour,1,,,0.5,0.5",
+            ]
+        );
+
+        let mut out = Vec::new();
+        write_energy(&mut out, &energy, OutputFormat::Json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json["trains"][0]["traction"], 2.);
+    }
+
+    #[test]
+    fn test_delays() {
+        let delays = [LineDelay {
+            line: 1,
+            delay: Delay::new(4, 10, DelayCause::KnockOn),
+        }];
+        let mut out = Vec::new();
+        write_delays(&mut out, &delays, OutputFormat::Csv).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let rows: Vec<&str> = out.lines().collect();
+        assert_eq!(rows, ["line,car,start,steps,cause", "1,4,10,1,knock_on"]);
+
+        let mut out = Vec::new();
+        write_delays(&mut out, &delays, OutputFormat::Json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json[0]["cause"], "knock_on");
+        assert_eq!(json[0]["line"], 1);
+    }
+
+    #[test]
+    fn test_platforms() {
+        let stops = [LinePlatformStop {
+            line: 0,
+            stop: PlatformStop::new(2, 5, LineDirection::DirectionB, 1, 30),
+        }];
+        let mut out = Vec::new();
+        write_platforms(&mut out, &stops, OutputFormat::Csv).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let rows: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            rows,
+            [
+                "line,car,station,direction,platform,step",
+                "0,2,5,direction_b,1,30"
+            ]
+        );
+
+        let mut out = Vec::new();
+        write_platforms(&mut out, &stops, OutputFormat::Json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json[0]["platform"], 1);
+        assert_eq!(json[0]["direction"], "direction_b");
+    }
+
+    #[test]
+    fn test_capacity() {
+        let capacity = [LineCapacity {
+            line: 0,
+            min_headway: 4,
+            capacity: 30.,
+            throughput: 12.5,
+        }];
+        let mut out = Vec::new();
+        write_capacity(&mut out, &capacity, OutputFormat::Csv).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let rows: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            rows,
+            ["line,min_headway,capacity,throughput", "0,4,30,12.5"]
+        );
+
+        let mut out = Vec::new();
+        write_capacity(&mut out, &capacity, OutputFormat::Json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json[0]["min_headway"], 4);
+    }
+}
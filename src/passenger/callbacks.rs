@@ -1,6 +1,63 @@
+//! Callbacks invoked by passengers during their trip. User code can
+//! implement ``PassengerAction`` to collect its own statistics.
+
+use crate::line::LineDirection;
+
+/// Information about where and when a passenger action takes place.
+/// ``line`` and ``direction`` are set only for actions involving a train.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActionContext {
+    pub step: u32,
+    pub station: usize,
+    pub line: Option<usize>,
+    pub direction: Option<LineDirection>,
+}
+
+impl ActionContext {
+    /// Context for an action on the platform.
+    #[must_use]
+    pub fn in_station(step: u32, station: usize) -> Self {
+        Self {
+            step,
+            station,
+            line: None,
+            direction: None,
+        }
+    }
+
+    /// Context for an action involving a train of the
+    /// given line, running in the given direction.
+    #[must_use]
+    pub fn on_train(step: u32, station: usize, line: usize, direction: LineDirection) -> Self {
+        Self {
+            step,
+            station,
+            line: Some(line),
+            direction: Some(direction),
+        }
+    }
+}
+
+/// Callbacks invoked by a passenger during its trip. The first
+/// argument is always the passenger unique id, the second
+/// describes when and where the action takes place.
 pub trait PassengerAction: Sync + Send + std::fmt::Debug {
-    fn enter_station(&mut self, _: usize) {}
-    fn leave_train(&mut self, _: usize) {}
+    /// The passenger enters the origin station.
+    fn enter_station(&mut self, _id: u32, _ctx: &ActionContext) {}
+    /// The passenger boards a train.
+    fn board(&mut self, _id: u32, _ctx: &ActionContext) {}
+    /// The passenger leaves a train.
+    fn alight(&mut self, _id: u32, _ctx: &ActionContext) {}
+    /// The passenger left a train in an interchange and
+    /// waits for the next one.
+    fn transfer(&mut self, _id: u32, _ctx: &ActionContext) {}
+    /// The passenger reached the final destination.
+    fn arrive(&mut self, _id: u32, _ctx: &ActionContext) {}
+    /// The passenger could not board a full train.
+    fn denied_boarding(&mut self, _id: u32, _ctx: &ActionContext) {}
+    /// A closure cut the passenger off its destination,
+    /// the passenger leaves the network.
+    fn stranded(&mut self, _id: u32, _ctx: &ActionContext) {}
 }
 
 pub trait PassengerActionFactory<T>
@@ -12,7 +69,5 @@ where
 
 impl PassengerAction for () {}
 impl PassengerActionFactory<()> for () {
-    fn factory(&mut self) -> () {
-        ()
-    }
+    fn factory(&mut self) {}
 }
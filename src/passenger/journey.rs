@@ -0,0 +1,207 @@
+//! Journey record of a single passenger. It keeps the step the
+//! passenger entered the origin station, each boarding and alighting
+//! and the arrival at the destination, so that wait times, in-vehicle
+//! times and transfers can be computed once the journey is complete.
+//! A journey cut short by a closure records where the passenger
+//! left the network instead of the arrival.
+
+/// Something that happened at a given step in a given station.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JourneyEvent {
+    pub step: u32,
+    pub station: usize,
+}
+
+impl JourneyEvent {
+    #[must_use]
+    pub fn new(step: u32, station: usize) -> Self {
+        Self { step, station }
+    }
+}
+
+/// Timestamps of a passenger trip through the network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Journey {
+    passenger: u32,
+    origin: JourneyEvent,
+    destination: usize,
+    boardings: Vec<JourneyEvent>,
+    alightings: Vec<JourneyEvent>,
+    arrival: Option<u32>,
+    stranded: Option<JourneyEvent>,
+}
+
+impl Journey {
+    /// Start a new journey: the passenger enters station
+    /// origin at the given step and wants to reach destination.
+    #[must_use]
+    pub fn new(passenger: u32, step: u32, origin: usize, destination: usize) -> Self {
+        Self {
+            passenger,
+            origin: JourneyEvent::new(step, origin),
+            destination,
+            boardings: Vec::new(),
+            alightings: Vec::new(),
+            arrival: None,
+            stranded: None,
+        }
+    }
+
+    pub fn set_passenger_id(&mut self, id: u32) {
+        self.passenger = id;
+    }
+
+    /// Unique id of the passenger making this journey.
+    #[must_use]
+    pub fn get_passenger_id(&self) -> u32 {
+        self.passenger
+    }
+
+    /// Record a boarding in station at step.
+    pub fn board(&mut self, step: u32, station: usize) {
+        self.boardings.push(JourneyEvent::new(step, station));
+    }
+
+    /// Record an alighting in station at step. If station is
+    /// the final destination the journey is complete.
+    pub fn alight(&mut self, step: u32, station: usize) {
+        self.alightings.push(JourneyEvent::new(step, station));
+        if station == self.destination {
+            self.arrival = Some(step);
+        }
+    }
+
+    /// Record the passenger leaving the network in station at
+    /// step, because a closure cut it off its destination.
+    pub fn strand(&mut self, step: u32, station: usize) {
+        self.stranded = Some(JourneyEvent::new(step, station));
+    }
+
+    /// Station and step of the passenger entering the network.
+    #[must_use]
+    pub fn get_origin(&self) -> JourneyEvent {
+        self.origin
+    }
+
+    #[must_use]
+    pub fn get_destination(&self) -> usize {
+        self.destination
+    }
+
+    #[must_use]
+    pub fn get_boardings(&self) -> &[JourneyEvent] {
+        &self.boardings
+    }
+
+    #[must_use]
+    pub fn get_alightings(&self) -> &[JourneyEvent] {
+        &self.alightings
+    }
+
+    /// Step of the arrival at the destination, if any.
+    #[must_use]
+    pub fn get_arrival(&self) -> Option<u32> {
+        self.arrival
+    }
+
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.arrival.is_some()
+    }
+
+    /// Station and step of the passenger leaving the
+    /// network because of a closure, if any.
+    #[must_use]
+    pub fn get_stranded(&self) -> Option<JourneyEvent> {
+        self.stranded
+    }
+
+    #[must_use]
+    pub fn is_stranded(&self) -> bool {
+        self.stranded.is_some()
+    }
+
+    /// Number of line changes during the journey.
+    #[must_use]
+    pub fn transfers(&self) -> usize {
+        self.boardings.len().saturating_sub(1)
+    }
+
+    /// Steps from the origin station to the destination.
+    #[must_use]
+    pub fn travel_time(&self) -> Option<u32> {
+        self.arrival.map(|arrival| arrival - self.origin.step)
+    }
+
+    /// Step the passenger started waiting on its current platform:
+    /// entering the origin station or leaving the last train.
+    #[must_use]
+    pub fn waiting_since(&self) -> u32 {
+        self.alightings.last().map_or(self.origin.step, |e| e.step)
+    }
+
+    /// Steps spent waiting on a platform, both in the origin
+    /// station and in the interchanges.
+    #[must_use]
+    pub fn wait_time(&self) -> u32 {
+        let ready = std::iter::once(self.origin.step).chain(self.alightings.iter().map(|e| e.step));
+        ready
+            .zip(self.boardings.iter())
+            .map(|(ready, board)| board.step - ready)
+            .sum()
+    }
+
+    /// Steps spent on board of a train.
+    #[must_use]
+    pub fn in_vehicle_time(&self) -> u32 {
+        self.boardings
+            .iter()
+            .zip(self.alightings.iter())
+            .map(|(board, alight)| alight.step - board.step)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_journey_times() {
+        let mut journey = Journey::new(0, 10, 0, 4);
+        assert!(!journey.is_complete());
+        journey.board(14, 0);
+        journey.alight(20, 2);
+        journey.board(23, 2);
+        journey.alight(30, 4);
+
+        assert!(journey.is_complete());
+        assert_eq!(journey.get_arrival(), Some(30));
+        assert_eq!(journey.travel_time(), Some(20));
+        assert_eq!(journey.wait_time(), 7);
+        assert_eq!(journey.in_vehicle_time(), 13);
+        assert_eq!(journey.transfers(), 1);
+        assert_eq!(
+            journey.travel_time().unwrap(),
+            journey.wait_time() + journey.in_vehicle_time()
+        );
+    }
+
+    #[test]
+    fn test_incomplete_journey() {
+        let mut journey = Journey::new(1, 3, 1, 2);
+        journey.board(5, 1);
+        assert_eq!(journey.travel_time(), None);
+        assert_eq!(journey.wait_time(), 2);
+        assert_eq!(journey.in_vehicle_time(), 0);
+        assert_eq!(journey.transfers(), 0);
+        assert_eq!(journey.waiting_since(), 3);
+        journey.alight(9, 3);
+        assert_eq!(journey.waiting_since(), 9);
+        assert!(!journey.is_stranded());
+        journey.strand(12, 3);
+        assert_eq!(journey.get_stranded(), Some(JourneyEvent::new(12, 3)));
+        assert!(!journey.is_complete());
+    }
+}
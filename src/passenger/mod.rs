@@ -1,3 +1,5 @@
 pub mod callbacks;
+pub mod journey;
+#[allow(clippy::module_inception)]
 mod passenger;
 pub use passenger::*;
@@ -6,14 +6,18 @@
 //!  iterates through this matrix and generate the required number of
 //!  passengers that start from station i to stattion j
 //!
-use super::callbacks::{PassengerAction, PassengerActionFactory};
+use super::callbacks::{ActionContext, PassengerAction, PassengerActionFactory};
+use super::journey::Journey;
 use crate::station::PassengerStation;
 use crate::traffic_generator::TrafficGenerator;
 use crate::utils;
 use crate::utils::index_list;
+use crate::utils::random::SimRng;
+use crate::utils::unique_id::UniqueId;
 
 /// Passenger struct. Keep information about the
-/// departure and destionaton station, with an unique id.
+/// departure and destionaton station, with an unique id,
+/// and the record of the journey so far.
 #[derive(Debug)]
 pub struct Passenger<T> {
     id: u32,
@@ -22,15 +26,17 @@ pub struct Passenger<T> {
     next_stop: usize,
     dest: usize,
     callback: T,
+    journey: Journey,
 }
 
 impl<T> Passenger<T>
 where
     T: PassengerAction,
 {
-    /// Create a new passenger instance.
+    /// Create a new passenger instance, entering the
+    /// network at the given step.
     #[must_use]
-    fn new(id: u32, start: usize, dest: usize, callback: T) -> Self {
+    pub(crate) fn new(id: u32, start: usize, dest: usize, callback: T, step: u32) -> Self {
         Self {
             id,
             start,
@@ -38,6 +44,7 @@ where
             next_stop: 0,
             dest,
             callback,
+            journey: Journey::new(id, step, start, dest),
         }
     }
 
@@ -68,28 +75,103 @@ where
         self
     }
 
+    /// return the direction - terminus station - of
+    /// the next train to take
+    #[must_use]
+    pub fn get_next_direction(&self) -> usize {
+        self.next_dir
+    }
+
+    /// return the next station where the passenger
+    /// leaves the train
+    #[must_use]
+    pub fn get_next_stop(&self) -> usize {
+        self.next_stop
+    }
+
     /// return passenger destination
     #[must_use]
     pub fn get_destination(&self) -> usize {
         self.dest
     }
 
+    /// return passenger unique id
+    #[must_use]
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    /// Notify the passenger entering the origin station.
     #[must_use]
     pub fn enter_station(mut self) -> Self {
-        self.callback.enter_station(self.start);
+        let step = self.journey.get_origin().step;
+        let ctx = ActionContext::in_station(step, self.start);
+        self.callback.enter_station(self.id, &ctx);
         self
     }
 
+    /// Record the boarding on a train.
     #[must_use]
-    pub fn leave_train(mut self) -> Self {
-        self.callback.leave_train(self.next_stop);
+    pub fn board(mut self, ctx: &ActionContext) -> Self {
+        self.journey.board(ctx.step, ctx.station);
+        self.callback.board(self.id, ctx);
         self
     }
+
+    /// Record the alighting from a train.
+    #[must_use]
+    pub fn leave_train(mut self, ctx: &ActionContext) -> Self {
+        self.journey.alight(ctx.step, ctx.station);
+        self.callback.alight(self.id, ctx);
+        self
+    }
+
+    /// Notify the passenger waiting for another
+    /// train in an interchange.
+    #[must_use]
+    pub fn transfer(mut self, ctx: &ActionContext) -> Self {
+        self.callback.transfer(self.id, ctx);
+        self
+    }
+
+    /// Notify the arrival at the final destination.
+    #[must_use]
+    pub fn arrive(mut self, ctx: &ActionContext) -> Self {
+        self.callback.arrive(self.id, ctx);
+        self
+    }
+
+    /// Record the passenger leaving the network
+    /// because a closure cut it off its destination.
+    #[must_use]
+    pub fn strand(mut self, ctx: &ActionContext) -> Self {
+        self.journey.strand(ctx.step, ctx.station);
+        self.callback.stranded(self.id, ctx);
+        self
+    }
+
+    /// Notify the passenger that a full train left without it.
+    pub fn deny_boarding(&mut self, ctx: &ActionContext) {
+        self.callback.denied_boarding(self.id, ctx);
+    }
+
+    /// Return the journey so far.
+    #[must_use]
+    pub fn get_journey(&self) -> &Journey {
+        &self.journey
+    }
+
+    /// Consume the passenger returning its journey record.
+    #[must_use]
+    pub fn into_journey(self) -> Journey {
+        self.journey
+    }
 }
 
 impl<T> utils::unique_id::SetId for Passenger<T> {
     fn set_id(mut self, id: u32) -> Self {
         self.id = id;
+        self.journey.set_passenger_id(id);
         self
     }
 }
@@ -115,9 +197,10 @@ impl<T> index_list::Indexer<Passenger<T>> for PassengerNextDirectionIndex {
 /// Create new passengers for each station going to each
 /// station. At each simulation step create passengers
 /// according to the number given by the traffic generator
-/// implementation.
+/// implementation. Each passenger gets a unique id.
 pub struct PassengerFactory<T> {
     traffic_generator: Vec<Vec<Option<T>>>,
+    unique_id: UniqueId,
 }
 
 impl<T> PassengerFactory<T>
@@ -127,23 +210,44 @@ where
     /// Initialize factory. T initialization is now handled here.
     #[must_use]
     pub fn new(traffic_generator: Vec<Vec<Option<T>>>) -> Self {
-        Self { traffic_generator }
+        Self {
+            traffic_generator,
+            unique_id: UniqueId::new(),
+        }
+    }
+
+    /// Factory drawing from the same traffic generators, with
+    /// its own passenger ids starting from zero.
+    #[must_use]
+    pub fn by_ref(&self) -> PassengerFactory<&T> {
+        PassengerFactory::new(
+            self.traffic_generator
+                .iter()
+                .map(|row| row.iter().map(Option::as_ref).collect())
+                .collect(),
+        )
     }
 
     /// Generate traffic at given step. Borrows mutable the list of all stations.
+    /// Randomness is drawn from the given generator.
+    /// Return the number of generated passengers.
     pub fn generate_traffic<Pc: PassengerAction, S: PassengerStation<Pc>, Tf>(
-        &self,
+        &mut self,
         step: u32,
         stations: &mut [S],
         tf: &mut Tf,
-    ) where
+        rng: &mut SimRng,
+    ) -> usize
+    where
         Tf: PassengerActionFactory<Pc> + Send + Sync,
     {
+        let unique_id = &mut self.unique_id;
         self.traffic_generator
             .iter()
             .zip(stations.iter_mut())
             .enumerate()
-            .for_each(|(i, (g, s))| Self::build_station_traffic(i, g, s, step, tf));
+            .map(|(i, (g, s))| Self::build_station_traffic(i, g, s, step, tf, rng, unique_id))
+            .sum()
     }
 
     /// Create passengers for the given station implementation.
@@ -153,17 +257,23 @@ where
         stat: &mut S,
         step: u32,
         tf: &mut Tf,
-    ) where
+        rng: &mut SimRng,
+        unique_id: &mut UniqueId,
+    ) -> usize
+    where
         Tf: PassengerActionFactory<Pc>,
     {
+        let mut count = 0;
         for (dst, gen) in traff_gen.iter().enumerate() {
             if let Some(gen) = gen {
-                for _ in 0..gen.next_traffic_flow(step) {
-                    let p = Passenger::new(0, index, dst, tf.factory());
-                    stat.enter_passenger(p);
+                for _ in 0..gen.next_traffic_flow(step, rng) {
+                    let p = Passenger::new(0, index, dst, tf.factory(), step);
+                    stat.enter_passenger(unique_id.set_id(p));
+                    count += 1;
                 }
             }
         }
+        count
     }
 }
 
@@ -182,7 +292,7 @@ mod test {
     }
 
     impl TrafficGenerator for u32 {
-        fn next_traffic_flow(&self, _step: u32) -> u32 {
+        fn next_traffic_flow(&self, _step: u32, _rng: &mut SimRng) -> u32 {
             *self
         }
     }
@@ -202,11 +312,34 @@ mod test {
 
         let mut stations = vec![vec![], vec![], vec![]];
 
-        let pass_factory = PassengerFactory::new(traffic_generator);
-        pass_factory.generate_traffic(0, &mut stations, &mut ());
+        let mut pass_factory = PassengerFactory::new(traffic_generator);
+        let count = pass_factory.generate_traffic(
+            0,
+            &mut stations,
+            &mut (),
+            &mut crate::utils::random::make_rng(None),
+        );
+        assert_eq!(count, 17);
         assert_eq!(stations.len(), 3);
         assert_eq!(stations[0].len(), 9);
         assert_eq!(stations[1].len(), 5);
         assert_eq!(stations[2].len(), 3);
     }
+
+    #[test]
+    fn test_unique_passenger_id() {
+        let traffic_generator = vec![vec![None, Some(4)], vec![Some(3), None]];
+        let mut stations = vec![vec![], vec![]];
+        let mut pass_factory = PassengerFactory::new(traffic_generator);
+        let mut rng = crate::utils::random::make_rng(None);
+        pass_factory.generate_traffic(0, &mut stations, &mut (), &mut rng);
+        pass_factory.generate_traffic(1, &mut stations, &mut (), &mut rng);
+
+        let ids: std::collections::HashSet<u32> =
+            stations.iter().flatten().map(Passenger::get_id).collect();
+        assert_eq!(ids.len(), 14);
+        for p in stations.iter().flatten() {
+            assert_eq!(p.get_id(), p.get_journey().get_passenger_id());
+        }
+    }
 }
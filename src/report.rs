@@ -0,0 +1,442 @@
+//! Collect the results of a simulation run.
+//! For each step the report keeps the number of generated passengers,
+//! the passengers waiting in each station, the passengers on board of
+//! each line's fleet, the passengers that reached their destination,
+//! those rerouted or stranded by closures, the deadlocks between
+//! trains and the energy used by each line's trains. Delays caused by
+//! failures and blocks, the platforms taken at the stations and the
+//! capacity of each line are collected from the lines at the end.
+
+use crate::energy::Energy;
+use crate::line::disruption::Delay;
+use crate::line::platform::PlatformStop;
+use crate::line::Line;
+
+use serde::Serialize;
+
+/// Totals for a single simulation step.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StepReport {
+    step: u32,
+    generated: usize,
+    waiting: Vec<usize>,
+    on_board: Vec<usize>,
+    delivered: usize,
+    denied: usize,
+    rerouted: usize,
+    stranded: usize,
+    deadlocks: usize,
+    energy: Vec<Energy>,
+}
+
+impl StepReport {
+    #[must_use]
+    pub fn new(
+        step: u32,
+        generated: usize,
+        waiting: Vec<usize>,
+        on_board: Vec<usize>,
+        delivered: usize,
+    ) -> Self {
+        Self {
+            step,
+            generated,
+            waiting,
+            on_board,
+            delivered,
+            denied: 0,
+            rerouted: 0,
+            stranded: 0,
+            deadlocks: 0,
+            energy: Vec::new(),
+        }
+    }
+
+    /// Set the energy used by each line, indexed as the line list.
+    #[must_use]
+    pub fn set_energy(mut self, energy: Vec<Energy>) -> Self {
+        self.energy = energy;
+        self
+    }
+
+    /// Set the number of passengers left behind by full trains.
+    #[must_use]
+    pub fn set_denied(mut self, denied: usize) -> Self {
+        self.denied = denied;
+        self
+    }
+
+    /// Set the number of passengers sent on another route
+    /// and of those that left the network because of a closure.
+    #[must_use]
+    pub fn set_closure_effects(mut self, rerouted: usize, stranded: usize) -> Self {
+        self.rerouted = rerouted;
+        self.stranded = stranded;
+        self
+    }
+
+    /// Set the number of circular waits between trains
+    /// detected in the step.
+    #[must_use]
+    pub fn set_deadlocks(mut self, deadlocks: usize) -> Self {
+        self.deadlocks = deadlocks;
+        self
+    }
+
+    #[must_use]
+    pub fn get_step(&self) -> u32 {
+        self.step
+    }
+
+    /// Passengers generated during this step.
+    #[must_use]
+    pub fn get_generated(&self) -> usize {
+        self.generated
+    }
+
+    /// Passengers waiting in each station, indexed by station id.
+    #[must_use]
+    pub fn get_waiting(&self) -> &[usize] {
+        &self.waiting
+    }
+
+    /// Passengers on board of each line, indexed as the line list.
+    #[must_use]
+    pub fn get_on_board(&self) -> &[usize] {
+        &self.on_board
+    }
+
+    /// Passengers that reached their final destination during this step.
+    #[must_use]
+    pub fn get_delivered(&self) -> usize {
+        self.delivered
+    }
+
+    /// Passengers left behind by full trains during this step.
+    #[must_use]
+    pub fn get_denied(&self) -> usize {
+        self.denied
+    }
+
+    /// Passengers sent on another route by a closure during this step.
+    #[must_use]
+    pub fn get_rerouted(&self) -> usize {
+        self.rerouted
+    }
+
+    /// Passengers unable to continue their journey
+    /// because of a closure during this step.
+    #[must_use]
+    pub fn get_stranded(&self) -> usize {
+        self.stranded
+    }
+
+    /// Deadlocks between trains detected during this step.
+    #[must_use]
+    pub fn get_deadlocks(&self) -> usize {
+        self.deadlocks
+    }
+
+    /// Energy used by each line during this step, indexed as the line list.
+    #[must_use]
+    pub fn get_energy(&self) -> &[Energy] {
+        &self.energy
+    }
+
+    /// Energy used by all the lines during this step.
+    #[must_use]
+    pub fn total_energy(&self) -> Energy {
+        self.energy.iter().copied().sum()
+    }
+
+    /// Total number of passengers waiting in the network.
+    #[must_use]
+    pub fn total_waiting(&self) -> usize {
+        self.waiting.iter().sum()
+    }
+
+    /// Total number of passengers on board of any train.
+    #[must_use]
+    pub fn total_on_board(&self) -> usize {
+        self.on_board.iter().sum()
+    }
+}
+
+/// Aggregated values for a whole run.
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+pub struct RunSummary {
+    pub steps: usize,
+    pub generated: usize,
+    pub delivered: usize,
+    pub denied: usize,
+    pub rerouted: usize,
+    pub stranded: usize,
+    pub deadlocks: usize,
+    pub peak_waiting: usize,
+    pub peak_on_board: usize,
+    pub final_waiting: usize,
+    pub final_on_board: usize,
+    /// Traction energy in kWh.
+    pub traction: f64,
+    /// Energy recovered by regenerative braking in kWh.
+    pub regenerated: f64,
+}
+
+/// Step by step report of a simulation run.
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+pub struct SimulationReport {
+    steps: Vec<StepReport>,
+}
+
+impl SimulationReport {
+    /// Initialize an empty report, reserving space
+    /// for the expected number of steps.
+    #[must_use]
+    pub fn new(steps: usize) -> Self {
+        Self {
+            steps: Vec::with_capacity(steps),
+        }
+    }
+
+    /// Add the totals of the next step.
+    pub fn push(&mut self, step: StepReport) {
+        self.steps.push(step);
+    }
+
+    #[must_use]
+    pub fn get_steps(&self) -> &[StepReport] {
+        &self.steps
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Summarize the run.
+    #[must_use]
+    pub fn summary(&self) -> RunSummary {
+        let last = self.steps.last();
+        let energy: Energy = self.steps.iter().map(StepReport::total_energy).sum();
+        RunSummary {
+            steps: self.steps.len(),
+            generated: self.steps.iter().map(StepReport::get_generated).sum(),
+            delivered: self.steps.iter().map(StepReport::get_delivered).sum(),
+            denied: self.steps.iter().map(StepReport::get_denied).sum(),
+            rerouted: self.steps.iter().map(StepReport::get_rerouted).sum(),
+            stranded: self.steps.iter().map(StepReport::get_stranded).sum(),
+            deadlocks: self.steps.iter().map(StepReport::get_deadlocks).sum(),
+            peak_waiting: self.peak(StepReport::total_waiting),
+            peak_on_board: self.peak(StepReport::total_on_board),
+            final_waiting: last.map_or(0, StepReport::total_waiting),
+            final_on_board: last.map_or(0, StepReport::total_on_board),
+            traction: energy.get_traction(),
+            regenerated: energy.get_regenerated(),
+        }
+    }
+
+    /// Energy used by all the lines in each hour, the first
+    /// hour starting with the first step.
+    #[must_use]
+    pub fn energy_per_hour(&self, steps_per_hour: usize) -> Vec<Energy> {
+        self.steps
+            .chunks(steps_per_hour)
+            .map(|hour| hour.iter().map(StepReport::total_energy).sum())
+            .collect()
+    }
+
+    fn peak<F>(&self, f: F) -> usize
+    where
+        F: Fn(&StepReport) -> usize,
+    {
+        self.steps.iter().map(f).max().unwrap_or_default()
+    }
+}
+
+/// Energy used by a train during the run.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TrainEnergy {
+    pub line: usize,
+    pub car: usize,
+    pub class: Option<String>,
+    #[serde(flatten)]
+    pub energy: Energy,
+}
+
+/// Energy used during a run, per train, per line and per hour.
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+pub struct EnergyReport {
+    pub trains: Vec<TrainEnergy>,
+    pub lines: Vec<Energy>,
+    pub hours: Vec<Energy>,
+}
+
+impl EnergyReport {
+    #[must_use]
+    pub fn new<T>(lines: &[Line<T>], report: &SimulationReport, steps_per_hour: usize) -> Self
+    where
+        T: crate::passenger::callbacks::PassengerAction,
+    {
+        let trains = lines
+            .iter()
+            .enumerate()
+            .flat_map(|(line, l)| {
+                l.train_energy()
+                    .map(move |(car, class, energy)| TrainEnergy {
+                        line,
+                        car,
+                        class: class.map(String::from),
+                        energy,
+                    })
+            })
+            .collect();
+        Self {
+            trains,
+            lines: lines.iter().map(Line::get_energy).collect(),
+            hours: report.energy_per_hour(steps_per_hour),
+        }
+    }
+}
+
+/// A delay of a train of the given line.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LineDelay {
+    pub line: usize,
+    #[serde(flatten)]
+    pub delay: Delay,
+}
+
+/// Delays of all the lines, in line order.
+#[must_use]
+pub fn line_delays<T>(lines: &[Line<T>]) -> Vec<LineDelay>
+where
+    T: crate::passenger::callbacks::PassengerAction,
+{
+    lines
+        .iter()
+        .enumerate()
+        .flat_map(|(line, l)| {
+            l.get_delays().iter().map(move |delay| LineDelay {
+                line,
+                delay: *delay,
+            })
+        })
+        .collect()
+}
+
+/// A train of the given line standing at a platform.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LinePlatformStop {
+    pub line: usize,
+    #[serde(flatten)]
+    pub stop: PlatformStop,
+}
+
+/// Platforms taken by the trains of all the lines, in line order.
+#[must_use]
+pub fn line_platforms<T>(lines: &[Line<T>]) -> Vec<LinePlatformStop>
+where
+    T: crate::passenger::callbacks::PassengerAction,
+{
+    lines
+        .iter()
+        .enumerate()
+        .flat_map(|(line, l)| {
+            l.get_platform_log()
+                .iter()
+                .map(move |stop| LinePlatformStop { line, stop: *stop })
+        })
+        .collect()
+}
+
+/// Capacity of a line: the shortest headway in steps, the trains
+/// per hour it allows and the trains per hour that ran through the
+/// busiest segment.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LineCapacity {
+    pub line: usize,
+    pub min_headway: usize,
+    pub capacity: f64,
+    pub throughput: f64,
+}
+
+/// Capacity of all the lines, in line order.
+#[must_use]
+pub fn line_capacity<T>(lines: &[Line<T>], steps_per_hour: usize) -> Vec<LineCapacity>
+where
+    T: crate::passenger::callbacks::PassengerAction,
+{
+    lines
+        .iter()
+        .enumerate()
+        .map(|(line, l)| LineCapacity {
+            line,
+            min_headway: l.min_headway(),
+            capacity: l.capacity(steps_per_hour),
+            throughput: l.throughput(steps_per_hour),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        let mut report = SimulationReport::new(3);
+        report.push(StepReport::new(0, 10, vec![6, 4], vec![0], 0));
+        report.push(
+            StepReport::new(1, 5, vec![3, 2], vec![8], 2)
+                .set_denied(3)
+                .set_deadlocks(1),
+        );
+        report.push(StepReport::new(2, 0, vec![1, 0], vec![4], 7).set_closure_effects(2, 1));
+
+        let summary = report.summary();
+        assert_eq!(summary.steps, 3);
+        assert_eq!(summary.generated, 15);
+        assert_eq!(summary.delivered, 9);
+        assert_eq!(summary.denied, 3);
+        assert_eq!(summary.rerouted, 2);
+        assert_eq!(summary.stranded, 1);
+        assert_eq!(summary.deadlocks, 1);
+        assert_eq!(summary.peak_waiting, 10);
+        assert_eq!(summary.peak_on_board, 8);
+        assert_eq!(summary.final_waiting, 1);
+        assert_eq!(summary.final_on_board, 4);
+        assert_eq!(
+            summary.generated,
+            summary.delivered + summary.stranded + summary.final_waiting + summary.final_on_board
+        );
+    }
+
+    #[test]
+    fn test_energy_per_hour() {
+        let mut report = SimulationReport::new(3);
+        for step in 0..3 {
+            let energy = vec![Energy::new(2., 1.), Energy::new(1., 0.)];
+            report.push(StepReport::new(step, 0, vec![0], vec![0, 0], 0).set_energy(energy));
+        }
+        assert_eq!(
+            report.energy_per_hour(2),
+            [Energy::new(6., 2.), Energy::new(3., 1.)]
+        );
+        let summary = report.summary();
+        assert_eq!(summary.traction, 9.);
+        assert_eq!(summary.regenerated, 3.);
+    }
+
+    #[test]
+    fn test_empty_summary() {
+        let report = SimulationReport::default();
+        assert!(report.is_empty());
+        assert_eq!(report.summary(), RunSummary::default());
+    }
+}
@@ -0,0 +1,138 @@
+//! Rolling stock classes. A class describes the trains of a kind:
+//! how many passengers they carry, how fast they run and the
+//! parameters needed to estimate their energy consumption.
+
+type Duration = usize;
+
+/// A class of trains. The capacity is split between seats and
+/// standing room. Speeds are in meters per second, masses in tonnes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RollingStock {
+    name: String,
+    seated: usize,
+    standing: usize,
+    max_speed: Option<f64>,
+    mass: f64,
+    traction_efficiency: f64,
+    regen_efficiency: f64,
+}
+
+impl RollingStock {
+    /// A class without a top speed, the trains run at the speed the
+    /// segment times refer to. By default a train weighs 200 tonnes,
+    /// converts 85% of the energy into traction and recovers nothing
+    /// while braking.
+    #[must_use]
+    pub fn new(name: &str, seated: usize, standing: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            seated,
+            standing,
+            max_speed: None,
+            mass: 200.,
+            traction_efficiency: 0.85,
+            regen_efficiency: 0.,
+        }
+    }
+
+    #[must_use]
+    pub fn set_max_speed(mut self, speed: f64) -> Self {
+        assert!(speed > 0., "max speed must be positive");
+        self.max_speed = Some(speed);
+        self
+    }
+
+    /// Set the empty train mass, the share of energy converted
+    /// into traction and the share of the braking energy recovered.
+    #[must_use]
+    pub fn set_energy(
+        mut self,
+        mass: f64,
+        traction_efficiency: f64,
+        regen_efficiency: f64,
+    ) -> Self {
+        assert!(mass > 0., "mass must be positive");
+        assert!(
+            traction_efficiency > 0. && traction_efficiency <= 1.,
+            "traction efficiency must be in (0, 1]"
+        );
+        assert!(
+            (0. ..=1.).contains(&regen_efficiency),
+            "regenerative efficiency must be in [0, 1]"
+        );
+        self.mass = mass;
+        self.traction_efficiency = traction_efficiency;
+        self.regen_efficiency = regen_efficiency;
+        self
+    }
+
+    #[must_use]
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub fn get_seated(&self) -> usize {
+        self.seated
+    }
+
+    #[must_use]
+    pub fn get_standing(&self) -> usize {
+        self.standing
+    }
+
+    /// Maximal number of passengers on board.
+    #[must_use]
+    pub fn get_capacity(&self) -> usize {
+        self.seated + self.standing
+    }
+
+    #[must_use]
+    pub fn get_max_speed(&self) -> Option<f64> {
+        self.max_speed
+    }
+
+    #[must_use]
+    pub fn get_mass(&self) -> f64 {
+        self.mass
+    }
+
+    #[must_use]
+    pub fn get_traction_efficiency(&self) -> f64 {
+        self.traction_efficiency
+    }
+
+    #[must_use]
+    pub fn get_regen_efficiency(&self) -> f64 {
+        self.regen_efficiency
+    }
+
+    /// Time to run a segment crossed in the given time at the reference
+    /// speed. Trains slower than the reference take proportionally
+    /// longer, faster ones are still bound to the reference.
+    #[must_use]
+    pub fn running_time(&self, time: Duration, reference_speed: f64) -> Duration {
+        match self.max_speed {
+            Some(speed) if speed < reference_speed => {
+                (time as f64 * reference_speed / speed).ceil() as Duration
+            }
+            _ => time,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_running_time() {
+        let stock = RollingStock::new("slow", 40, 100).set_max_speed(15.);
+        assert_eq!(stock.get_capacity(), 140);
+        assert_eq!(stock.running_time(4, 20.), 6);
+        assert_eq!(stock.running_time(4, 10.), 4);
+        let stock = RollingStock::new("any", 40, 100);
+        assert_eq!(stock.running_time(4, 20.), 4);
+    }
+}
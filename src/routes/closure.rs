@@ -0,0 +1,267 @@
+//! Routing during planned closures. Closed stations and links are
+//! taken out of the network for the passengers: trains run through a
+//! closed station without stopping, so its neighbors on each line are
+//! linked directly, while a closed link cuts the lines running on it
+//! into sections served by trains turning back at the closure.
+
+use ndarray::Array2;
+use num_traits::PrimInt;
+
+use super::build_directions::build_directions_from_lines;
+use super::matrix_wrapper::{MetroDirection, MetroInterchange};
+use super::metro_lines::MetroLines;
+
+/// Routing matrices in use while some stations and links are
+/// closed, from the start step up to the end one, excluded.
+pub struct ClosurePeriod {
+    start: usize,
+    end: usize,
+    stations: Vec<usize>,
+    direction: MetroDirection,
+    interchange: MetroInterchange,
+}
+
+impl ClosurePeriod {
+    /// Build the routing of the given network and lines without
+    /// the closed stations and links. Lines are given as in
+    /// [`build_directions_from_lines`].
+    #[must_use]
+    pub fn new<T: PrimInt + Default>(
+        (start, end): (usize, usize),
+        adj_mat: Array2<T>,
+        metro_lines: &MetroLines<'_>,
+        stations: Vec<usize>,
+        links: &[(usize, usize)],
+    ) -> Self {
+        let closed = Closed {
+            stations: &stations,
+            links,
+        };
+        let (adj_mat, sections) = closed.cut_network(adj_mat, metro_lines);
+        let terminus: Vec<(usize, usize)> = sections.iter().map(|s| s.terminus).collect();
+        let lines = sections.iter().map(|s| s.stations.clone()).collect();
+        let open_lines = sections.iter().enumerate().fold(
+            MetroLines::from_given_lines(lines, &terminus),
+            |lines, (i, section)| match (section.ring, section.cut) {
+                (true, true) => lines.set_loop(i).set_section(i),
+                (true, false) => lines.set_loop(i),
+                (false, _) => lines.set_section(i),
+            },
+        );
+        let (direction, interchange) = build_directions_from_lines(adj_mat, &open_lines);
+        Self {
+            start,
+            end,
+            stations,
+            direction,
+            interchange,
+        }
+    }
+
+    /// Check if the routing is in use at the given step.
+    #[must_use]
+    pub fn is_active(&self, step: usize) -> bool {
+        (self.start..self.end).contains(&step)
+    }
+
+    #[must_use]
+    pub fn get_start(&self) -> usize {
+        self.start
+    }
+
+    #[must_use]
+    pub fn get_end(&self) -> usize {
+        self.end
+    }
+
+    /// Check if the given station is closed.
+    #[must_use]
+    pub fn is_closed(&self, station: usize) -> bool {
+        self.stations.contains(&station)
+    }
+
+    #[must_use]
+    pub fn get_direction(&self) -> &MetroDirection {
+        &self.direction
+    }
+
+    #[must_use]
+    pub fn get_interchange(&self) -> &MetroInterchange {
+        &self.interchange
+    }
+}
+
+/// Part of a line left open by the closures.
+struct Section {
+    stations: Vec<usize>,
+    terminus: (usize, usize),
+    ring: bool,
+    cut: bool,
+}
+
+struct Closed<'a> {
+    stations: &'a [usize],
+    links: &'a [(usize, usize)],
+}
+
+impl<'a> Closed<'a> {
+    fn is_link_closed(&self, a: usize, b: usize) -> bool {
+        self.links.contains(&(a, b)) || self.links.contains(&(b, a))
+    }
+
+    /// Remove the closed stations and links from the adjacent matrix,
+    /// linking the stations on the two sides of a closed one, and
+    /// split the lines into the sections left open.
+    fn cut_network<T: PrimInt>(
+        &self,
+        mut adj_mat: Array2<T>,
+        metro_lines: &MetroLines<'_>,
+    ) -> (Array2<T>, Vec<Section>) {
+        let mut bypasses = vec![];
+        let mut sections = vec![];
+        let terminus = metro_lines.get_terminus();
+        for (i, line) in metro_lines.line_iter().enumerate() {
+            let ring = metro_lines.is_loop(i);
+            let mut path = line.to_vec();
+            if ring {
+                // Start right after a closed link, if any, so that the
+                // loop is cut at the end of the path, or else from an
+                // open station, repeated at the end to close the loop.
+                let n = path.len();
+                let first = (0..n)
+                    .find(|k| self.is_link_closed(path[*k], path[(k + 1) % n]))
+                    .map(|k| k + 1)
+                    .or_else(|| path.iter().position(|s| !self.stations.contains(s)))
+                    .unwrap_or_default();
+                path.rotate_left(first % n);
+                path.push(path[0]);
+            }
+            let mut cut = false;
+            let mut pieces: Vec<Vec<usize>> = vec![vec![]];
+            let mut last: Option<(usize, T)> = None;
+            for (k, station) in path.iter().enumerate() {
+                if k > 0 {
+                    let prev = path[k - 1];
+                    if self.is_link_closed(prev, *station) {
+                        cut = true;
+                        pieces.push(vec![]);
+                        last = None;
+                    } else if let Some((open, weight)) = last {
+                        last = Some((open, weight + adj_mat[(prev, *station)]));
+                    }
+                }
+                if self.stations.contains(station) {
+                    continue;
+                }
+                if let Some((open, weight)) = last {
+                    if adj_mat[(open, *station)] != weight {
+                        bypasses.push((open, *station, weight));
+                    }
+                }
+                pieces.last_mut().unwrap().push(*station);
+                last = Some((*station, T::zero()));
+            }
+            if ring {
+                let first = pieces.first_mut().unwrap();
+                if !cut {
+                    // The loop is whole: drop the repeated first station.
+                    first.pop();
+                }
+            }
+            for stations in pieces.into_iter().filter(|p| p.len() >= 2) {
+                let terminus = if ring {
+                    (stations[0], *stations.last().unwrap())
+                } else {
+                    terminus[i]
+                };
+                sections.push(Section {
+                    stations,
+                    terminus,
+                    ring,
+                    cut,
+                });
+            }
+        }
+        for (a, b, weight) in bypasses {
+            let w = adj_mat[(a, b)].min(weight);
+            adj_mat[(a, b)] = w;
+            adj_mat[(b, a)] = w;
+        }
+        for (a, b) in self.links {
+            adj_mat[(*a, *b)] = T::max_value();
+            adj_mat[(*b, *a)] = T::max_value();
+        }
+        for station in self.stations {
+            adj_mat.row_mut(*station).fill(T::max_value());
+            adj_mat.column_mut(*station).fill(T::max_value());
+            adj_mat[(*station, *station)] = T::zero();
+        }
+        (adj_mat, sections)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    // Line [0, 1, 2, 3] crossed at 2 by line [4, 2, 5].
+    fn make_network() -> Array2<u32> {
+        let mut adj = Array2::from_elem((6, 6), u32::MAX);
+        adj.diag_mut().fill(0);
+        for (a, b) in [(0, 1), (1, 2), (2, 3), (4, 2), (2, 5)] {
+            adj[(a, b)] = 1;
+            adj[(b, a)] = 1;
+        }
+        adj
+    }
+
+    #[test]
+    fn test_closed_station() {
+        let terminus = [(0, 3), (4, 5)];
+        let lines = MetroLines::from_given_lines(vec![vec![0, 1, 2, 3], vec![4, 2, 5]], &terminus);
+        let period = ClosurePeriod::new((10, 20), make_network(), &lines, vec![1], &[]);
+        assert!(period.is_active(10) && !period.is_active(20));
+        assert!(period.is_closed(1));
+        // trains run through station 1
+        assert_eq!(period.get_interchange().next_station(0, 2), 2);
+        assert_eq!(period.get_direction().get_direction(0, 2), 3);
+        assert_eq!(period.get_interchange().next_station(0, 4), 2);
+        // station 1 cannot be reached
+        assert_eq!(period.get_interchange().next_station(0, 1), 0);
+        assert_eq!(period.get_interchange().next_station(4, 1), 4);
+    }
+
+    #[test]
+    fn test_closed_link() {
+        let terminus = [(0, 3), (4, 5)];
+        let lines = MetroLines::from_given_lines(vec![vec![0, 1, 2, 3], vec![4, 2, 5]], &terminus);
+        let period = ClosurePeriod::new((0, 5), make_network(), &lines, vec![], &[(2, 1)]);
+        // the two sections keep the directions of the whole line
+        assert_eq!(period.get_direction().get_direction(1, 0), 0);
+        assert_eq!(period.get_direction().get_direction(0, 1), 3);
+        assert_eq!(period.get_direction().get_direction(3, 2), 0);
+        assert_eq!(period.get_interchange().next_station(3, 4), 2);
+        // nothing links the two sections
+        assert_eq!(period.get_interchange().next_station(0, 3), 0);
+        assert_eq!(period.get_interchange().next_station(5, 1), 5);
+    }
+
+    #[test]
+    fn test_closed_loop_link() {
+        let mut adj = Array2::from_elem((4, 4), u32::MAX);
+        adj.diag_mut().fill(0);
+        for (a, b) in [(0, 1), (1, 2), (2, 3), (3, 0)] {
+            adj[(a, b)] = 1;
+            adj[(b, a)] = 1;
+        }
+        let terminus = [(0, 3)];
+        let lines = MetroLines::from_given_lines(vec![vec![0, 1, 2, 3]], &terminus).set_loop(0);
+        let period = ClosurePeriod::new((0, 5), adj, &lines, vec![], &[(1, 2)]);
+        // the loop becomes the section [2, 3, 0, 1]
+        assert_eq!(period.get_direction().get_direction(1, 2), 0);
+        assert_eq!(period.get_direction().get_direction(2, 1), 3);
+        assert_eq!(period.get_direction().get_direction(0, 3), 3);
+        assert_eq!(period.get_interchange().next_station(1, 2), 2);
+    }
+}
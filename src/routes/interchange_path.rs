@@ -12,21 +12,32 @@ use super::metro_line_set;
 use super::path_iterator::PathIterator;
 use super::Mat;
 use crate::utils::matrix_utils;
+use ndarray::Array2;
+use num_traits::PrimInt;
 use std::collections::HashSet;
 
 /// Build the interchange path matrix starting
-/// from the given successor matrix, the terminus list (to identify the lines)
+/// from the given successor matrix, the distance matrix, the terminus list (to identify the lines)
 /// and the interchanges list.
 /// For the implementation the order of the interchanges, order of terminus and relative
 /// order in station in the terminus couple is irrelevant.
-pub fn build_interchange_path_matrix(next: &Mat, lines: &metro_line_set::MetroLinesSet) -> Mat {
+/// When the destination cannot be reached the next station is the start itself.
+pub fn build_interchange_path_matrix<T: PrimInt>(
+    next: &Mat,
+    dist: &Array2<T>,
+    lines: &metro_line_set::MetroLinesSet,
+) -> Mat {
     let interchanges = lines.find_interchanges();
     let mut output = matrix_utils::zeros_as(next);
 
     for s in 0..next.nrows() {
         for e in 0..next.nrows() {
-            let n = if lines.is_same_line(s, e) {
+            // A station cut off all lines by a closure is on
+            // no line, not even with itself.
+            let n = if s == e || lines.is_same_line(s, e) {
                 e
+            } else if dist[(s, e)] == T::max_value() {
+                s
             } else {
                 take_next(s, e, next, &interchanges)
             };
@@ -37,9 +48,12 @@ pub fn build_interchange_path_matrix(next: &Mat, lines: &metro_line_set::MetroLi
     output
 }
 
-/// Identify the fist interchange node in path from node start to node end.
+/// Identify the fist interchange node in path from node start to node end,
+/// start excluded: the start marks an unreachable destination, so a
+/// passenger in an interchange must be sent to the next one.
 fn take_next(start: usize, end: usize, next: &Mat, interchanges: &HashSet<usize>) -> usize {
     PathIterator::new(start, end, next)
+        .skip(1)
         .find(|n| interchanges.contains(n))
         .unwrap()
 }
@@ -58,8 +72,40 @@ mod test {
         let lines = metro_lines::MetroLines::from_successor_matrix(&next, &terminus);
         let lines = metro_line_set::MetroLinesSet::from(&lines);
 
-        let ipm = build_interchange_path_matrix(&next, &lines);
+        let dist = test_definitions::make_dist_matrix();
+        let ipm = build_interchange_path_matrix(&next, &dist, &lines);
         let correct = test_definitions::make_correct_interchange_path();
         assert_eq!(ipm, correct);
     }
+
+    // Lines [0, 1, 2], [3, 1, 4] and [5, 4, 6] in a chain,
+    // station 7 is on no line.
+    fn make_chain() -> (Mat, Array2<u32>) {
+        let mut adj = Array2::from_elem((8, 8), u32::MAX);
+        adj.diag_mut().fill(0);
+        for (a, b) in [(0, 1), (1, 2), (3, 1), (1, 4), (5, 4), (4, 6)] {
+            adj[(a, b)] = 1;
+            adj[(b, a)] = 1;
+        }
+        let (dist, next) = all_shortest_path::all_shortest_path(adj);
+        (next, dist)
+    }
+
+    #[test]
+    fn test_interchange_from_interchange() {
+        let (next, dist) = make_chain();
+        let terminus = [(0, 2), (3, 4), (5, 6)];
+        let lines = vec![vec![0, 1, 2], vec![3, 1, 4], vec![5, 4, 6]];
+        let lines = metro_lines::MetroLines::from_given_lines(lines, &terminus);
+        let lines = metro_line_set::MetroLinesSet::from(&lines);
+
+        let ipm = build_interchange_path_matrix(&next, &dist, &lines);
+        // from the interchange 1 the next one is 4, not 1 itself
+        assert_eq!(ipm[(1, 5)], 4);
+        assert_eq!(ipm[(0, 6)], 1);
+        assert_eq!(ipm[(4, 0)], 1);
+        // a station on no line can only stay where it is
+        assert_eq!(ipm[(7, 7)], 7);
+        assert_eq!(ipm[(7, 0)], 7);
+    }
 }
@@ -1,5 +1,4 @@
-//!
-//!
+//! Wrappers around the routing matrices used by the passengers.
 
 use super::Mat;
 use ndarray::Array2;
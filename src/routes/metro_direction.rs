@@ -44,7 +44,14 @@ fn set_in_line_directions<T: PrimInt>(
     for line in lines.line_iterator() {
         for s1 in line.stations {
             for s2 in line.stations {
-                let dir = find_closer(dist, line.terminus.0, line.terminus.1, *s1, *s2);
+                let dir = match (line.section, line.ring) {
+                    (Some(section), Some(_)) => find_closer_on_section(section, None, *s1, *s2),
+                    (Some(section), None) => {
+                        find_closer_on_section(section, Some(line.terminus), *s1, *s2)
+                    }
+                    (None, Some(ring)) => find_closer_on_loop(ring, *s1, *s2),
+                    (None, None) => find_closer(dist, line.terminus.0, line.terminus.1, *s1, *s2),
+                };
                 dir_mat[(*s1, *s2)] = dir;
             }
         }
@@ -92,6 +99,53 @@ fn find_closer<T: PrimInt>(
     }
 }
 
+/// Same as [`find_closer`] for loop lines, given the stations in
+/// order around the loop. The passenger follows the way with fewer
+/// stations, clockwise on a tie, and the direction is the next
+/// station on that way.
+fn find_closer_on_loop(ring: &[usize], start: usize, dest: usize) -> usize {
+    if start == dest {
+        return start;
+    }
+    let n = ring.len();
+    let position = |s| ring.iter().position(|r| *r == s).unwrap();
+    let (i, j) = (position(start), position(dest));
+    let forward = (j + n - i) % n;
+    if forward <= n - forward {
+        ring[(i + 1) % n]
+    } else {
+        ring[(i + n - 1) % n]
+    }
+}
+
+/// Same as [`find_closer`] for a section of a line, given its
+/// stations in order and the terminus couple of the whole line. The
+/// direction follows the order of the stations. Without terminus,
+/// on a section of a loop, the direction is the next station.
+fn find_closer_on_section(
+    section: &[usize],
+    terminus: Option<(usize, usize)>,
+    start: usize,
+    dest: usize,
+) -> usize {
+    if start == dest {
+        return start;
+    }
+    let position = |s| section.iter().position(|r| *r == s).unwrap();
+    let (i, j) = (position(start), position(dest));
+    match terminus {
+        Some((t1, t2)) => {
+            if j > i {
+                t2
+            } else {
+                t1
+            }
+        }
+        None if j > i => section[i + 1],
+        None => section[i - 1],
+    }
+}
+
 /// This function identifies the direction the passenger must
 /// follow to reach from station start (that is on one line) station
 /// dst that is on another line. The direction set by this function is
@@ -136,4 +190,25 @@ mod test {
         let direction = build_metro_direction(&next, &dist, &lines, &interchange_path);
         assert_eq!(direction, expected_direction);
     }
+
+    #[test]
+    fn test_loop_direction() {
+        let ring = [3, 0, 4, 1, 2];
+        assert_eq!(find_closer_on_loop(&ring, 0, 4), 4);
+        assert_eq!(find_closer_on_loop(&ring, 0, 1), 4);
+        assert_eq!(find_closer_on_loop(&ring, 0, 2), 3);
+        assert_eq!(find_closer_on_loop(&ring, 3, 2), 2);
+        assert_eq!(find_closer_on_loop(&ring, 2, 0), 3);
+        assert_eq!(find_closer_on_loop(&ring, 1, 1), 1);
+    }
+
+    #[test]
+    fn test_section_direction() {
+        let section = [2, 3, 4];
+        assert_eq!(find_closer_on_section(&section, Some((0, 5)), 2, 4), 5);
+        assert_eq!(find_closer_on_section(&section, Some((0, 5)), 4, 3), 0);
+        assert_eq!(find_closer_on_section(&section, None, 2, 4), 3);
+        assert_eq!(find_closer_on_section(&section, None, 4, 2), 3);
+        assert_eq!(find_closer_on_section(&section, None, 3, 3), 3);
+    }
 }
@@ -25,6 +25,8 @@ type Set<'a> = &'a Line;
 pub struct MetroLinesSet<'a> {
     terminus: &'a [(usize, usize)],
     lines: Vec<Line>,
+    rings: Vec<Option<&'a [usize]>>,
+    sections: Vec<Option<&'a [usize]>>,
 }
 
 impl<'a> MetroLinesSet<'a> {
@@ -45,7 +47,13 @@ impl<'a> MetroLinesSet<'a> {
         self.terminus
             .iter()
             .zip(self.lines.iter())
-            .map(LineItem::from_tuple)
+            .zip(self.rings.iter())
+            .zip(self.sections.iter())
+            .map(|((t, ring), section)| {
+                LineItem::from_tuple(t)
+                    .set_ring(*ring)
+                    .set_section(*section)
+            })
     }
 
     /// Iterate though all the unique couple of lines. Unique means that
@@ -68,14 +76,28 @@ impl<'a, 'b: 'a> From<&'b MetroLines<'a>> for MetroLinesSet<'b> {
     /// Initialize Line data structure. Start from the successor matrix
     /// and the terminus list. Order inside the list and order between station is
     /// irrelevant.
-
     fn from(metro_lines: &'b MetroLines<'a>) -> Self {
         let lines = metro_lines
             .line_iter()
             .map(|line| line.iter().copied().collect())
             .collect();
+        let rings = metro_lines
+            .line_iter()
+            .enumerate()
+            .map(|(i, line)| metro_lines.is_loop(i).then_some(line))
+            .collect();
+        let sections = metro_lines
+            .line_iter()
+            .enumerate()
+            .map(|(i, line)| metro_lines.is_section(i).then_some(line))
+            .collect();
         let terminus = metro_lines.get_terminus();
-        Self { terminus, lines }
+        Self {
+            terminus,
+            lines,
+            rings,
+            sections,
+        }
     }
 }
 
@@ -85,6 +107,10 @@ impl<'a, 'b: 'a> From<&'b MetroLines<'a>> for MetroLinesSet<'b> {
 pub struct LineItem<'a> {
     pub terminus: (usize, usize),
     pub stations: Set<'a>,
+    /// Stations in order around the loop, for loop lines.
+    pub ring: Option<&'a [usize]>,
+    /// Stations in order, for sections of a longer line.
+    pub section: Option<&'a [usize]>,
 }
 
 impl<'a> LineItem<'a> {
@@ -93,7 +119,22 @@ impl<'a> LineItem<'a> {
     fn from_tuple(t: (&(usize, usize), &'a HashSet<usize>)) -> Self {
         let ((t1, t2), stations) = t;
         let terminus = (*t1, *t2);
-        Self { terminus, stations }
+        Self {
+            terminus,
+            stations,
+            ring: None,
+            section: None,
+        }
+    }
+
+    fn set_ring(mut self, ring: Option<&'a [usize]>) -> Self {
+        self.ring = ring;
+        self
+    }
+
+    fn set_section(mut self, section: Option<&'a [usize]>) -> Self {
+        self.section = section;
+        self
     }
 }
 
@@ -12,6 +12,8 @@ use super::Mat;
 pub struct MetroLines<'a> {
     lines: Vec<Vec<usize>>,
     terminus: &'a [(usize, usize)],
+    loops: Vec<bool>,
+    sections: Vec<bool>,
 }
 
 impl<'a> MetroLines<'a> {
@@ -26,14 +28,63 @@ impl<'a> MetroLines<'a> {
             .iter()
             .map(|(t1, t2)| PathIterator::new(*t1, *t2, next).into_vector())
             .collect();
-        Self { lines, terminus }
+        let loops = vec![false; terminus.len()];
+        let sections = loops.clone();
+        Self {
+            lines,
+            terminus,
+            loops,
+            sections,
+        }
     }
 
     /// Build object from pre-build lines. Useful if the lines cannot be generated
     /// automatically from the shortest path.
     #[must_use]
     pub fn from_given_lines(lines: Vec<Vec<usize>>, terminus: &'a [(usize, usize)]) -> Self {
-        Self { lines, terminus }
+        let loops = vec![false; lines.len()];
+        let sections = loops.clone();
+        Self {
+            lines,
+            terminus,
+            loops,
+            sections,
+        }
+    }
+
+    /// Mark the given line as a loop: its last station is linked
+    /// to the first one and trains run around it in both directions,
+    /// without termini. The direction of a train on a loop is
+    /// identified by the next station it stops at, so the
+    /// terminus couple of a loop line is ignored. Loops can only
+    /// be given, they are never built from the successor matrix.
+    #[must_use]
+    pub fn set_loop(mut self, line: usize) -> Self {
+        self.loops[line] = true;
+        self
+    }
+
+    /// Check if the given line is a loop.
+    #[must_use]
+    pub fn is_loop(&self, line: usize) -> bool {
+        self.loops[line]
+    }
+
+    /// Mark the given line as a section of a longer line, left
+    /// open by a closure. Its stations are given in order and its
+    /// terminus couple is the one of the whole line, the direction
+    /// toward each station follows that order. On a section of a
+    /// loop, set as a loop too, the direction is the next station.
+    #[must_use]
+    pub fn set_section(mut self, line: usize) -> Self {
+        self.sections[line] = true;
+        self
+    }
+
+    /// Check if the given line is a section of a longer line.
+    #[must_use]
+    pub fn is_section(&self, line: usize) -> bool {
+        self.sections[line]
     }
 
     /// Return an iterator implementation over
@@ -41,7 +92,7 @@ impl<'a> MetroLines<'a> {
     /// of the given terminus line on object construction.
     /// The path for the line defined from terminus (T1, T2) is
     /// always the path from T1 to T2.
-    pub fn line_iter(&'a self) -> impl Iterator<Item = &[usize]> + 'a {
+    pub fn line_iter(&'a self) -> impl Iterator<Item = &'a [usize]> + 'a {
         self.lines.iter().map(Vec::as_slice)
     }
 
@@ -24,6 +24,7 @@ type Mat = Array2<usize>;
 
 mod build_directions;
 mod build_matrices;
+mod closure;
 mod interchange_path;
 mod matrix_wrapper;
 mod metro_direction;
@@ -32,6 +33,7 @@ mod metro_lines;
 mod path_iterator;
 
 pub use build_directions::{build_directions, build_directions_from_lines};
+pub use closure::ClosurePeriod;
 pub use matrix_wrapper::{MetroDirection, MetroInterchange};
 pub use metro_lines::MetroLines;
 
@@ -0,0 +1,176 @@
+//! Errors reported while loading or validating a scenario.
+
+use std::fmt;
+
+/// Something wrong in a scenario file. Each variant
+/// carries enough context to locate the problem in the file.
+#[derive(Debug)]
+pub enum ScenarioError {
+    /// The file could not be read.
+    Io(std::io::Error),
+    /// The file extension is neither toml nor json.
+    UnknownFormat(String),
+    /// The file is not valid TOML or JSON, or does not
+    /// follow the scenario structure.
+    Parse(String),
+    /// ``time.end`` is not after ``time.begin`` or the resolution is zero.
+    InvalidTime {
+        begin: u32,
+        end: u32,
+        resolution: u32,
+    },
+    /// The network has no stations.
+    EmptyNetwork,
+    /// The number of station names differs from the number of stations.
+    StationNames { expected: usize, found: usize },
+    /// A station index is out of range.
+    UnknownStation { context: String, station: usize },
+    /// A link has zero weight or connects a station to itself.
+    InvalidLink { index: usize },
+    /// A line has less than two stations.
+    LineTooShort { line: usize },
+    /// A station appears twice in the same line.
+    DuplicateStation { line: usize, station: usize },
+    /// Two consecutive stations of a line are not linked in the network.
+    NotAdjacent { line: usize, from: usize, to: usize },
+    /// The number of segments does not match the number of stations.
+    SegmentCount {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// A line parameter is invalid.
+    InvalidLine { line: usize, reason: String },
+    /// A rolling stock class parameter is invalid.
+    InvalidRollingStock { class: String, reason: String },
+    /// The traffic matrix is not a square matrix of station count size.
+    TrafficShape {
+        expected: usize,
+        row: usize,
+        found: usize,
+    },
+    /// A station with traffic has a non zero entry toward itself.
+    SelfTraffic { station: usize },
+    /// The traffic levels are not finite and positive
+    /// or min is greater than max.
+    InvalidLevels { min: f64, max: f64 },
+    /// An anchor is outside the simulated time or
+    /// there are more anchors than simulated hours.
+    InvalidAnchor { index: usize },
+    /// A closure is not on the network lines or outside the simulated time.
+    InvalidClosure { index: usize, reason: String },
+    /// A station is not served by any line.
+    UnservedStation { station: usize },
+    /// A station cannot be reached from the first station using the lines.
+    Disconnected { station: usize },
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "cannot read scenario: {err}"),
+            Self::UnknownFormat(ext) => write!(
+                f,
+                "unknown scenario format '{ext}': expected a .toml or .json file"
+            ),
+            Self::Parse(msg) => write!(f, "cannot parse scenario: {msg}"),
+            Self::InvalidTime {
+                begin,
+                end,
+                resolution,
+            } => write!(
+                f,
+                "time: end ({end}) must be after begin ({begin}) and resolution ({resolution}) must be positive"
+            ),
+            Self::EmptyNetwork => write!(f, "network: at least one station is required"),
+            Self::StationNames { expected, found } => write!(
+                f,
+                "network: {found} station names given for {expected} stations"
+            ),
+            Self::UnknownStation { context, station } => {
+                write!(f, "{context}: unknown station {station}")
+            }
+            Self::InvalidLink { index } => write!(
+                f,
+                "network: link {index} must connect two different stations with a positive weight"
+            ),
+            Self::LineTooShort { line } => {
+                write!(f, "line {line}: at least two stations are required")
+            }
+            Self::DuplicateStation { line, station } => {
+                write!(f, "line {line}: station {station} appears more than once")
+            }
+            Self::NotAdjacent { line, from, to } => write!(
+                f,
+                "line {line}: stations {from} and {to} are not linked in the network"
+            ),
+            Self::SegmentCount {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {line}: expected {expected} segment times, found {found}"
+            ),
+            Self::InvalidLine { line, reason } => write!(f, "line {line}: {reason}"),
+            Self::InvalidRollingStock { class, reason } => {
+                write!(f, "rolling stock {class}: {reason}")
+            }
+            Self::TrafficShape {
+                expected,
+                row,
+                found,
+            } => write!(
+                f,
+                "traffic: row {row} has {found} entries, expected {expected}"
+            ),
+            Self::SelfTraffic { station } => write!(
+                f,
+                "traffic: station {station} cannot have traffic toward itself"
+            ),
+            Self::InvalidLevels { min, max } => write!(
+                f,
+                "traffic: levels must be finite and positive with min ({min}) not greater than max ({max})"
+            ),
+            Self::InvalidAnchor { index } => write!(
+                f,
+                "traffic: anchor {index} is outside the simulated time or there are too many anchors"
+            ),
+            Self::InvalidClosure { index, reason } => write!(f, "closure {index}: {reason}"),
+            Self::UnservedStation { station } => {
+                write!(f, "network: station {station} is not served by any line")
+            }
+            Self::Disconnected { station } => write!(
+                f,
+                "network: station {station} cannot be reached from station 0 using the lines"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ScenarioError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for ScenarioError {
+    fn from(err: toml::de::Error) -> Self {
+        Self::Parse(err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for ScenarioError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Parse(err.to_string())
+    }
+}
@@ -0,0 +1,1893 @@
+//! Load a whole simulation from a scenario file. A scenario describes
+//! the simulated time, the network (stations and links), the lines
+//! and the traffic matrix. It can be written in TOML or JSON:
+//!
+//! ```toml
+//! seed = 42
+//!
+//! [time]
+//! begin = 5
+//! end = 22
+//! resolution = 2
+//!
+//! [network]
+//! stations = 3
+//! names = ["West", "Center", "East"]
+//! links = [[0, 1, 1], [1, 2, 1]]
+//!
+//! [[lines]]
+//! stations = [0, 1, 2]
+//! station_time = 5
+//! segment_times = [6, 6]
+//! split_len = 7
+//! depo_size = 4
+//! train_delay = 11
+//!
+//! [traffic]
+//! matrix = [[0, 10, 5], [5, 0, 12], [5, 11, 0]]
+//! levels = [1.0, 10.0]
+//! ```
+//!
+//! Loading a scenario validates it, so that a wrong file is reported
+//! with a clear ``ScenarioError`` instead of a panic during the run.
+
+mod error;
+
+pub use error::ScenarioError;
+
+use crate::energy::EnergyModel;
+use crate::line::deadlock::DeadlockPolicy;
+use crate::line::dispatch::{Dispatch, HeadwayProfile, Service, ServiceSpan, Timetable};
+use crate::line::disruption::{BlockTarget, FailureModel, SegmentBlock};
+use crate::line::kinematics::Kinematics;
+use crate::line::line_factory::LineOptions;
+use crate::line::service::ServicePattern;
+use crate::line::signalling::Signalling;
+use crate::line::{fast_line_factory, Line, LineDirection};
+use crate::passenger::{callbacks, PassengerFactory};
+use crate::rolling_stock::RollingStock;
+use crate::routes::{self, ClosurePeriod, MetroDirection, MetroInterchange, MetroLines};
+use crate::station::{self, Station};
+use crate::traffic_generator::{self, SimpleTrafficGenerator};
+use crate::{Int, Node};
+
+use ndarray::Array2;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// A whole simulation scenario.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// Seed for the traffic generation and the simulation.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    pub time: TimeConfig,
+    pub network: NetworkConfig,
+    pub lines: Vec<LineConfig>,
+    /// Rolling stock classes the line fleets are made of.
+    #[serde(default)]
+    pub rolling_stock: Vec<RollingStockConfig>,
+    /// Planned closures of stations and links.
+    #[serde(default)]
+    pub closures: Vec<ClosureConfig>,
+    pub traffic: TrafficConfig,
+}
+
+/// Simulated time: first and last hour of the day
+/// and number of steps per minute.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TimeConfig {
+    pub begin: Int,
+    pub end: Int,
+    pub resolution: Int,
+}
+
+/// Network stations and the links between them. Each link is
+/// the tuple (station, station, weight) and it is undirected.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct NetworkConfig {
+    pub stations: usize,
+    #[serde(default)]
+    pub names: Vec<String>,
+    pub links: Vec<(usize, usize, Int)>,
+}
+
+/// A metro line. The parameters follow
+/// [`crate::line::fast_line_factory::FastLineFactoryConfig`].
+/// A circular line links its last station back to the first one,
+/// with one more segment time; trains of both directions start
+/// from the first station, those following ``from_first`` in
+/// the order of the stations. With kinematics the segment times
+/// are derived from ``segment_lengths``, in place of
+/// ``segment_times``, and ``split_len`` is a length.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct LineConfig {
+    pub stations: Vec<usize>,
+    pub station_time: usize,
+    #[serde(default)]
+    pub segment_times: Vec<usize>,
+    #[serde(default)]
+    pub segment_lengths: Vec<usize>,
+    pub split_len: usize,
+    pub depo_size: usize,
+    pub train_delay: usize,
+    #[serde(default)]
+    pub train_capacity: Option<usize>,
+    /// Passengers boarding or alighting a train in a step,
+    /// when set dwell times grow with the load.
+    #[serde(default)]
+    pub door_throughput: Option<usize>,
+    #[serde(default)]
+    pub timetable: Option<TimetableConfig>,
+    #[serde(default)]
+    pub service: Option<ServiceConfig>,
+    #[serde(default)]
+    pub patterns: Vec<PatternConfig>,
+    #[serde(default)]
+    pub branches: Vec<BranchConfig>,
+    #[serde(default)]
+    pub circular: bool,
+    #[serde(default)]
+    pub kinematics: Option<KinematicsConfig>,
+    /// Names of the rolling stock classes taken in turn by the
+    /// trains of the line. The class capacity replaces the train
+    /// capacity.
+    #[serde(default)]
+    pub fleet: Vec<String>,
+    #[serde(default)]
+    pub energy: Option<EnergyModelConfig>,
+    #[serde(default)]
+    pub failures: Option<FailureConfig>,
+    #[serde(default)]
+    pub blocks: Vec<BlockConfig>,
+    /// Keep trains in passing loops and stations until they can
+    /// clear the single track ahead, instead of stopping the run
+    /// when trains wait for each other.
+    #[serde(default)]
+    pub passing_loop_priority: bool,
+    /// Separation of the trains between stations, each segment
+    /// is a single block by default.
+    #[serde(default)]
+    pub signalling: Option<SignallingConfig>,
+    #[serde(default)]
+    pub platforms: Vec<PlatformConfig>,
+}
+
+impl LineConfig {
+    /// Stations served by the given branch, from the first station of
+    /// the line to the branch end. Branch 0 is the main line.
+    #[must_use]
+    pub fn branch_stations(&self, branch: usize) -> Option<Vec<usize>> {
+        if branch == 0 {
+            return Some(self.stations.clone());
+        }
+        let config = self.branches.get(branch - 1)?;
+        let junction = self.stations.iter().position(|s| *s == config.junction)?;
+        let mut stations = self.stations[..=junction].to_vec();
+        stations.extend(&config.stations);
+        Some(stations)
+    }
+
+    /// Stations served by each branch, main line first.
+    fn paths(&self) -> impl Iterator<Item = Vec<usize>> + '_ {
+        (0..=self.branches.len()).filter_map(|b| self.branch_stations(b))
+    }
+
+    /// Couples of consecutive stations, on all the branches.
+    fn links(&self) -> Vec<(usize, usize)> {
+        let mut links: Vec<(usize, usize)> = self
+            .paths()
+            .flat_map(|path| path.windows(2).map(|w| (w[0], w[1])).collect::<Vec<_>>())
+            .collect();
+        if self.circular {
+            links.push((*self.stations.last().unwrap(), self.stations[0]));
+        }
+        links
+    }
+
+    /// Segment times, or segment lengths with kinematics.
+    fn segments(&self) -> &[usize] {
+        if self.kinematics.is_some() {
+            &self.segment_lengths
+        } else {
+            &self.segment_times
+        }
+    }
+}
+
+/// A branch leaving the line after the junction station. The first
+/// segment time is from the junction to the first branch station.
+/// With kinematics each branch segment can have a speed limit.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct BranchConfig {
+    pub junction: usize,
+    pub stations: Vec<usize>,
+    #[serde(default)]
+    pub segment_times: Vec<usize>,
+    #[serde(default)]
+    pub segment_lengths: Vec<usize>,
+    #[serde(default)]
+    pub speed_limits: Vec<Option<f64>>,
+}
+
+/// Train motion on a line: acceleration and braking in m/s², top
+/// speed in m/s and an optional speed limit for each segment of the
+/// main line. Segment lengths are in meters.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct KinematicsConfig {
+    pub acceleration: f64,
+    pub braking: f64,
+    pub max_speed: f64,
+    #[serde(default)]
+    pub speed_limits: Vec<Option<f64>>,
+}
+
+/// Energy model of a line, see [`crate::energy::EnergyModel`]. The
+/// cruise speed, in m/s, defaults to the top speed of the line
+/// kinematics. The running resistance is in N per tonne and the
+/// passenger mass in kg.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnergyModelConfig {
+    #[serde(default)]
+    pub cruise_speed: Option<f64>,
+    #[serde(default)]
+    pub resistance: Option<f64>,
+    #[serde(default)]
+    pub passenger_mass: Option<f64>,
+}
+
+/// Random train failures: the probability a running train fails in a
+/// step and the bounds of the repair time, in steps.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FailureConfig {
+    pub probability: f64,
+    pub repair: (usize, usize),
+}
+
+/// A scheduled block between two hours of the day. One station
+/// blocks its platforms, two consecutive stations the track
+/// between them. Without a direction both are blocked.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct BlockConfig {
+    pub stations: Vec<usize>,
+    pub start: Node,
+    pub end: Node,
+    #[serde(default)]
+    pub direction: Option<BlockDirection>,
+}
+
+/// Trains stopped by a block, or received by platforms:
+/// those leaving the first station or the last one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockDirection {
+    FromFirst,
+    FromLast,
+}
+
+impl BlockDirection {
+    /// Trains leaving the first station run toward the last one.
+    fn line_direction(self) -> LineDirection {
+        match self {
+            Self::FromFirst => LineDirection::DirectionB,
+            Self::FromLast => LineDirection::DirectionA,
+        }
+    }
+}
+
+/// Platforms of a station of the line for the trains running in
+/// the given direction, or in both without a direction. A station
+/// has a platform for each direction by default.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlatformConfig {
+    pub station: usize,
+    pub count: usize,
+    #[serde(default)]
+    pub direction: Option<BlockDirection>,
+}
+
+/// A planned closure between two hours of the day. One station
+/// is closed to the passengers, trains run through it without
+/// stopping; two consecutive stations close the track between
+/// them, trains turn back on each side. Line ends stay open,
+/// a loop has none. Passengers are rerouted or, if they
+/// cannot reach their destination, stranded.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClosureConfig {
+    pub stations: Vec<usize>,
+    pub start: Node,
+    pub end: Node,
+}
+
+impl ClosureConfig {
+    fn target(&self) -> BlockTarget {
+        match self.stations[..] {
+            [a, b] => BlockTarget::Link(a, b),
+            _ => BlockTarget::Station(self.stations[0]),
+        }
+    }
+}
+
+/// Signalling of a line. Fixed blocks have the given length, in
+/// steps or, with kinematics, in meters. A moving block keeps the
+/// trains at the braking distance from top speed plus a margin in
+/// meters, and needs kinematics.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum SignallingConfig {
+    FixedBlock(usize),
+    MovingBlock { margin: f64 },
+}
+
+/// A rolling stock class. The top speed, in m/s, slows down the
+/// trains only on lines with kinematics, whose top speed is the
+/// reference.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RollingStockConfig {
+    pub name: String,
+    pub seated: usize,
+    pub standing: usize,
+    #[serde(default)]
+    pub max_speed: Option<f64>,
+    #[serde(default)]
+    pub energy: Option<EnergyConfig>,
+}
+
+/// Energy parameters of a rolling stock class, see
+/// [`crate::rolling_stock::RollingStock::set_energy`].
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnergyConfig {
+    pub mass: f64,
+    pub traction_efficiency: f64,
+    pub regen_efficiency: f64,
+}
+
+impl RollingStockConfig {
+    fn build(&self) -> RollingStock {
+        let stock = RollingStock::new(&self.name, self.seated, self.standing);
+        let stock = match self.max_speed {
+            Some(speed) => stock.set_max_speed(speed),
+            None => stock,
+        };
+        match &self.energy {
+            Some(e) => stock.set_energy(e.mass, e.traction_efficiency, e.regen_efficiency),
+            None => stock,
+        }
+    }
+}
+
+/// Departure steps from the first and the last station of a line.
+/// A terminus without departures starts trains every ``train_delay`` steps.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TimetableConfig {
+    #[serde(default)]
+    pub from_first: Option<Vec<usize>>,
+    #[serde(default)]
+    pub from_last: Option<Vec<usize>>,
+}
+
+/// Headway profile of a line: each entry is the hour of the day
+/// when a period starts and its headway in steps. Each terminus
+/// can limit the service between the first and the last train,
+/// otherwise trains run during the whole simulated time.
+/// A terminus with a timetable ignores the service.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServiceConfig {
+    pub headways: Vec<(Node, usize)>,
+    #[serde(default)]
+    pub from_first: Option<SpanConfig>,
+    #[serde(default)]
+    pub from_last: Option<SpanConfig>,
+}
+
+/// Service pattern of a line: trains started by the line cycle
+/// through its patterns. A pattern with a turnback serves only the
+/// stations between the two given ones, otherwise it serves the
+/// whole line. Trains pass through the skipped stations without
+/// stopping, circular lines have no skipped stations. Branch 0 is
+/// the main line, the others follow the order of the line branches.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PatternConfig {
+    #[serde(default)]
+    pub turnback: Option<(usize, usize)>,
+    #[serde(default)]
+    pub skip: Vec<usize>,
+    #[serde(default)]
+    pub branch: usize,
+}
+
+/// Hours of the first and of the last train leaving a terminus.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpanConfig {
+    pub first_train: Node,
+    pub last_train: Node,
+}
+
+/// Traffic matrix, daily profile anchors and anchor levels.
+/// See [`crate::traffic_generator::SimpleTrafficGeneratorConfig`].
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TrafficConfig {
+    pub matrix: Vec<Vec<Int>>,
+    #[serde(default)]
+    pub anchors: Vec<(Int, Node)>,
+    pub levels: (Node, Node),
+}
+
+impl Scenario {
+    /// Parse and validate a TOML scenario.
+    pub fn from_toml_str(s: &str) -> Result<Self, ScenarioError> {
+        let scenario: Self = toml::from_str(s)?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    /// Parse and validate a JSON scenario.
+    pub fn from_json_str(s: &str) -> Result<Self, ScenarioError> {
+        let scenario: Self = serde_json::from_str(s)?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    /// Load and validate a scenario file. The format
+    /// is chosen by the file extension.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ScenarioError> {
+        let path = path.as_ref();
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        let parse = match ext.as_str() {
+            "toml" => Self::from_toml_str,
+            "json" => Self::from_json_str,
+            _ => return Err(ScenarioError::UnknownFormat(ext)),
+        };
+        parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Check that the scenario describes a consistent network.
+    pub fn validate(&self) -> Result<(), ScenarioError> {
+        self.validate_time()?;
+        self.validate_network()?;
+        self.validate_rolling_stock()?;
+        self.lines
+            .iter()
+            .enumerate()
+            .try_for_each(|(i, line)| self.validate_line(i, line))?;
+        self.validate_traffic()?;
+        self.validate_coverage()?;
+        self.validate_closures()
+    }
+
+    /// Number of simulation steps.
+    #[must_use]
+    pub fn steps(&self) -> Int {
+        crate::get_steps(self.time.begin, self.time.end, self.time.resolution)
+    }
+
+    /// Number of simulation steps in an hour.
+    #[must_use]
+    pub fn steps_per_hour(&self) -> usize {
+        (crate::MINUTE_IN_HOUR * self.time.resolution) as usize
+    }
+
+    /// Simulated seconds in a step.
+    fn step_time(&self) -> f64 {
+        60. / f64::from(self.time.resolution)
+    }
+
+    /// Simulation step at the given hour of the day.
+    #[must_use]
+    pub fn step_at(&self, hour: Node) -> usize {
+        let minutes = (hour - Node::from(self.time.begin)) * Node::from(crate::MINUTE_IN_HOUR);
+        (minutes * Node::from(self.time.resolution)).round().max(0.) as usize
+    }
+
+    /// Number of stations in the network.
+    #[must_use]
+    pub fn station_count(&self) -> usize {
+        self.network.stations
+    }
+
+    /// Name of the given station. If no name is given in the
+    /// scenario the station index is used.
+    #[must_use]
+    pub fn station_name(&self, station: usize) -> String {
+        self.network
+            .names
+            .get(station)
+            .cloned()
+            .unwrap_or_else(|| station.to_string())
+    }
+
+    /// Adjacent matrix of the network. Missing links are ``Int::MAX``,
+    /// the diagonal is zero so that the distance of each station
+    /// from itself is zero too.
+    #[must_use]
+    pub fn adjacent_matrix(&self) -> Array2<Int> {
+        let n = self.network.stations;
+        let mut adj = Array2::from_elem((n, n), Int::MAX);
+        adj.diag_mut().fill(0);
+        for (a, b, w) in &self.network.links {
+            adj[(*a, *b)] = *w;
+            adj[(*b, *a)] = *w;
+        }
+        adj
+    }
+
+    /// Build the routing matrices for the scenario lines.
+    #[must_use]
+    pub fn network(&self) -> ScenarioNetwork {
+        // Each branch is a line for the passengers routing.
+        let mut loops = vec![];
+        let mut lines: Vec<Vec<usize>> = vec![];
+        for line in &self.lines {
+            if line.circular {
+                loops.push(lines.len());
+            }
+            lines.extend(line.paths());
+        }
+        let terminus: Vec<(usize, usize)> =
+            lines.iter().map(|l| (l[0], *l.last().unwrap())).collect();
+        let metro_lines = loops.into_iter().fold(
+            MetroLines::from_given_lines(lines, &terminus),
+            MetroLines::set_loop,
+        );
+        let closures = self.closure_periods(&metro_lines);
+        let (direction, interchange) =
+            routes::build_directions_from_lines(self.adjacent_matrix(), &metro_lines);
+        ScenarioNetwork {
+            direction,
+            interchange,
+            closures,
+            station_count: self.network.stations,
+        }
+    }
+
+    /// Split the simulated time at each closure start and end and
+    /// build the routing of the periods with some closure in progress.
+    fn closure_periods(&self, metro_lines: &MetroLines<'_>) -> Vec<ClosurePeriod> {
+        let steps: Vec<(usize, usize)> = self
+            .closures
+            .iter()
+            .map(|c| (self.step_at(c.start), self.step_at(c.end)))
+            .collect();
+        let mut bounds: Vec<usize> = steps.iter().flat_map(|(s, e)| [*s, *e]).collect();
+        bounds.sort_unstable();
+        bounds.dedup();
+        bounds
+            .windows(2)
+            .filter_map(|w| {
+                let active = self
+                    .closures
+                    .iter()
+                    .zip(&steps)
+                    .filter(|(_, (start, end))| (*start..*end).contains(&w[0]));
+                let mut stations = vec![];
+                let mut links = vec![];
+                for (closure, _) in active {
+                    match closure.target() {
+                        BlockTarget::Station(s) => stations.push(s),
+                        BlockTarget::Link(a, b) => links.push((a, b)),
+                    }
+                }
+                (!stations.is_empty() || !links.is_empty()).then(|| {
+                    ClosurePeriod::new(
+                        (w[0], w[1]),
+                        self.adjacent_matrix(),
+                        metro_lines,
+                        stations,
+                        &links,
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Build the scenario lines.
+    #[must_use]
+    pub fn lines<T>(&self) -> Vec<Line<T>>
+    where
+        T: callbacks::PassengerAction,
+    {
+        self.lines.iter().map(|l| self.build_line(l)).collect()
+    }
+
+    /// Build the passenger factory. Noise on the daily profile
+    /// is seeded with the scenario seed, if any.
+    #[must_use]
+    pub fn passenger_factory(&self) -> PassengerFactory<SimpleTrafficGenerator> {
+        let mut config = traffic_generator::SimpleTrafficGeneratorConfig::new(
+            (self.time.begin, self.time.end),
+            self.time.resolution,
+            self.traffic.anchors.clone(),
+            self.traffic.levels,
+        );
+        if let Some(seed) = self.seed {
+            config = config.set_seed(seed);
+        }
+        let generators = traffic_generator::simple_traffic_generator_factory(
+            self.traffic.matrix.clone(),
+            &config,
+        );
+        PassengerFactory::new(generators)
+    }
+
+    fn build_line<T>(&self, line: &LineConfig) -> Line<T>
+    where
+        T: callbacks::PassengerAction,
+    {
+        let mut config = fast_line_factory::FastLineFactoryConfig::new(
+            line.stations.iter().copied(),
+            line.station_time,
+            line.segments().iter().copied(),
+            line.split_len,
+            line.depo_size,
+            line.train_delay,
+        );
+        let mut options = LineOptions::default();
+        if let Some(capacity) = line.train_capacity {
+            options = options.set_train_capacity(capacity);
+        }
+        if let Some(rate) = line.door_throughput {
+            options = options.set_door_throughput(rate);
+        }
+        let kinematics = line.kinematics.as_ref().map(|kin| {
+            Kinematics::new(kin.acceleration, kin.braking, kin.max_speed)
+                .set_step_time(self.step_time())
+        });
+        if let (Some(kinematics), Some(kin)) = (kinematics, &line.kinematics) {
+            config = config
+                .set_kinematics(kinematics)
+                .set_speed_limits(kin.speed_limits.clone());
+        }
+        if let Some(signalling) = line.signalling {
+            let signalling = match (signalling, kinematics) {
+                (SignallingConfig::FixedBlock(length), Some(kinematics)) => {
+                    Signalling::fixed_block(kinematics.cruise_time(length))
+                }
+                (SignallingConfig::FixedBlock(length), None) => Signalling::fixed_block(length),
+                (SignallingConfig::MovingBlock { margin }, kinematics) => Signalling::moving_block(
+                    kinematics
+                        .expect("validated moving block kinematics")
+                        .braking_separation(margin),
+                ),
+            };
+            options = options.set_signalling(signalling);
+        }
+        if let Some(energy) = &line.energy {
+            let cruise_speed = energy
+                .cruise_speed
+                .or(line.kinematics.as_ref().map(|k| k.max_speed))
+                .expect("validated cruise speed");
+            let mut model = EnergyModel::new(cruise_speed).set_step_time(self.step_time());
+            if let Some(resistance) = energy.resistance {
+                model = model.set_resistance(resistance);
+            }
+            if let Some(mass) = energy.passenger_mass {
+                model = model.set_passenger_mass(mass);
+            }
+            options = options.set_energy_model(model);
+        }
+        if !line.fleet.is_empty() {
+            let classes = line
+                .fleet
+                .iter()
+                .filter_map(|name| self.rolling_stock.iter().find(|c| &c.name == name))
+                .map(RollingStockConfig::build)
+                .collect();
+            options = options.set_rolling_stock(classes);
+        }
+        if !line.patterns.is_empty() {
+            let patterns = line
+                .patterns
+                .iter()
+                .map(|p| {
+                    match p.turnback {
+                        Some((first, last)) => ServicePattern::short_turn(first, last),
+                        None => ServicePattern::full(),
+                    }
+                    .set_skipped(p.skip.clone())
+                    .set_branch(p.branch)
+                })
+                .collect();
+            options = options.set_service_patterns(patterns);
+        }
+        if line.circular {
+            config = config.set_circular(true);
+        }
+        if let Some(failures) = &line.failures {
+            let (min, max) = failures.repair;
+            options = options.set_failure_model(FailureModel::new(failures.probability, min, max));
+        }
+        for block in &line.blocks {
+            options = options.add_segment_block(self.build_block(block));
+        }
+        if line.passing_loop_priority {
+            options = options.set_deadlock_policy(DeadlockPolicy::PassingLoopPriority);
+        }
+        for platforms in &line.platforms {
+            let directions = match platforms.direction {
+                Some(direction) => vec![direction.line_direction()],
+                None => vec![LineDirection::DirectionA, LineDirection::DirectionB],
+            };
+            for dir in directions {
+                options = options.set_platforms(platforms.station, dir, platforms.count);
+            }
+        }
+        let stations: HashSet<usize> = line.paths().flatten().collect();
+        let links = line.links();
+        for closure in &self.closures {
+            let on_line = match closure.target() {
+                BlockTarget::Station(s) => stations.contains(&s),
+                BlockTarget::Link(a, b) => links.contains(&(a, b)) || links.contains(&(b, a)),
+            };
+            if on_line {
+                options = options.add_closure(
+                    closure.target(),
+                    self.step_at(closure.start),
+                    self.step_at(closure.end),
+                );
+            }
+        }
+        for (i, branch) in line.branches.iter().enumerate() {
+            config = config.add_branch(
+                branch.junction,
+                branch.stations.clone(),
+                if line.kinematics.is_some() {
+                    branch.segment_lengths.clone()
+                } else {
+                    branch.segment_times.clone()
+                },
+            );
+            if line.kinematics.is_some() {
+                config = config.set_branch_speed_limits(i + 1, branch.speed_limits.clone());
+            }
+        }
+        // Trains leaving the first station run toward the last one.
+        for (dir, from_first) in [
+            (LineDirection::DirectionB, true),
+            (LineDirection::DirectionA, false),
+        ] {
+            if let Some(dispatch) = self.line_dispatch(line, from_first) {
+                options = options.set_dispatch(dir, dispatch);
+            }
+        }
+        config = config.set_options(options);
+        fast_line_factory::fast_line_factory(config, self.network.stations)
+    }
+
+    fn build_block(&self, block: &BlockConfig) -> SegmentBlock {
+        let target = match block.stations[..] {
+            [a, b] => BlockTarget::Link(a, b),
+            _ => BlockTarget::Station(block.stations[0]),
+        };
+        let segment_block =
+            SegmentBlock::new(target, self.step_at(block.start), self.step_at(block.end));
+        match block.direction {
+            Some(direction) => segment_block.set_direction(direction.line_direction()),
+            None => segment_block,
+        }
+    }
+
+    /// Dispatch policy of the trains leaving the first or the last
+    /// station of the line, if different from the fixed train delay.
+    fn line_dispatch(&self, line: &LineConfig, from_first: bool) -> Option<Dispatch> {
+        let timetable = line
+            .timetable
+            .as_ref()
+            .and_then(|t| pick(from_first, &t.from_first, &t.from_last).as_ref());
+        if let Some(steps) = timetable {
+            return Some(Timetable::new(steps.iter().copied()).into());
+        }
+        line.service.as_ref().map(|service| {
+            // the first period also covers the time before it starts
+            let first = service
+                .headways
+                .iter()
+                .min_by(|a, b| a.0.total_cmp(&b.0))
+                .map_or(0, |(_, headway)| *headway);
+            let profile = service
+                .headways
+                .iter()
+                .fold(HeadwayProfile::new(first), |profile, (hour, headway)| {
+                    profile.add_period(self.step_at(*hour), *headway)
+                });
+            let span = match pick(from_first, &service.from_first, &service.from_last) {
+                Some(span) => ServiceSpan::new(
+                    self.step_at(span.first_train),
+                    self.step_at(span.last_train),
+                ),
+                None => ServiceSpan::new(0, self.steps() as usize),
+            };
+            Service::new(profile, span).into()
+        })
+    }
+
+    fn validate_time(&self) -> Result<(), ScenarioError> {
+        let TimeConfig {
+            begin,
+            end,
+            resolution,
+        } = self.time;
+        if end <= begin || resolution == 0 {
+            Err(ScenarioError::InvalidTime {
+                begin,
+                end,
+                resolution,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    fn validate_network(&self) -> Result<(), ScenarioError> {
+        let network = &self.network;
+        if network.stations == 0 {
+            return Err(ScenarioError::EmptyNetwork);
+        }
+        if !network.names.is_empty() && network.names.len() != network.stations {
+            return Err(ScenarioError::StationNames {
+                expected: network.stations,
+                found: network.names.len(),
+            });
+        }
+        for (index, (a, b, w)) in network.links.iter().enumerate() {
+            let context = format!("network: link {index}");
+            self.check_station(&context, *a)?;
+            self.check_station(&context, *b)?;
+            if a == b || *w == 0 || *w == Int::MAX {
+                return Err(ScenarioError::InvalidLink { index });
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_line(&self, index: usize, line: &LineConfig) -> Result<(), ScenarioError> {
+        let min_len = if line.circular { 3 } else { 2 };
+        if line.stations.len() < min_len {
+            return Err(ScenarioError::LineTooShort { line: index });
+        }
+        if line.circular && !line.branches.is_empty() {
+            return Err(invalid_line(index, "a circular line cannot have branches"));
+        }
+        let context = format!("line {index}");
+        let mut visited = HashSet::new();
+        let branch_stations = line.branches.iter().flat_map(|b| &b.stations);
+        for station in line.stations.iter().chain(branch_stations) {
+            self.check_station(&context, *station)?;
+            if !visited.insert(*station) {
+                return Err(ScenarioError::DuplicateStation {
+                    line: index,
+                    station: *station,
+                });
+            }
+        }
+        for branch in &line.branches {
+            let inner = &line.stations[1..line.stations.len() - 1];
+            if !inner.contains(&branch.junction) {
+                return Err(invalid_line(
+                    index,
+                    "branch junction must be an intermediate station of the line",
+                ));
+            }
+            let segments = if line.kinematics.is_some() {
+                &branch.segment_lengths
+            } else {
+                &branch.segment_times
+            };
+            if branch.stations.is_empty() || segments.len() != branch.stations.len() {
+                return Err(ScenarioError::SegmentCount {
+                    line: index,
+                    expected: branch.stations.len(),
+                    found: segments.len(),
+                });
+            }
+        }
+        let adj = self.adjacent_matrix();
+        for (from, to) in line.links() {
+            if adj[(from, to)] == Int::MAX {
+                return Err(ScenarioError::NotAdjacent {
+                    line: index,
+                    from,
+                    to,
+                });
+            }
+        }
+        let expected = if line.circular {
+            line.stations.len()
+        } else {
+            line.stations.len() - 1
+        };
+        if line.segments().len() != expected {
+            return Err(ScenarioError::SegmentCount {
+                line: index,
+                expected,
+                found: line.segments().len(),
+            });
+        }
+        let branch_segments = line.branches.iter().flat_map(|b| {
+            if line.kinematics.is_some() {
+                &b.segment_lengths
+            } else {
+                &b.segment_times
+            }
+        });
+        if line
+            .segments()
+            .iter()
+            .chain(branch_segments)
+            .any(|t| *t == 0)
+        {
+            return Err(invalid_line(index, "segment times must be positive"));
+        }
+        if line.station_time == 0 {
+            return Err(invalid_line(index, "station_time must be positive"));
+        }
+        if line.split_len == 0 {
+            return Err(invalid_line(index, "split_len must be positive"));
+        }
+        self.validate_kinematics(index, line)?;
+        match line.signalling {
+            Some(SignallingConfig::FixedBlock(0)) => {
+                return Err(invalid_line(index, "block length must be positive"));
+            }
+            Some(SignallingConfig::MovingBlock { margin }) => {
+                if line.kinematics.is_none() {
+                    return Err(invalid_line(index, "a moving block needs kinematics"));
+                }
+                if margin.is_nan() || margin < 0. {
+                    return Err(invalid_line(
+                        index,
+                        "the moving block margin must not be negative",
+                    ));
+                }
+            }
+            _ => {}
+        }
+        if let Some(energy) = &line.energy {
+            if energy.cruise_speed.is_none() && line.kinematics.is_none() {
+                return Err(invalid_line(
+                    index,
+                    "the energy model needs a cruise_speed or kinematics",
+                ));
+            }
+            let positive = [
+                energy.cruise_speed,
+                energy.resistance,
+                energy.passenger_mass,
+            ]
+            .into_iter()
+            .flatten()
+            .all(|v| v > 0.);
+            if !positive {
+                return Err(invalid_line(index, "energy parameters must be positive"));
+            }
+        }
+        for name in &line.fleet {
+            if !self.rolling_stock.iter().any(|c| &c.name == name) {
+                return Err(invalid_line(
+                    index,
+                    &format!("unknown rolling stock class {name}"),
+                ));
+            }
+        }
+        let stations: HashSet<usize> = line.paths().flatten().collect();
+        if line
+            .platforms
+            .iter()
+            .any(|p| p.count == 0 || !stations.contains(&p.station))
+        {
+            return Err(invalid_line(
+                index,
+                "platforms need a station of the line and a positive count",
+            ));
+        }
+        if line.depo_size == 0 {
+            return Err(invalid_line(index, "depo_size must be positive"));
+        }
+        if line.train_capacity == Some(0) {
+            return Err(invalid_line(index, "train_capacity must be positive"));
+        }
+        if line.door_throughput == Some(0) {
+            return Err(invalid_line(index, "door_throughput must be positive"));
+        }
+        for pattern in &line.patterns {
+            self.validate_pattern(index, line, pattern)?;
+        }
+        self.validate_disruptions(index, line)?;
+        match &line.service {
+            Some(service) => self.validate_service(index, service),
+            None => Ok(()),
+        }
+    }
+
+    fn validate_rolling_stock(&self) -> Result<(), ScenarioError> {
+        let mut names = HashSet::new();
+        for class in &self.rolling_stock {
+            let invalid = |reason: &str| ScenarioError::InvalidRollingStock {
+                class: class.name.clone(),
+                reason: reason.to_owned(),
+            };
+            if !names.insert(&class.name) {
+                return Err(invalid("the class is defined more than once"));
+            }
+            if class.seated + class.standing == 0 {
+                return Err(invalid("capacity must be positive"));
+            }
+            if class.max_speed.is_some_and(|s| s.is_nan() || s <= 0.) {
+                return Err(invalid("max_speed must be positive"));
+            }
+            if let Some(e) = &class.energy {
+                let valid = e.mass > 0.
+                    && e.traction_efficiency > 0.
+                    && e.traction_efficiency <= 1.
+                    && (0. ..=1.).contains(&e.regen_efficiency);
+                if !valid {
+                    return Err(invalid(
+                        "mass must be positive and efficiencies between 0 and 1",
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_kinematics(&self, index: usize, line: &LineConfig) -> Result<(), ScenarioError> {
+        let lengths = line.branches.iter().map(|b| &b.segment_lengths);
+        let branch_limits = line.branches.iter().flat_map(|b| b.speed_limits.iter());
+        let Some(kin) = &line.kinematics else {
+            if std::iter::once(&line.segment_lengths)
+                .chain(lengths)
+                .any(|l| !l.is_empty())
+            {
+                return Err(invalid_line(index, "segment lengths need kinematics"));
+            }
+            if branch_limits.count() > 0 {
+                return Err(invalid_line(index, "speed limits need kinematics"));
+            }
+            return Ok(());
+        };
+        let times = line.branches.iter().map(|b| &b.segment_times);
+        if std::iter::once(&line.segment_times)
+            .chain(times)
+            .any(|t| !t.is_empty())
+        {
+            return Err(invalid_line(
+                index,
+                "segment times cannot be given with kinematics",
+            ));
+        }
+        let limits = kin.speed_limits.iter().chain(branch_limits).flatten();
+        let positive = [kin.acceleration, kin.braking, kin.max_speed]
+            .iter()
+            .chain(limits)
+            .all(|v| *v > 0.);
+        if !positive {
+            return Err(invalid_line(index, "kinematic parameters must be positive"));
+        }
+        if kin.speed_limits.len() > line.segment_lengths.len()
+            || line
+                .branches
+                .iter()
+                .any(|b| b.speed_limits.len() > b.segment_lengths.len())
+        {
+            return Err(invalid_line(index, "more speed limits than segments"));
+        }
+        Ok(())
+    }
+
+    fn validate_pattern(
+        &self,
+        index: usize,
+        line: &LineConfig,
+        pattern: &PatternConfig,
+    ) -> Result<(), ScenarioError> {
+        let Some(stations) = line.branch_stations(pattern.branch) else {
+            return Err(invalid_line(index, "pattern branch is not on the line"));
+        };
+        if let Some((first, last)) = pattern.turnback {
+            if first == last || !stations.contains(&first) || !stations.contains(&last) {
+                return Err(invalid_line(
+                    index,
+                    "turnback must be two different stations of the line",
+                ));
+            }
+        }
+        if line.circular && pattern.turnback.is_some() {
+            return Err(invalid_line(index, "a circular line has no turnback"));
+        }
+        // on a loop passengers wait for the next station only,
+        // a train skipping it would never pick them up
+        if line.circular && !pattern.skip.is_empty() {
+            return Err(invalid_line(index, "a circular line cannot skip stations"));
+        }
+        let termini = [stations[0], stations[stations.len() - 1]];
+        for station in &pattern.skip {
+            let stops = pattern.turnback.map_or(termini, |(a, b)| [a, b]);
+            if stops.contains(station) || termini.contains(station) || !stations.contains(station) {
+                return Err(invalid_line(
+                    index,
+                    "skipped stations must be intermediate stations of the line",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_disruptions(&self, index: usize, line: &LineConfig) -> Result<(), ScenarioError> {
+        if let Some(failures) = &line.failures {
+            let (min, max) = failures.repair;
+            if !(0. ..=1.).contains(&failures.probability) || min > max {
+                return Err(invalid_line(
+                    index,
+                    "failure probability must be in [0, 1] and repair bounds ordered",
+                ));
+            }
+        }
+        let stations: HashSet<usize> = line.paths().flatten().collect();
+        let links = line.links();
+        for block in &line.blocks {
+            let on_line = match block.stations[..] {
+                [station] => stations.contains(&station),
+                [a, b] => links.contains(&(a, b)) || links.contains(&(b, a)),
+                _ => false,
+            };
+            if !on_line {
+                return Err(invalid_line(
+                    index,
+                    "a block needs a station or two consecutive stations of the line",
+                ));
+            }
+            let in_time = |hour: Node| {
+                hour >= Node::from(self.time.begin) && hour <= Node::from(self.time.end)
+            };
+            if block.start >= block.end || !in_time(block.start) || !in_time(block.end) {
+                return Err(invalid_line(
+                    index,
+                    "block start and end must be ordered and within the simulated time",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_closures(&self) -> Result<(), ScenarioError> {
+        let invalid = |index, reason: &str| ScenarioError::InvalidClosure {
+            index,
+            reason: reason.to_owned(),
+        };
+        let in_time =
+            |hour: Node| hour >= Node::from(self.time.begin) && hour <= Node::from(self.time.end);
+        for (index, closure) in self.closures.iter().enumerate() {
+            let on_lines = match closure.stations[..] {
+                // Trains turn back at the ends of a line, so these
+                // stations stay open. A loop has no ends.
+                [station] => {
+                    self.lines
+                        .iter()
+                        .any(|l| l.paths().any(|p| p.contains(&station)))
+                        && !self.lines.iter().any(|l| {
+                            !l.circular
+                                && l.paths()
+                                    .any(|p| p[0] == station || p[p.len() - 1] == station)
+                        })
+                }
+                [a, b] => self.lines.iter().any(|l| {
+                    let links = l.links();
+                    links.contains(&(a, b)) || links.contains(&(b, a))
+                }),
+                _ => false,
+            };
+            if !on_lines {
+                return Err(invalid(
+                    index,
+                    "a closure needs a station that is not a line end or two consecutive stations of a line",
+                ));
+            }
+            if closure.start >= closure.end || !in_time(closure.start) || !in_time(closure.end) {
+                return Err(invalid(
+                    index,
+                    "closure start and end must be ordered and within the simulated time",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_service(&self, index: usize, service: &ServiceConfig) -> Result<(), ScenarioError> {
+        let in_time =
+            |hour: Node| hour >= Node::from(self.time.begin) && hour <= Node::from(self.time.end);
+        if service.headways.is_empty() {
+            return Err(invalid_line(index, "service needs at least one headway"));
+        }
+        for (hour, headway) in &service.headways {
+            if !in_time(*hour) {
+                return Err(invalid_line(
+                    index,
+                    "headway period outside the simulated time",
+                ));
+            }
+            if *headway == 0 {
+                return Err(invalid_line(index, "headway must be positive"));
+            }
+        }
+        let spans = [&service.from_first, &service.from_last];
+        for span in spans.into_iter().flatten() {
+            if span.first_train > span.last_train
+                || !in_time(span.first_train)
+                || !in_time(span.last_train)
+            {
+                return Err(invalid_line(
+                    index,
+                    "first and last train must be ordered and within the simulated time",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_traffic(&self) -> Result<(), ScenarioError> {
+        let expected = self.network.stations;
+        let traffic = &self.traffic;
+        if traffic.matrix.len() != expected {
+            return Err(ScenarioError::TrafficShape {
+                expected,
+                row: traffic.matrix.len(),
+                found: 0,
+            });
+        }
+        for (row, values) in traffic.matrix.iter().enumerate() {
+            if values.len() != expected {
+                return Err(ScenarioError::TrafficShape {
+                    expected,
+                    row,
+                    found: values.len(),
+                });
+            }
+            if values[row] != 0 {
+                return Err(ScenarioError::SelfTraffic { station: row });
+            }
+        }
+        let (min, max) = traffic.levels;
+        if !min.is_finite() || !max.is_finite() || min <= 0. || min > max {
+            return Err(ScenarioError::InvalidLevels { min, max });
+        }
+        let hours = (self.time.end - self.time.begin + 1) as usize;
+        for (index, (time, _)) in traffic.anchors.iter().enumerate() {
+            if *time < self.time.begin || *time > self.time.end || index >= hours {
+                return Err(ScenarioError::InvalidAnchor { index });
+            }
+        }
+        Ok(())
+    }
+
+    /// Each station must be on a line and reachable through the lines.
+    fn validate_coverage(&self) -> Result<(), ScenarioError> {
+        let n = self.network.stations;
+        let mut neighbors = vec![Vec::new(); n];
+        let mut served = vec![false; n];
+        for line in &self.lines {
+            for (a, b) in line.links() {
+                neighbors[a].push(b);
+                neighbors[b].push(a);
+                served[a] = true;
+                served[b] = true;
+            }
+        }
+        if let Some(station) = served.iter().position(|s| !s) {
+            return Err(ScenarioError::UnservedStation { station });
+        }
+        let mut reached = vec![false; n];
+        let mut stack = vec![0];
+        reached[0] = true;
+        while let Some(s) = stack.pop() {
+            for next in &neighbors[s] {
+                if !reached[*next] {
+                    reached[*next] = true;
+                    stack.push(*next);
+                }
+            }
+        }
+        match reached.iter().position(|r| !r) {
+            Some(station) => Err(ScenarioError::Disconnected { station }),
+            None => Ok(()),
+        }
+    }
+
+    fn check_station(&self, context: &str, station: usize) -> Result<(), ScenarioError> {
+        if station < self.network.stations {
+            Ok(())
+        } else {
+            Err(ScenarioError::UnknownStation {
+                context: context.to_owned(),
+                station,
+            })
+        }
+    }
+}
+
+fn pick<T>(first: bool, a: T, b: T) -> T {
+    if first {
+        a
+    } else {
+        b
+    }
+}
+
+fn invalid_line(line: usize, reason: &str) -> ScenarioError {
+    ScenarioError::InvalidLine {
+        line,
+        reason: reason.to_owned(),
+    }
+}
+
+/// Routing matrices built from a scenario. Stations borrow
+/// them, so this object must outlive the simulation.
+pub struct ScenarioNetwork {
+    direction: MetroDirection,
+    interchange: MetroInterchange,
+    closures: Vec<ClosurePeriod>,
+    station_count: usize,
+}
+
+impl ScenarioNetwork {
+    /// Build the network stations.
+    #[must_use]
+    pub fn stations<T>(&self) -> Vec<Station<'_, T>> {
+        station::station_list_factory(self.station_count, &self.direction, &self.interchange)
+    }
+
+    #[must_use]
+    pub fn get_direction(&self) -> &MetroDirection {
+        &self.direction
+    }
+
+    #[must_use]
+    pub fn get_interchange(&self) -> &MetroInterchange {
+        &self.interchange
+    }
+
+    /// Routing in use while the planned closures are in progress.
+    #[must_use]
+    pub fn get_closures(&self) -> &[ClosurePeriod] {
+        &self.closures
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    const SCENARIO: &str = r#"
+        seed = 3
+
+        [time]
+        begin = 5
+        end = 8
+        resolution = 2
+
+        [network]
+        stations = 5
+        links = [[0, 1, 1], [1, 2, 1], [1, 3, 1], [1, 4, 1]]
+
+        [[lines]]
+        stations = [0, 1, 2]
+        station_time = 2
+        segment_times = [6, 6]
+        split_len = 3
+        depo_size = 2
+        train_delay = 5
+        signalling = { fixed_block = 2 }
+        platforms = [{ station = 1, count = 2 }, { station = 2, count = 3, direction = "from_first" }]
+
+        [[lines.patterns]]
+
+        [[lines.patterns]]
+        turnback = [0, 1]
+
+        [[lines.patterns]]
+        skip = [1]
+
+        [lines.failures]
+        probability = 0.01
+        repair = [2, 6]
+
+        [[lines.blocks]]
+        stations = [1, 2]
+        start = 6
+        end = 6.5
+        direction = "from_first"
+
+        [[lines]]
+        stations = [3, 1, 4]
+        station_time = 2
+        segment_times = [6, 6]
+        split_len = 3
+        depo_size = 2
+        train_delay = 5
+        train_capacity = 100
+        door_throughput = 20
+        fleet = ["long", "short"]
+        passing_loop_priority = true
+
+        [lines.timetable]
+        from_first = [10, 40, 70]
+
+        [lines.service]
+        headways = [[5, 10], [7, 4]]
+        from_last = { first_train = 5.5, last_train = 7.5 }
+
+        [[rolling_stock]]
+        name = "long"
+        seated = 120
+        standing = 480
+        max_speed = 20.0
+
+        [rolling_stock.energy]
+        mass = 250.0
+        traction_efficiency = 0.9
+        regen_efficiency = 0.3
+
+        [[rolling_stock]]
+        name = "short"
+        seated = 60
+        standing = 240
+
+        [[closures]]
+        stations = [1]
+        start = 6
+        end = 7
+
+        [[closures]]
+        stations = [1, 4]
+        start = 6.5
+        end = 7.5
+
+        [traffic]
+        matrix = [
+            [0, 10, 5, 4, 3],
+            [5, 0, 12, 3, 3],
+            [5, 11, 0, 6, 3],
+            [4, 4, 7, 0, 1],
+            [4, 4, 7, 2, 0],
+        ]
+        anchors = [[7, 8.0]]
+        levels = [1.0, 10.0]
+    "#;
+
+    const LOOP_SCENARIO: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/scenarios/loop.toml");
+
+    fn load() -> Scenario {
+        Scenario::from_toml_str(SCENARIO).unwrap()
+    }
+
+    #[test]
+    fn test_load_toml() {
+        let scenario = load();
+        assert_eq!(scenario.seed, Some(3));
+        assert_eq!(scenario.steps(), 360);
+        assert_eq!(scenario.lines.len(), 2);
+        assert_eq!(scenario.lines[1].train_capacity, Some(100));
+        assert_eq!(scenario.lines[1].door_throughput, Some(20));
+        assert!(scenario.lines[1].passing_loop_priority);
+        assert_eq!(
+            scenario.lines[0].signalling,
+            Some(SignallingConfig::FixedBlock(2))
+        );
+        assert_eq!(
+            scenario.lines[0].platforms[1].direction,
+            Some(BlockDirection::FromFirst)
+        );
+        assert_eq!(
+            scenario.rolling_stock[0].energy.as_ref().unwrap().mass,
+            250.
+        );
+        let lines = scenario.lines::<()>();
+        let classes: Vec<&str> = lines[1]
+            .get_rolling_stock()
+            .iter()
+            .map(RollingStock::get_name)
+            .collect();
+        assert_eq!(classes, ["long", "short"]);
+        assert_eq!(scenario.lines[0].patterns[1].turnback, Some((0, 1)));
+        assert_eq!(scenario.lines[0].patterns[2].skip, [1]);
+        assert_eq!(
+            scenario.build_block(&scenario.lines[0].blocks[0]),
+            SegmentBlock::new(BlockTarget::Link(1, 2), 120, 180)
+                .set_direction(LineDirection::DirectionB)
+        );
+        assert!(scenario.lines[1].patterns.is_empty());
+        let timetable = scenario.lines[1].timetable.as_ref().unwrap();
+        assert_eq!(timetable.from_first, Some(vec![10, 40, 70]));
+        assert_eq!(timetable.from_last, None);
+        let service = scenario.lines[1].service.as_ref().unwrap();
+        assert_eq!(service.headways, [(5., 10), (7., 4)]);
+        assert_eq!(scenario.step_at(5.5), 60);
+        assert!(scenario.line_dispatch(&scenario.lines[0], true).is_none());
+        assert!(matches!(
+            scenario.line_dispatch(&scenario.lines[1], true),
+            Some(Dispatch::Timetable(_))
+        ));
+        match scenario.line_dispatch(&scenario.lines[1], false) {
+            Some(Dispatch::Service(service)) => {
+                assert_eq!(service.get_span(), ServiceSpan::new(60, 300));
+                assert_eq!(service.get_profile().headway_at(240), 4);
+            }
+            other => panic!("unexpected dispatch {other:?}"),
+        }
+        let mut late_start = scenario.lines[1].clone();
+        late_start.service.as_mut().unwrap().headways = vec![(7., 4), (6., 10)];
+        match scenario.line_dispatch(&late_start, false) {
+            Some(Dispatch::Service(service)) => {
+                assert_eq!(service.get_profile().headway_at(0), 10);
+                assert_eq!(service.get_profile().headway_at(240), 4);
+            }
+            other => panic!("unexpected dispatch {other:?}"),
+        }
+        assert_eq!(scenario.station_name(2), "2");
+
+        let network = scenario.network();
+        assert_eq!(network.get_direction().get_direction(0, 2), 2);
+        assert_eq!(network.get_direction().get_direction(2, 0), 0);
+        assert_eq!(network.get_interchange().next_station(0, 4), 1);
+        let stations = network.stations::<()>();
+        assert_eq!(stations.len(), 5);
+        let lines = scenario.lines::<()>();
+        assert_eq!(lines.len(), 2);
+
+        // the two closures overlap between 6.5 and 7
+        let closures = network.get_closures();
+        let periods: Vec<(usize, usize)> = closures
+            .iter()
+            .map(|c| (c.get_start(), c.get_end()))
+            .collect();
+        assert_eq!(periods, [(120, 180), (180, 240), (240, 300)]);
+        assert!(closures[1].is_closed(1) && !closures[2].is_closed(1));
+        assert_eq!(closures[0].get_interchange().next_station(0, 2), 2);
+        assert_eq!(closures[0].get_interchange().next_station(0, 3), 0);
+        assert_eq!(closures[2].get_interchange().next_station(3, 4), 3);
+    }
+
+    #[test]
+    fn test_load_json() {
+        let scenario = load();
+        let json = serde_json::to_string(&scenario).unwrap();
+        let from_json = Scenario::from_json_str(&json).unwrap();
+        assert_eq!(scenario, from_json);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let err = Scenario::from_toml_str("seed = 3").unwrap_err();
+        assert!(matches!(err, ScenarioError::Parse(_)), "{err}");
+        let unknown = SCENARIO.replace("seed = 3", "seed = 3\nsteps = 4");
+        let err = Scenario::from_toml_str(&unknown).unwrap_err();
+        assert!(err.to_string().contains("steps"), "{err}");
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        let mut scenario = load();
+        scenario.lines[0].stations = vec![0, 2, 1];
+        assert!(matches!(
+            scenario.validate(),
+            Err(ScenarioError::NotAdjacent {
+                line: 0,
+                from: 0,
+                to: 2
+            })
+        ));
+
+        let mut scenario = load();
+        scenario.lines[1].segment_times.pop();
+        assert!(matches!(
+            scenario.validate(),
+            Err(ScenarioError::SegmentCount {
+                line: 1,
+                expected: 2,
+                found: 1
+            })
+        ));
+
+        let mut scenario = load();
+        scenario.network.links[0].1 = 9;
+        let err = scenario.validate().unwrap_err();
+        assert_eq!(err.to_string(), "network: link 0: unknown station 9");
+
+        let mut scenario = load();
+        scenario.traffic.matrix[2].push(1);
+        assert!(matches!(
+            scenario.validate(),
+            Err(ScenarioError::TrafficShape { row: 2, .. })
+        ));
+
+        let mut scenario = load();
+        scenario.traffic.levels = (1., f64::INFINITY);
+        assert!(matches!(
+            scenario.validate(),
+            Err(ScenarioError::InvalidLevels { .. })
+        ));
+        scenario.traffic.levels = (f64::NAN, 10.);
+        assert!(matches!(
+            scenario.validate(),
+            Err(ScenarioError::InvalidLevels { .. })
+        ));
+
+        let mut scenario = load();
+        scenario.lines.pop();
+        assert!(matches!(
+            scenario.validate(),
+            Err(ScenarioError::UnservedStation { station: 3 })
+        ));
+
+        let mut scenario = load();
+        scenario.time.end = 5;
+        assert!(matches!(
+            scenario.validate(),
+            Err(ScenarioError::InvalidTime { .. })
+        ));
+
+        let mut scenario = load();
+        scenario.lines[1].service.as_mut().unwrap().headways[0].0 = 3.;
+        let err = scenario.validate().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 1: headway period outside the simulated time"
+        );
+
+        let mut scenario = load();
+        scenario.lines[0].depo_size = 0;
+        let err = scenario.validate().unwrap_err();
+        assert_eq!(err.to_string(), "line 0: depo_size must be positive");
+
+        let mut scenario = load();
+        scenario.lines[1].door_throughput = Some(0);
+        let err = scenario.validate().unwrap_err();
+        assert_eq!(err.to_string(), "line 1: door_throughput must be positive");
+
+        let mut scenario = load();
+        scenario.lines[1].energy = Some(EnergyModelConfig {
+            cruise_speed: None,
+            resistance: Some(30.),
+            passenger_mass: None,
+        });
+        let err = scenario.validate().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 1: the energy model needs a cruise_speed or kinematics"
+        );
+        scenario.lines[1].energy.as_mut().unwrap().cruise_speed = Some(20.);
+        assert!(scenario.validate().is_ok());
+
+        let mut scenario = load();
+        scenario.lines[1].fleet.push("tram".to_string());
+        let err = scenario.validate().unwrap_err();
+        assert_eq!(err.to_string(), "line 1: unknown rolling stock class tram");
+        scenario.lines[1].fleet.pop();
+        scenario.rolling_stock[1].name = "long".to_string();
+        let err = scenario.validate().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "rolling stock long: the class is defined more than once"
+        );
+        scenario.rolling_stock[1].name = "short".to_string();
+        scenario.rolling_stock[0]
+            .energy
+            .as_mut()
+            .unwrap()
+            .regen_efficiency = 1.5;
+        let err = scenario.validate().unwrap_err();
+        assert!(
+            matches!(err, ScenarioError::InvalidRollingStock { .. }),
+            "{err}"
+        );
+
+        let mut scenario = load();
+        scenario.lines[1].segment_lengths = vec![800, 1200];
+        let err = scenario.validate().unwrap_err();
+        assert_eq!(err.to_string(), "line 1: segment lengths need kinematics");
+        scenario.lines[1].kinematics = Some(KinematicsConfig {
+            acceleration: 1.,
+            braking: 1.2,
+            max_speed: 22.,
+            speed_limits: vec![None, Some(15.)],
+        });
+        let err = scenario.validate().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 1: segment times cannot be given with kinematics"
+        );
+        scenario.lines[1].segment_times.clear();
+        assert!(scenario.validate().is_ok());
+        scenario.lines[1].kinematics.as_mut().unwrap().braking = 0.;
+        let err = scenario.validate().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 1: kinematic parameters must be positive"
+        );
+        scenario.lines[1].kinematics.as_mut().unwrap().braking = 1.2;
+        scenario.lines[1].segment_lengths.pop();
+        let err = scenario.validate().unwrap_err();
+        assert!(matches!(err, ScenarioError::SegmentCount { .. }), "{err}");
+        scenario.lines[1].segment_lengths.push(1200);
+        scenario.lines[1].signalling = Some(SignallingConfig::MovingBlock { margin: 50. });
+        assert!(scenario.validate().is_ok());
+        // trains 252 m apart at 22 m/s are less than a 30 s step
+        // apart, the dwell time at stations sets the headway
+        assert_eq!(scenario.lines::<()>()[1].min_headway(), 2);
+        scenario.lines[1].signalling = Some(SignallingConfig::MovingBlock { margin: -1. });
+        let err = scenario.validate().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 1: the moving block margin must not be negative"
+        );
+
+        let mut scenario = load();
+        scenario.lines[1].signalling = Some(SignallingConfig::MovingBlock { margin: 50. });
+        let err = scenario.validate().unwrap_err();
+        assert_eq!(err.to_string(), "line 1: a moving block needs kinematics");
+        scenario.lines[1].signalling = Some(SignallingConfig::FixedBlock(0));
+        let err = scenario.validate().unwrap_err();
+        assert_eq!(err.to_string(), "line 1: block length must be positive");
+
+        let mut scenario = load();
+        scenario.lines[0].segment_times[1] = 0;
+        let err = scenario.validate().unwrap_err();
+        assert_eq!(err.to_string(), "line 0: segment times must be positive");
+        scenario.lines[0].segment_times[1] = 6;
+        scenario.lines[0].station_time = 0;
+        let err = scenario.validate().unwrap_err();
+        assert_eq!(err.to_string(), "line 0: station_time must be positive");
+        scenario.lines[0].station_time = 2;
+        scenario.lines[0].split_len = 0;
+        let err = scenario.validate().unwrap_err();
+        assert_eq!(err.to_string(), "line 0: split_len must be positive");
+
+        let mut scenario = load();
+        scenario.lines[0].patterns[1].turnback = Some((0, 3));
+        let err = scenario.validate().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 0: turnback must be two different stations of the line"
+        );
+
+        let mut scenario = load();
+        scenario.lines[0].branches.push(BranchConfig {
+            junction: 0,
+            stations: vec![4],
+            segment_times: vec![6],
+            segment_lengths: Vec::new(),
+            speed_limits: Vec::new(),
+        });
+        let err = scenario.validate().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 0: branch junction must be an intermediate station of the line"
+        );
+        scenario.lines[0].branches[0].junction = 1;
+        assert!(scenario.validate().is_ok());
+        assert_eq!(scenario.lines[0].branch_stations(1), Some(vec![0, 1, 4]));
+        scenario.lines[0].branches[0].speed_limits = vec![Some(10.)];
+        let err = scenario.validate().unwrap_err();
+        assert_eq!(err.to_string(), "line 0: speed limits need kinematics");
+        scenario.lines[0].branches[0].speed_limits.clear();
+        scenario.lines[0].patterns[1].branch = 2;
+        let err = scenario.validate().unwrap_err();
+        assert_eq!(err.to_string(), "line 0: pattern branch is not on the line");
+
+        let mut scenario = load();
+        scenario.lines[0].circular = true;
+        assert!(matches!(
+            scenario.validate(),
+            Err(ScenarioError::NotAdjacent {
+                line: 0,
+                from: 2,
+                to: 0
+            })
+        ));
+
+        let mut scenario = load();
+        scenario.lines[0].failures.as_mut().unwrap().repair = (6, 2);
+        let err = scenario.validate().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 0: failure probability must be in [0, 1] and repair bounds ordered"
+        );
+
+        let mut scenario = load();
+        scenario.lines[0].blocks[0].stations = vec![0, 2];
+        let err = scenario.validate().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 0: a block needs a station or two consecutive stations of the line"
+        );
+        scenario.lines[0].blocks[0].stations = vec![2, 1];
+        assert!(scenario.validate().is_ok());
+        scenario.lines[0].blocks[0].end = 9.;
+        let err = scenario.validate().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 0: block start and end must be ordered and within the simulated time"
+        );
+
+        let mut scenario = load();
+        scenario.closures[0].stations = vec![0];
+        let err = scenario.validate().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "closure 0: a closure needs a station that is not a line end or two consecutive stations of a line"
+        );
+        scenario.closures[0].stations = vec![3, 4];
+        assert!(matches!(
+            scenario.validate(),
+            Err(ScenarioError::InvalidClosure { index: 0, .. })
+        ));
+        scenario.closures[0].stations = vec![4, 1];
+        assert!(scenario.validate().is_ok());
+        scenario.closures[0].start = 8.;
+        let err = scenario.validate().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "closure 0: closure start and end must be ordered and within the simulated time"
+        );
+
+        // a loop has no ends, any of its stations can close
+        let mut scenario = Scenario::from_file(LOOP_SCENARIO).unwrap();
+        scenario.closures.push(ClosureConfig {
+            stations: vec![0],
+            start: 7.,
+            end: 8.,
+        });
+        assert!(scenario.validate().is_ok());
+        scenario.closures[0].stations = vec![2];
+        assert!(matches!(
+            scenario.validate(),
+            Err(ScenarioError::InvalidClosure { index: 0, .. })
+        ));
+
+        let mut scenario = load();
+        scenario.lines[0].platforms[0].station = 3;
+        let err = scenario.validate().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 0: platforms need a station of the line and a positive count"
+        );
+
+        let mut scenario = load();
+        scenario.lines[0].patterns[2].skip = vec![2];
+        let err = scenario.validate().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 0: skipped stations must be intermediate stations of the line"
+        );
+    }
+
+    #[test]
+    fn test_unknown_format() {
+        let err = Scenario::from_file("scenario.yaml").unwrap_err();
+        assert!(matches!(err, ScenarioError::UnknownFormat(_)));
+        let err = Scenario::from_file("missing-scenario.toml").unwrap_err();
+        assert!(matches!(err, ScenarioError::Io(_)));
+    }
+}
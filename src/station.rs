@@ -3,8 +3,10 @@
 //! a simple implementation of those traits.
 
 use crate::car::Car;
-use crate::passenger::{callbacks, Passenger, PassengerNextDirectionIndex};
-use crate::routes::{MetroDirection, MetroInterchange};
+use crate::passenger::callbacks::{self, ActionContext};
+use crate::passenger::journey::Journey;
+use crate::passenger::{Passenger, PassengerNextDirectionIndex};
+use crate::routes::{ClosurePeriod, MetroDirection, MetroInterchange};
 use crate::utils::index_list;
 
 pub trait StationClass {
@@ -22,16 +24,20 @@ pub trait PassengerStation<T>: Send + Sync {
 
 /// Board passengers on given train
 pub trait BoardPassengers<T>: Send + Sync {
-    /// Board passenger on given ``Car``. Boarded passengers
+    /// Board passenger on given ``Car``; ctx describes the
+    /// current step and the train. Boarded passengers
     /// will no longer be on object
-    fn board_passengers(&mut self, c: &mut Car<T>);
+    fn board_passengers(&mut self, c: &mut Car<T>, ctx: &ActionContext);
 }
 
 /// Land passengers from train to current implementation
 pub trait LandPassenger<T>: Send + Sync {
-    /// Land passenger from given Car in trait implementation.
-    /// Landed train will no longer be inside given car
-    fn land_passenger(&mut self, c: &mut Car<T>);
+    /// Land passenger from given Car in trait implementation; ctx
+    /// describes the current step and the train.
+    /// Landed train will no longer be inside given car.
+    /// Return the number of passengers that reached their
+    /// final destination.
+    fn land_passenger(&mut self, c: &mut Car<T>, ctx: &ActionContext) -> usize;
 }
 
 #[must_use]
@@ -57,18 +63,32 @@ fn station_factory<'a, T>(
         direction,
         interchange,
         passengers,
+        denied: vec![0; total],
+        completed: Vec::new(),
+        closure: None,
+        stranded: 0,
+        rerouted: 0,
     }
 }
 
 /// Simple station implementation.
 /// Contains information about the
-/// station id, ``MetroDirection`` and ``MetroIntechage`` and
-/// the passenger list
+/// station id, ``MetroDirection`` and ``MetroIntechage``,
+/// the passenger list, the number of passengers
+/// left behind by full trains, for each direction,
+/// the journeys ended in this station, at the destination or
+/// stranded, and the closure in progress, whose routing
+/// replaces the network one.
 pub struct Station<'a, T> {
     index: usize,
     direction: &'a MetroDirection,
     interchange: &'a MetroInterchange,
     passengers: index_list::IndexList<Passenger<T>, PassengerNextDirectionIndex>,
+    denied: Vec<usize>,
+    completed: Vec<Journey>,
+    closure: Option<&'a ClosurePeriod>,
+    stranded: usize,
+    rerouted: usize,
 }
 
 impl<'a, T> Station<'a, T>
@@ -82,11 +102,113 @@ where
     }
 
     fn get_dir(&self, dst: usize) -> usize {
-        self.direction.get_direction(self.index, dst)
+        match self.closure {
+            Some(closure) => closure.get_direction().get_direction(self.index, dst),
+            None => self.direction.get_direction(self.index, dst),
+        }
     }
 
     fn get_inter(&self, dst: usize) -> usize {
-        self.interchange.next_station(self.index, dst)
+        match self.closure {
+            Some(closure) => closure.get_interchange().next_station(self.index, dst),
+            None => self.interchange.next_station(self.index, dst),
+        }
+    }
+
+    /// Route the passenger and add it to the waiting list. A passenger
+    /// in a closed station, or that cannot reach its destination from
+    /// here, is stranded at the given step and leaves the network.
+    fn queue_passenger(&mut self, p: Passenger<T>, step: u32) {
+        let p = self.set_directions(p);
+        if self.is_closed() || p.is_destination(self.index) {
+            self.stranded += 1;
+            let ctx = ActionContext::in_station(step, self.index);
+            self.completed.push(p.strand(&ctx).into_journey());
+        } else {
+            self.passengers.push(p);
+        }
+    }
+
+    /// Start or end a closure: the station takes the routing of the
+    /// given closure, or the network one, and routes again the waiting
+    /// passengers at the given step. Those that cannot continue are
+    /// stranded. Passengers keep the order they reached the platform in.
+    pub fn set_closure(&mut self, closure: Option<&'a ClosurePeriod>, step: u32) {
+        self.closure = closure;
+        let mut waiting = self.passengers.take_all();
+        waiting.sort_by_key(|p| p.get_journey().waiting_since());
+        for p in waiting {
+            let route = (p.get_next_direction(), p.get_next_stop());
+            let before = self.stranded;
+            let p = self.set_directions(p);
+            let changed = route != (p.get_next_direction(), p.get_next_stop());
+            self.queue_passenger(p, step);
+            if changed && self.stranded == before {
+                self.rerouted += 1;
+            }
+        }
+    }
+}
+
+impl<'a, T> Station<'a, T> {
+    /// Number of passengers waiting in the station.
+    #[must_use]
+    pub fn waiting_count(&self) -> usize {
+        self.passengers.len()
+    }
+
+    /// Number of passengers waiting for trains in the given
+    /// direction (identified by the terminus station).
+    #[must_use]
+    pub fn waiting_count_for(&self, direction: usize) -> usize {
+        self.passengers.get_list(direction).len()
+    }
+
+    /// Number of passengers left behind by full trains going
+    /// in the given direction (identified by the terminus station).
+    /// Each passenger is counted once for each train that could
+    /// not take them.
+    #[must_use]
+    pub fn denied_boardings(&self, direction: usize) -> usize {
+        self.denied[direction]
+    }
+
+    /// Number of passengers left behind by full trains in any direction.
+    #[must_use]
+    pub fn total_denied_boardings(&self) -> usize {
+        self.denied.iter().sum()
+    }
+
+    /// Check if the station is closed by the closure in progress.
+    #[must_use]
+    pub fn is_closed(&self) -> bool {
+        self.closure.is_some_and(|c| c.is_closed(self.index))
+    }
+
+    /// Number of passengers that left the network in this
+    /// station because a closure cut off their destination.
+    #[must_use]
+    pub fn stranded_count(&self) -> usize {
+        self.stranded
+    }
+
+    /// Number of waiting passengers that changed route
+    /// because a closure started or ended.
+    #[must_use]
+    pub fn rerouted_count(&self) -> usize {
+        self.rerouted
+    }
+
+    /// Journeys ended in this station and not yet taken.
+    #[must_use]
+    pub fn completed_journeys(&self) -> &[Journey] {
+        &self.completed
+    }
+
+    /// Take the journeys ended in this station, leaving
+    /// the station's list empty.
+    pub fn take_completed_journeys(&mut self) -> Vec<Journey> {
+        std::mem::take(&mut self.completed)
     }
 }
 
@@ -95,8 +217,8 @@ where
     T: callbacks::PassengerAction,
 {
     fn enter_passenger(&mut self, p: Passenger<T>) {
-        let p = self.set_directions(p).enter_station();
-        self.passengers.push(p);
+        let step = p.get_journey().get_origin().step;
+        self.queue_passenger(p.enter_station(), step);
     }
 }
 
@@ -104,10 +226,26 @@ impl<'a, T> BoardPassengers<T> for Station<'a, T>
 where
     T: callbacks::PassengerAction,
 {
-    fn board_passengers(&mut self, car: &mut Car<T>) {
-        let dst = car.get_destination();
-        let passengers = self.passengers.get_list_mut(dst);
-        car.board_passengers(passengers);
+    fn board_passengers(&mut self, car: &mut Car<T>, ctx: &ActionContext) {
+        if self.is_closed() {
+            return;
+        }
+        let directions = car.boarding_directions();
+        let left: Vec<usize> = directions
+            .iter()
+            .map(|dst| car.board_passengers(self.passengers.get_list_mut(*dst), ctx))
+            .collect();
+        // Boarding may keep the doors open longer.
+        if car.close_doors() {
+            for (dst, left) in directions.into_iter().zip(left) {
+                self.passengers
+                    .get_list_mut(dst)
+                    .iter_mut()
+                    .filter(|p| car.serves(p.get_next_stop()))
+                    .for_each(|p| p.deny_boarding(ctx));
+                self.denied[dst] += left;
+            }
+        }
     }
 }
 
@@ -115,13 +253,71 @@ impl<'a, T> LandPassenger<T> for Station<'a, T>
 where
     T: callbacks::PassengerAction,
 {
-    fn land_passenger(&mut self, c: &mut Car<T>) {
+    fn land_passenger(&mut self, c: &mut Car<T>, ctx: &ActionContext) -> usize {
         let passenger = c.unboard_passengers();
         let index = self.index;
-        passenger
+        let before = self.completed.len();
+        let completed = &mut self.completed;
+        let landed: Vec<Passenger<T>> = passenger
             .drain(..)
-            .map(Passenger::leave_train)
-            .filter(|p| !p.is_final_destination(index))
-            .for_each(|p| self.enter_passenger(p));
+            .map(|p| p.leave_train(ctx))
+            .filter_map(|p| {
+                if p.is_final_destination(index) {
+                    completed.push(p.arrive(ctx).into_journey());
+                    None
+                } else {
+                    Some(p.transfer(ctx))
+                }
+            })
+            .collect();
+        let delivered = self.completed.len() - before;
+        landed
+            .into_iter()
+            .for_each(|p| self.queue_passenger(p, ctx.step));
+        delivered
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::routes::{build_directions_from_lines, MetroLines};
+    use ndarray::Array2;
+
+    // Loop [0, 1, 2, 3], closed between 1 and 2.
+    fn make_loop() -> Array2<u32> {
+        let mut adj = Array2::from_elem((4, 4), u32::MAX);
+        adj.diag_mut().fill(0);
+        for (a, b) in [(0, 1), (1, 2), (2, 3), (3, 0)] {
+            adj[(a, b)] = 1;
+            adj[(b, a)] = 1;
+        }
+        adj
+    }
+
+    #[test]
+    fn test_closure_keeps_order() {
+        let adj = make_loop();
+        let terminus = [(0, 3)];
+        let lines = MetroLines::from_given_lines(vec![vec![0, 1, 2, 3]], &terminus).set_loop(0);
+        let (direction, interchange) = build_directions_from_lines(adj.clone(), &lines);
+        let closure = ClosurePeriod::new((0, 5), adj, &lines, vec![], &[(1, 2)]);
+        let mut station = station_list_factory::<()>(4, &direction, &interchange).remove(1);
+        for (id, dest, step) in [(0, 2, 1), (1, 0, 2), (2, 2, 3)] {
+            station.enter_passenger(Passenger::new(id, 1, dest, (), step));
+        }
+        assert_eq!(station.waiting_count_for(2), 2);
+
+        // everybody now leaves towards 0, in arrival order
+        station.set_closure(Some(&closure), 4);
+        assert_eq!(station.rerouted_count(), 2);
+        let ids: Vec<u32> = station
+            .passengers
+            .get_list(0)
+            .iter()
+            .map(Passenger::get_id)
+            .collect();
+        assert_eq!(ids, [0, 1, 2]);
     }
 }
@@ -4,9 +4,11 @@ mod simple_traffic_generator_factory;
 pub use simple_traffic_generator::SimpleTrafficGenerator;
 
 pub use simple_traffic_generator_factory::{
-    simple_traffic_generator_factory, SimpleTrafficGeneratorConfig,
+    simple_traffic_generator_factory, simple_traffic_generator_factory_with_rng,
+    SimpleTrafficGeneratorConfig,
 };
 
+use crate::utils::random::SimRng;
 use crate::Int;
 use crate::Node;
 
@@ -17,6 +19,13 @@ use crate::Node;
 pub trait TrafficGenerator: Send + Sync {
     /// Return number of passenger arriving at the station
     /// at request step. This are passengers going to a
-    /// specific destination.
-    fn next_traffic_flow(&self, step: Int) -> Int;
+    /// specific destination. Any randomness must be drawn
+    /// from the given generator.
+    fn next_traffic_flow(&self, step: Int, rng: &mut SimRng) -> Int;
+}
+
+impl<T: TrafficGenerator> TrafficGenerator for &T {
+    fn next_traffic_flow(&self, step: Int, rng: &mut SimRng) -> Int {
+        (**self).next_traffic_flow(step, rng)
+    }
 }
@@ -1,6 +1,5 @@
 use super::TrafficGenerator;
-use super::{Int, Node};
-use rand;
+use super::{Int, Node, SimRng};
 use rand_distr::{Distribution, Poisson};
 use splines::{Interpolation, Key, Spline};
 
@@ -28,16 +27,20 @@ impl SimpleTrafficGenerator {
     }
 
     // get the average number of passenger at given step.
-    fn get_passenger_probability_at(&self, step: Int) -> Node {
-        self.spline.sample(Node::from(step)).unwrap() * self.scale
+    // There is no value outside of the simulated time.
+    fn get_passenger_probability_at(&self, step: Int) -> Option<Node> {
+        self.spline
+            .sample(Node::from(step))
+            .map(|lambda| lambda * self.scale)
     }
 }
 
 impl TrafficGenerator for SimpleTrafficGenerator {
-    fn next_traffic_flow(&self, step: Int) -> Int {
-        let lambda = self.get_passenger_probability_at(step);
-        let passengers = sample_poisson(lambda);
-        passengers as Int
+    fn next_traffic_flow(&self, step: Int, rng: &mut SimRng) -> Int {
+        match self.get_passenger_probability_at(step) {
+            Some(lambda) if lambda > 0. => sample_poisson(lambda, rng) as Int,
+            _ => 0,
+        }
     }
 }
 
@@ -70,9 +73,9 @@ fn key_from_tuple<T, V>(t: (T, V)) -> Key<T, V> {
 
 /// draw a random number from poisson distribution
 /// with avg = lambda
-fn sample_poisson(lambda: Node) -> Node {
+fn sample_poisson(lambda: Node, rng: &mut SimRng) -> Node {
     let poi = Poisson::new(lambda).unwrap();
-    poi.sample(&mut rand::thread_rng())
+    poi.sample(rng)
 }
 
 /// create an anchor at given time with default value (1.0)
@@ -199,9 +202,12 @@ mod test {
         let steps = conf.get_time_steps();
         let stg = SimpleTrafficGenerator::new(conf);
         let res: Node = (0..steps)
-            .map(|i| stg.get_passenger_probability_at(i))
+            .map(|i| stg.get_passenger_probability_at(i).unwrap())
             .sum();
         let err = (res - (traffic as Node)).abs();
         assert!(err < 1e-8);
+
+        let mut rng = crate::utils::random::make_rng(Some(1));
+        assert_eq!(stg.next_traffic_flow(steps + 10, &mut rng), 0);
     }
 }
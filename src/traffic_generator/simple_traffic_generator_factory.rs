@@ -3,6 +3,7 @@
 
 use super::simple_traffic_generator as stg;
 use super::{Int, Node};
+use crate::utils::random;
 
 use rand::prelude::*;
 use std::collections::HashSet;
@@ -14,6 +15,7 @@ pub struct SimpleTrafficGeneratorConfig {
     anchors: Vec<(Int, Node)>,
     min_anchor: Node,
     max_anchor: Node,
+    seed: Option<u64>,
 }
 
 impl SimpleTrafficGeneratorConfig {
@@ -31,32 +33,64 @@ impl SimpleTrafficGeneratorConfig {
             anchors,
             min_anchor: levels.0,
             max_anchor: levels.1,
+            seed: None,
         }
     }
+
+    /// Seed the noise applied to the anchors, making
+    /// the generated traffic matrix reproducible.
+    #[must_use]
+    pub fn set_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
 }
 
+/// Build the traffic generator matrix. Noise is drawn from a generator
+/// initialized with the configuration seed, if any.
 #[must_use]
 pub fn simple_traffic_generator_factory(
     traffic: Vec<Vec<Int>>,
     config: &SimpleTrafficGeneratorConfig,
+) -> Vec<Vec<Option<stg::SimpleTrafficGenerator>>> {
+    let mut rng = random::make_rng(config.seed);
+    simple_traffic_generator_factory_with_rng(traffic, config, &mut rng)
+}
+
+/// Build the traffic generator matrix drawing the noise from
+/// the given generator. The configuration seed is ignored.
+#[must_use]
+pub fn simple_traffic_generator_factory_with_rng<R: Rng>(
+    traffic: Vec<Vec<Int>>,
+    config: &SimpleTrafficGeneratorConfig,
+    rng: &mut R,
 ) -> Vec<Vec<Option<stg::SimpleTrafficGenerator>>> {
     traffic
         .into_iter()
-        .map(|row| row.into_iter().map(|t| make_stg(t, config)).collect())
+        .map(|row| row.into_iter().map(|t| make_stg(t, config, rng)).collect())
         .collect()
 }
 
-fn make_stg(t: Int, conf: &SimpleTrafficGeneratorConfig) -> Option<stg::SimpleTrafficGenerator> {
+fn make_stg<R: Rng>(
+    t: Int,
+    conf: &SimpleTrafficGeneratorConfig,
+    rng: &mut R,
+) -> Option<stg::SimpleTrafficGenerator> {
     if t > 0 {
-        let conf = make_config(t, conf);
+        let conf = make_config(t, conf, rng);
         Some(stg::SimpleTrafficGenerator::new(conf))
     } else {
         None
     }
 }
 
-fn make_config(t: Int, conf: &SimpleTrafficGeneratorConfig) -> stg::SimpleTrafficGeneratorConfig {
+fn make_config<R: Rng>(
+    t: Int,
+    conf: &SimpleTrafficGeneratorConfig,
+    rng: &mut R,
+) -> stg::SimpleTrafficGeneratorConfig {
     let anchors = apply_noise(
+        rng,
         &conf.anchors,
         conf.begin,
         conf.end,
@@ -72,7 +106,8 @@ fn make_config(t: Int, conf: &SimpleTrafficGeneratorConfig) -> stg::SimpleTraffi
     }
 }
 
-fn apply_noise(
+fn apply_noise<R: Rng>(
+    rng: &mut R,
     anchors: &[(Int, Node)],
     time_begin: Int,
     time_end: Int,
@@ -84,6 +119,7 @@ fn apply_noise(
         .iter()
         .map(|a| {
             remap_tuple(
+                rng,
                 *a,
                 time_begin,
                 time_end,
@@ -97,7 +133,8 @@ fn apply_noise(
     anchors
 }
 
-fn remap_tuple(
+fn remap_tuple<R: Rng>(
+    rng: &mut R,
     a: (Int, Node),
     tb: Int,
     te: Int,
@@ -106,14 +143,13 @@ fn remap_tuple(
     selected: &mut HashSet<Int>,
 ) -> (Int, Node) {
     let (time, traffic) = a;
-    let mut rng = thread_rng();
-    let time = remap_time(&mut rng, tb, te, time, selected);
-    let traffic = remap_value(&mut rng, min_a, max_a, traffic);
+    let time = remap_time(rng, tb, te, time, selected);
+    let traffic = remap_value(rng, min_a, max_a, traffic);
     (time, traffic)
 }
 
-fn remap_time(
-    rng: &mut ThreadRng,
+fn remap_time<R: Rng>(
+    rng: &mut R,
     min: Int,
     max: Int,
     time: Int,
@@ -128,7 +164,7 @@ fn remap_time(
     }
 }
 
-fn remap_value(rng: &mut ThreadRng, min: Node, max: Node, mean: Node) -> Node {
+fn remap_value<R: Rng>(rng: &mut R, min: Node, max: Node, mean: Node) -> Node {
     let normal = rand_distr::Normal::new(mean, 1.).unwrap();
     normal
         .sample_iter(rng)
@@ -159,8 +195,11 @@ mod test {
 
         let time_begin = 5;
         let time_end = 21;
+        let mut rng = thread_rng();
         for _ in 0..10000 {
-            let res = apply_noise(&anchors, time_begin, time_end, min_anchor, max_anchor);
+            let res = apply_noise(
+                &mut rng, &anchors, time_begin, time_end, min_anchor, max_anchor,
+            );
             assert_eq!(res.len(), anchors.len());
             let mut prev: Option<Int> = None;
             for (t, a) in &res {
@@ -174,6 +213,18 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_seeded_noise() {
+        let anchors = vec![(6, 2.), (8, 10.), (12, 2.), (18, 6.)];
+        let mut rng_a = random::make_rng(Some(7));
+        let mut rng_b = random::make_rng(Some(7));
+        for _ in 0..100 {
+            let a = apply_noise(&mut rng_a, &anchors, 5, 21, 1., 14.);
+            let b = apply_noise(&mut rng_b, &anchors, 5, 21, 1., 14.);
+            assert_eq!(a, b);
+        }
+    }
+
     #[test]
     fn test_remap_value() {
         let mut rng = thread_rng();
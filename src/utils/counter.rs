@@ -57,6 +57,40 @@ impl Counter {
     pub fn reset(&mut self) {
         self.current = 0;
     }
+
+    /// Undo one step. Return false if the counter
+    /// is already in its initial state.
+    pub fn step_back(&mut self) -> bool {
+        if self.is_reset() {
+            false
+        } else {
+            self.current -= 1;
+            true
+        }
+    }
+
+    /// Return true if the counter is in its initial state.
+    #[must_use]
+    pub fn is_reset(&self) -> bool {
+        self.current == 0
+    }
+
+    /// Delay the end of the count by the given number of steps,
+    /// counting from the current step if the counter is done.
+    pub fn postpone(&mut self, steps: usize) {
+        self.start = self.start.max(self.current) + steps;
+    }
+
+    /// Raise the target value, if lower than the given one.
+    /// Return true if the target changed.
+    pub fn extend_to(&mut self, start: usize) -> bool {
+        if start > self.start {
+            self.start = start;
+            true
+        } else {
+            false
+        }
+    }
 }
 
 /// Cyclic Counter implementation.
@@ -140,6 +174,49 @@ mod test {
         assert_eq!(counter.current, 3);
     }
 
+    #[test]
+    fn test_counter_step_back() {
+        let mut counter = Counter::new(2);
+        assert!(counter.is_reset());
+        assert!(!counter.step_back());
+        counter.step();
+        assert!(!counter.is_reset());
+        assert!(counter.step_back());
+        assert!(counter.is_reset());
+
+        assert!(!counter.step());
+        assert!(!counter.step());
+        assert!(counter.is_done());
+        assert!(counter.step_back());
+        assert!(!counter.is_done());
+        assert!(!counter.step());
+        assert!(counter.is_done());
+    }
+
+    #[test]
+    fn test_counter_extend() {
+        let mut counter = Counter::new(1);
+        assert!(!counter.step());
+        assert!(counter.is_done());
+        assert!(counter.extend_to(3));
+        assert!(!counter.extend_to(2));
+        assert!(!counter.step());
+        assert!(!counter.step());
+        assert!(counter.step());
+    }
+
+    #[test]
+    fn test_counter_postpone() {
+        let mut counter = Counter::new(1);
+        counter.postpone(1);
+        assert!(!counter.step());
+        assert!(!counter.step());
+        assert!(counter.step());
+        counter.postpone(1);
+        assert!(!counter.step());
+        assert!(counter.step());
+    }
+
     #[test]
     fn test_cyclic_counter() {
         let mut counter = CyclicCounter::new(5);
@@ -74,6 +74,12 @@ where
         iter.for_each(|i| self.push(i));
     }
 
+    /// Remove all the items from the collection, sub
+    /// collection after sub collection.
+    pub fn take_all(&mut self) -> Vec<T> {
+        self.list.iter_mut().flat_map(std::mem::take).collect()
+    }
+
     pub fn len(&self) -> usize {
         self.list.iter().map(Vec::len).sum()
     }
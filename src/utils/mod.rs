@@ -6,6 +6,7 @@ pub mod cross_index_iterator;
 pub mod index_list;
 pub mod matrix_utils;
 pub mod mixed_iterator;
+pub mod random;
 pub mod unique_id;
 
 use std::collections::HashSet;
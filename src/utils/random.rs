@@ -0,0 +1,35 @@
+//! Random number generation shared by the whole simulation.
+//! A single seedable generator is passed around, so that
+//! two runs with the same seed produce the same results.
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// Random number generator used by the simulation.
+pub type SimRng = StdRng;
+
+/// Build a new generator. If a seed is given the generator
+/// is deterministic, otherwise it is seeded from the system entropy.
+#[must_use]
+pub fn make_rng(seed: Option<u64>) -> SimRng {
+    match seed {
+        Some(seed) => SimRng::seed_from_u64(seed),
+        None => SimRng::from_entropy(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn test_seeded_rng() {
+        let mut a = make_rng(Some(42));
+        let mut b = make_rng(Some(42));
+        for _ in 0..100 {
+            assert_eq!(a.gen::<u64>(), b.gen::<u64>());
+        }
+    }
+}
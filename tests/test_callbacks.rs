@@ -0,0 +1,135 @@
+use ndarray::arr2;
+use simtro::line::line_factory::{
+    line_factory, LineChunkConfig, LineChunkKind, LineFactoryConfig, LineInfoConfig,
+    StationInfoConfig,
+};
+use simtro::passenger::callbacks::{ActionContext, PassengerAction, PassengerActionFactory};
+use simtro::traffic_generator::TrafficGenerator;
+use simtro::utils::random::SimRng;
+use simtro::*;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Event {
+    Enter(usize),
+    Board(usize, usize),
+    Alight(usize, usize),
+    Transfer(usize),
+    Arrive(usize),
+}
+
+type EventLog = Arc<Mutex<Vec<(u32, u32, Event)>>>;
+
+#[derive(Debug)]
+struct Recorder {
+    log: EventLog,
+}
+
+impl Recorder {
+    fn push(&self, id: u32, ctx: &ActionContext, event: Event) {
+        self.log.lock().unwrap().push((id, ctx.step, event));
+    }
+}
+
+impl PassengerAction for Recorder {
+    fn enter_station(&mut self, id: u32, ctx: &ActionContext) {
+        assert!(ctx.line.is_none());
+        self.push(id, ctx, Event::Enter(ctx.station));
+    }
+
+    fn board(&mut self, id: u32, ctx: &ActionContext) {
+        assert!(ctx.direction.is_some());
+        self.push(id, ctx, Event::Board(ctx.station, ctx.line.unwrap()));
+    }
+
+    fn alight(&mut self, id: u32, ctx: &ActionContext) {
+        self.push(id, ctx, Event::Alight(ctx.station, ctx.line.unwrap()));
+    }
+
+    fn transfer(&mut self, id: u32, ctx: &ActionContext) {
+        self.push(id, ctx, Event::Transfer(ctx.station));
+    }
+
+    fn arrive(&mut self, id: u32, ctx: &ActionContext) {
+        self.push(id, ctx, Event::Arrive(ctx.station));
+    }
+}
+
+struct RecorderFactory {
+    log: EventLog,
+}
+
+impl PassengerActionFactory<Recorder> for RecorderFactory {
+    fn factory(&mut self) -> Recorder {
+        Recorder {
+            log: self.log.clone(),
+        }
+    }
+}
+
+/// Generate one passenger at the first step.
+struct OnePassenger;
+
+impl TrafficGenerator for OnePassenger {
+    fn next_traffic_flow(&self, step: u32, _: &mut SimRng) -> u32 {
+        u32::from(step == 0)
+    }
+}
+
+fn make_line<T: PassengerAction>(stations: [usize; 3]) -> line::Line<T> {
+    let station_info = stations
+        .iter()
+        .map(|s| StationInfoConfig::new(*s, 2))
+        .collect();
+    let line_info = (0..2)
+        .map(|_| LineInfoConfig::new(vec![LineChunkConfig::new(3, LineChunkKind::Double)]))
+        .collect();
+    line_factory(LineFactoryConfig::new(station_info, line_info, 5, 1, 1))
+}
+
+#[test]
+fn test_passenger_callbacks() {
+    let adj_mat = arr2(&[
+        [u32::MAX, 1, u32::MAX, u32::MAX, u32::MAX],
+        [1, u32::MAX, 1, 1, 1],
+        [u32::MAX, 1, u32::MAX, u32::MAX, u32::MAX],
+        [u32::MAX, 1, u32::MAX, u32::MAX, u32::MAX],
+        [u32::MAX, 1, u32::MAX, u32::MAX, u32::MAX],
+    ]);
+    let (_, dir, inter) = routes::build_directions(adj_mat, &[(0, 2), (3, 4)]);
+
+    let mut traffic: Vec<Vec<Option<OnePassenger>>> =
+        (0..5).map(|_| (0..5).map(|_| None).collect()).collect();
+    traffic[0][4] = Some(OnePassenger);
+    let pf = passenger::PassengerFactory::new(traffic);
+
+    let log = EventLog::default();
+    let stations = station::station_list_factory(5, &dir, &inter);
+    let lines = vec![make_line([0, 1, 2]), make_line([3, 1, 4])];
+    let tf = RecorderFactory { log: log.clone() };
+
+    let mut simulation = engine::Simulation::new(pf, stations, lines, tf);
+    simulation.run_until(|sim| !sim.journeys().is_empty() || sim.current_step() > 200);
+    assert_eq!(simulation.journeys().len(), 1);
+
+    let log = log.lock().unwrap();
+    let events: Vec<Event> = log.iter().map(|(_, _, e)| *e).collect();
+    assert_eq!(
+        events,
+        vec![
+            Event::Enter(0),
+            Event::Board(0, 0),
+            Event::Alight(1, 0),
+            Event::Transfer(1),
+            Event::Board(1, 1),
+            Event::Alight(4, 1),
+            Event::Arrive(4),
+        ]
+    );
+    assert!(log.iter().all(|(id, _, _)| *id == 0));
+    assert!(log.windows(2).all(|w| w[0].1 <= w[1].1));
+
+    let journey = &simulation.journeys()[0];
+    assert_eq!(journey.transfers(), 1);
+    assert_eq!(journey.get_arrival(), Some(log.last().unwrap().1));
+}
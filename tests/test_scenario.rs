@@ -0,0 +1,281 @@
+use simtro::scenario::Scenario;
+use simtro::*;
+
+const SCENARIO: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/scenarios/simple.toml");
+
+#[test]
+fn test_scenario_run() {
+    let scenario = Scenario::from_file(SCENARIO).unwrap();
+    assert_eq!(scenario.station_name(1), "Center");
+
+    let network = scenario.network();
+    let run = || {
+        engine::run(
+            scenario.steps(),
+            scenario.passenger_factory(),
+            network.stations::<()>(),
+            scenario.lines(),
+            (),
+            scenario.seed,
+        )
+    };
+
+    let report = run();
+    assert_eq!(report.len(), scenario.steps() as usize);
+    let summary = report.summary();
+    assert!(summary.generated > 0);
+    assert_eq!(summary.deadlocks, 0);
+    // the trains keep running, everybody reaches their destination
+    assert_eq!(summary.delivered, summary.generated);
+    assert_eq!(
+        summary.generated,
+        summary.delivered + summary.final_waiting + summary.final_on_board
+    );
+    assert_eq!(report, run());
+}
+
+const BRANCH_SCENARIO: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/scenarios/branch.toml");
+
+#[test]
+fn test_branch_scenario_run() {
+    let scenario = Scenario::from_file(BRANCH_SCENARIO).unwrap();
+    let network = scenario.network();
+    assert_eq!(network.get_direction().get_direction(0, 4), 4);
+    assert_eq!(network.get_direction().get_direction(0, 3), 3);
+
+    let mut simulation = engine::Simulation::new(
+        scenario.passenger_factory(),
+        network.stations::<()>(),
+        scenario.lines(),
+        (),
+    )
+    .set_seed(scenario.seed.unwrap());
+    simulation.run_for(scenario.steps());
+    let summary = simulation.report().summary();
+    assert_eq!(
+        summary.generated,
+        summary.delivered + summary.final_waiting + summary.final_on_board
+    );
+    // both branches carry passengers
+    for end in [3, 4] {
+        assert!(simulation
+            .journeys()
+            .iter()
+            .any(|j| j.is_complete() && j.get_destination() == end));
+    }
+}
+
+const LOOP_SCENARIO: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/scenarios/loop.toml");
+
+#[test]
+fn test_loop_scenario_run() {
+    let scenario = Scenario::from_file(LOOP_SCENARIO).unwrap();
+    let network = scenario.network();
+    let direction = network.get_direction();
+    // on the loop the direction is the next stop on the shorter way
+    assert_eq!(direction.get_direction(0, 1), 1);
+    assert_eq!(direction.get_direction(0, 3), 4);
+    assert_eq!(direction.get_direction(4, 1), 0);
+    assert_eq!(direction.get_direction(1, 5), 2);
+
+    let mut simulation = engine::Simulation::new(
+        scenario.passenger_factory(),
+        network.stations::<()>(),
+        scenario.lines(),
+        (),
+    )
+    .set_seed(scenario.seed.unwrap());
+    simulation.run_for(scenario.steps());
+    let summary = simulation.report().summary();
+    assert_eq!(
+        summary.generated,
+        summary.delivered + summary.final_waiting + summary.final_on_board
+    );
+    for station in 0..scenario.station_count() {
+        assert!(simulation
+            .journeys()
+            .iter()
+            .any(|j| j.is_complete() && j.get_destination() == station));
+    }
+}
+
+#[test]
+fn test_loop_skip_rejected() {
+    // passengers on a loop wait for the next station,
+    // trains skipping it would leave them behind
+    let mut scenario = Scenario::from_file(LOOP_SCENARIO).unwrap();
+    scenario.lines[0].patterns.push(scenario::PatternConfig {
+        turnback: None,
+        skip: vec![1, 3],
+        branch: 0,
+    });
+    let err = scenario.validate().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "line 0: a circular line cannot skip stations"
+    );
+}
+
+const ENERGY_SCENARIO: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/scenarios/energy.toml");
+
+#[test]
+fn test_energy_scenario_run() {
+    let scenario = Scenario::from_file(ENERGY_SCENARIO).unwrap();
+    let network = scenario.network();
+    let mut simulation = engine::Simulation::new(
+        scenario.passenger_factory(),
+        network.stations::<()>(),
+        scenario.lines(),
+        (),
+    )
+    .set_seed(scenario.seed.unwrap());
+    simulation.run_for(scenario.steps());
+    let summary = simulation.report().summary();
+    assert!(summary.delivered > 0);
+    assert!(summary.traction > summary.regenerated);
+
+    let energy = report::EnergyReport::new(
+        simulation.lines(),
+        simulation.report(),
+        scenario.steps_per_hour(),
+    );
+    assert_eq!(energy.hours.len(), 3);
+    let by_train: f64 = energy.trains.iter().map(|t| t.energy.get_traction()).sum();
+    let by_hour: f64 = energy.hours.iter().map(|h| h.get_traction()).sum();
+    assert!((by_train - energy.lines[0].get_traction()).abs() < 1e-6);
+    assert!((by_hour - summary.traction).abs() < 1e-6);
+    // only the long trains recover braking energy
+    for train in &energy.trains {
+        let regenerated = train.energy.get_regenerated() > 0.;
+        assert_eq!(regenerated, train.class.as_deref() == Some("long"));
+    }
+}
+
+#[test]
+fn test_disruption_run() {
+    let mut scenario = Scenario::from_file(LOOP_SCENARIO).unwrap();
+    scenario.lines[0].failures = Some(scenario::FailureConfig {
+        probability: 0.01,
+        repair: (4, 10),
+    });
+    scenario.lines[0].blocks.push(scenario::BlockConfig {
+        stations: vec![1, 2],
+        start: 7.,
+        end: 7.5,
+        direction: None,
+    });
+    scenario.validate().unwrap();
+    let network = scenario.network();
+    let mut simulation = engine::Simulation::new(
+        scenario.passenger_factory(),
+        network.stations::<()>(),
+        scenario.lines(),
+        (),
+    )
+    .set_seed(scenario.seed.unwrap());
+    simulation.run_for(scenario.steps());
+
+    let delays = report::line_delays(simulation.lines());
+    assert!(delays.iter().all(|d| d.line == 0));
+    let caused = |cause| delays.iter().any(|d| d.delay.get_cause() == cause);
+    assert!(caused(line::disruption::DelayCause::Failure));
+    assert!(caused(line::disruption::DelayCause::Blocked));
+    // nothing enters the blocked link during the block
+    let (start, end) = (scenario.step_at(7.), scenario.step_at(7.5));
+    assert!(delays
+        .iter()
+        .filter(|d| d.delay.get_cause() == line::disruption::DelayCause::Blocked)
+        .all(|d| d.delay.get_start() >= start && d.delay.get_start() + d.delay.get_steps() <= end));
+}
+
+#[test]
+fn test_closure_run() {
+    let mut scenario = Scenario::from_file(LOOP_SCENARIO).unwrap();
+    scenario.closures = vec![
+        scenario::ClosureConfig {
+            stations: vec![1],
+            start: 7.,
+            end: 8.,
+        },
+        scenario::ClosureConfig {
+            stations: vec![3, 4],
+            start: 7.5,
+            end: 9.,
+        },
+    ];
+    scenario.validate().unwrap();
+    let network = scenario.network();
+    // closed station, both closures, closed link
+    assert_eq!(network.get_closures().len(), 3);
+    let mut simulation = engine::Simulation::new(
+        scenario.passenger_factory(),
+        network.stations::<()>(),
+        scenario.lines(),
+        (),
+    )
+    .set_closures(network.get_closures())
+    .set_seed(scenario.seed.unwrap());
+    simulation.run_for(scenario.steps());
+
+    let summary = simulation.report().summary();
+    assert!(summary.rerouted > 0);
+    assert!(summary.stranded > 0);
+    assert_eq!(
+        summary.generated,
+        summary.delivered + summary.stranded + summary.final_waiting + summary.final_on_board
+    );
+    let journeys = simulation.journeys();
+    assert_eq!(
+        journeys.iter().filter(|j| j.is_stranded()).count(),
+        summary.stranded
+    );
+    assert!(journeys
+        .iter()
+        .all(|j| !(j.is_complete() && j.is_stranded())));
+    // nobody gets on or off at the closed station
+    let (start, end) = (scenario.step_at(7.) as u32, scenario.step_at(8.) as u32);
+    assert!(simulation.journeys().iter().all(|j| j
+        .get_boardings()
+        .iter()
+        .chain(j.get_alightings())
+        .all(|e| e.station != 1 || e.step < start || e.step >= end)));
+}
+
+#[test]
+fn test_deadlock_run() {
+    let mut scenario = Scenario::from_file(SCENARIO).unwrap();
+    // the depots hold more trains than the platforms of the line
+    assert!(scenario.lines.iter().all(|line| line.depo_size == 4));
+    let network = scenario.network();
+    let mut simulation = engine::Simulation::new(
+        scenario.passenger_factory(),
+        network.stations::<()>(),
+        scenario.lines(),
+        (),
+    );
+    assert!(simulation.try_run_for(scenario.steps()).is_ok());
+
+    // without the priority trains meet on the single track
+    for line in &mut scenario.lines {
+        line.passing_loop_priority = false;
+    }
+    let mut simulation = engine::Simulation::new(
+        scenario.passenger_factory(),
+        network.stations::<()>(),
+        scenario.lines(),
+        (),
+    );
+    let err = simulation.try_run_for(scenario.steps()).unwrap_err();
+    let deadlock = err.get_deadlock();
+    assert_eq!(simulation.current_step() as usize, deadlock.get_step() + 1);
+    assert_eq!(
+        simulation.lines()[err.get_line()].get_deadlocks()[0],
+        *deadlock
+    );
+    assert!(simulation.report().summary().deadlocks > 0);
+    let delays = report::line_delays(simulation.lines());
+    assert!(delays
+        .iter()
+        .filter(|d| d.line == err.get_line())
+        .any(|d| d.delay.get_cause() == line::disruption::DelayCause::Deadlock));
+}
@@ -1,6 +1,10 @@
-use ndarray::arr2;
+use ndarray::{arr2, Array2};
 use simtro::*;
 
+const BEGIN: u32 = 5;
+const END: u32 = 22;
+const RESOLUTION: u32 = 2;
+
 #[test]
 fn test_run() {
     let traffic = vec![
@@ -49,5 +53,139 @@ fn test_run() {
     let line_b = line::fast_line_factory::fast_line_factory(fast_line_config, total_station_count);
     let lines = vec![line_a, line_b];
 
-    engine::engine(steps, &pf, stations, lines, ());
+    let report = engine::engine(steps, &pf, stations, lines, ());
+    assert_eq!(report.len(), steps as usize);
+}
+
+#[test]
+fn test_step_by_step() {
+    let adj_mat = make_adjacent_matrix();
+    let total_station_count = adj_mat.ncols();
+    let (_, dir, inter) = routes::build_directions(adj_mat, &[(0, 2), (3, 4)]);
+
+    let pf = make_passenger_factory();
+    let stations = station::station_list_factory(5, &dir, &inter);
+    let lines = make_lines(total_station_count);
+
+    let mut simulation = engine::Simulation::new(pf, stations, lines, ());
+    assert_eq!(simulation.current_step(), 0);
+
+    let step = simulation.step();
+    assert_eq!(step.get_step(), 0);
+    assert_eq!(step.get_waiting().len(), 5);
+    assert_eq!(step.get_on_board().len(), 2);
+
+    simulation.run_for(9);
+    assert_eq!(simulation.current_step(), 10);
+    assert_eq!(simulation.report().len(), 10);
+
+    let performed = simulation.run_until(|sim| sim.current_step() == 25);
+    assert_eq!(performed, 15);
+    let performed = simulation.run_until(|sim| sim.current_step() == 25);
+    assert_eq!(performed, 0);
+
+    let waiting: usize = simulation
+        .stations()
+        .iter()
+        .map(station::Station::waiting_count)
+        .sum();
+    let on_board: usize = simulation
+        .lines()
+        .iter()
+        .map(line::Line::passenger_count)
+        .sum();
+    let last = simulation.report().get_steps().last().unwrap();
+    assert_eq!(last.total_waiting(), waiting);
+    assert_eq!(last.total_on_board(), on_board);
+
+    let delivered: usize = simulation
+        .report()
+        .get_steps()
+        .iter()
+        .map(|s| s.get_delivered())
+        .sum();
+    assert_eq!(simulation.journeys().len(), delivered);
+    for journey in simulation.journeys() {
+        let travel_time = journey.travel_time().unwrap();
+        assert_eq!(travel_time, journey.wait_time() + journey.in_vehicle_time());
+    }
+
+    let report = simulation.into_report();
+    assert_eq!(report.len(), 25);
+}
+
+#[test]
+fn test_reproducible_run() {
+    let adj_mat = make_adjacent_matrix();
+    let total_station_count = adj_mat.ncols();
+    let (_, dir, inter) = routes::build_directions(adj_mat, &[(0, 2), (3, 4)]);
+    let steps = get_steps(BEGIN, END, RESOLUTION);
+
+    let run = |seed| {
+        let pf = make_seeded_passenger_factory(seed);
+        let stations = station::station_list_factory(5, &dir, &inter);
+        let lines = make_lines(total_station_count);
+        engine::run(steps, pf, stations, lines, (), Some(seed))
+    };
+
+    let first = run(1234);
+    let second = run(1234);
+    assert_eq!(first, second);
+}
+
+fn make_adjacent_matrix() -> Array2<u32> {
+    arr2(&[
+        [u32::MAX, 1, u32::MAX, u32::MAX, u32::MAX],
+        [1, u32::MAX, 1, 1, 1],
+        [u32::MAX, 1, u32::MAX, u32::MAX, u32::MAX],
+        [u32::MAX, 1, u32::MAX, u32::MAX, u32::MAX],
+        [u32::MAX, 1, u32::MAX, u32::MAX, u32::MAX],
+    ])
+}
+
+type PassengerFactory = passenger::PassengerFactory<traffic_generator::SimpleTrafficGenerator>;
+
+fn make_passenger_factory() -> PassengerFactory {
+    let stgc = make_traffic_generator_config();
+    let stg = traffic_generator::simple_traffic_generator_factory(make_traffic(), &stgc);
+    passenger::PassengerFactory::new(stg)
+}
+
+fn make_seeded_passenger_factory(seed: u64) -> PassengerFactory {
+    let stgc = make_traffic_generator_config().set_seed(seed);
+    let stg = traffic_generator::simple_traffic_generator_factory(make_traffic(), &stgc);
+    passenger::PassengerFactory::new(stg)
+}
+
+fn make_traffic() -> Vec<Vec<u32>> {
+    vec![
+        vec![0, 10, 5, 4, 3],
+        vec![5, 0, 12, 3, 3],
+        vec![5, 11, 0, 6, 3],
+        vec![4, 4, 7, 0, 1],
+        vec![4, 4, 7, 2, 0],
+    ]
+}
+
+fn make_traffic_generator_config() -> traffic_generator::SimpleTrafficGeneratorConfig {
+    let anchors = vec![];
+    let min_anchor = 1.;
+    let max_anchor = 10.;
+
+    traffic_generator::SimpleTrafficGeneratorConfig::new(
+        (BEGIN, END),
+        RESOLUTION,
+        anchors,
+        (min_anchor, max_anchor),
+    )
+}
+
+fn make_lines(total_station_count: usize) -> Vec<line::Line<()>> {
+    let fast_line_config =
+        line::fast_line_factory::FastLineFactoryConfig::new(0..=2, 5, [6, 6], 7, 4, 11);
+    let line_a = line::fast_line_factory::fast_line_factory(fast_line_config, total_station_count);
+    let fast_line_config =
+        line::fast_line_factory::FastLineFactoryConfig::new([3, 1, 4], 5, [6, 6], 7, 4, 11);
+    let line_b = line::fast_line_factory::fast_line_factory(fast_line_config, total_station_count);
+    vec![line_a, line_b]
 }